@@ -0,0 +1,148 @@
+use std::rc::Rc;
+
+//Operands index into the chunk's constant pool, the current frame's stack
+//slots, or the closure's upvalues depending on the instruction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpCode{
+    Constant(usize),
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal(usize),
+    SetLocal(usize),
+    GetGlobal(usize),
+    DefineGlobal(usize),
+    SetGlobal(usize),
+    GetUpvalue(usize),
+    SetUpvalue(usize),
+    GetProperty(usize),
+    SetProperty(usize),
+    GetSuper(usize),
+    GetIndex,
+    SetIndex,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    IntDivide,
+    Power,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Not,
+    Negate,
+    BitNot,
+    Print,
+    Jump(usize),
+    JumpIfFalse(usize),
+    Loop(usize),
+    Call(usize),
+    Closure(usize),
+    CloseUpvalue,
+    Return,
+    Class(usize),
+    Inherit,
+    Method(usize),
+    BuildList(usize),
+    BuildMap(usize),
+    //Calls toString() on an instance whose class has one, anything else is left alone
+    ToString,
+    BuildString(usize)
+}
+
+//Values the compiler can produce ahead of time
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant{
+    Number(f64),
+    Int(i64),
+    String(Rc<str>),
+    Function(Rc<Function>)
+}
+
+//Where a closure finds each variable it captures when it is created:
+//a slot in the enclosing frame, or one of the enclosing closure's upvalues
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UpvalueRef{
+    pub index: usize,
+    pub is_local: bool
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Function{
+    pub name: String,
+    pub arity: usize,
+    pub upvalues: Vec<UpvalueRef>,
+    pub chunk: Chunk
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Chunk{
+    pub code: Vec<OpCode>,
+    //line and column of the source that produced each instruction
+    pub positions: Vec<(usize, i64)>,
+    pub constants: Vec<Constant>
+}
+
+impl Chunk{
+    pub fn new() -> Self{
+        Chunk::default()
+    }
+
+    pub fn write(&mut self, op: OpCode, line: usize, col: i64) -> usize{
+        self.code.push(op);
+        self.positions.push((line, col));
+        return self.code.len() - 1;
+    }
+
+    //Strings are interned per chunk so repeated names share one entry
+    pub fn add_constant(&mut self, constant: Constant) -> usize{
+        if let Constant::String(_) = constant{
+            if let Some(index) = self.constants.iter().position(|existing| *existing == constant){
+                return index;
+            }
+        }
+        self.constants.push(constant);
+        return self.constants.len() - 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_records_position() {
+        let mut chunk = Chunk::new();
+        let first = chunk.write(OpCode::Nil, 1, 4);
+        let second = chunk.write(OpCode::Return, 2, 7);
+
+        assert_eq!((first, second), (0, 1));
+        assert_eq!(chunk.code, vec![OpCode::Nil, OpCode::Return]);
+        assert_eq!(chunk.positions, vec![(1, 4), (2, 7)]);
+    }
+
+    #[test]
+    fn test_constants_are_deduplicated() {
+        let mut chunk = Chunk::new();
+        let name = chunk.add_constant(Constant::String(Rc::from("x")));
+        let number = chunk.add_constant(Constant::Number(1.5));
+        let same_name = chunk.add_constant(Constant::String(Rc::from("x")));
+        let function = chunk.add_constant(Constant::Function(Rc::new(Function::default())));
+        let other_function = chunk.add_constant(Constant::Function(Rc::new(Function::default())));
+
+        assert_eq!(name, same_name);
+        assert_ne!(name, number);
+        assert_ne!(function, other_function, "Expected every function to get its own entry");
+        assert_eq!(chunk.constants.len(), 4);
+    }
+}
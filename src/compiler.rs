@@ -0,0 +1,686 @@
+use std::rc::Rc;
+
+use crate::chunk::*;
+use crate::diagnostics::{Diagnostic, DiagnosticKind};
+use crate::expr::*;
+use crate::stmt::Stmt;
+
+//Single pass from the resolved AST to bytecode. The resolver has already
+//rejected the programs this could choke on (stray returns, reading a local
+//in its own initializer, 'this' outside a class), so compiling only fails
+//on nodes the parser left behind after an error.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionKind{
+    Script,
+    Function,
+    Method,
+    Initializer
+}
+
+#[derive(Debug, Clone)]
+struct Local{
+    name: String,
+    depth: usize,
+    is_captured: bool
+}
+
+#[derive(Debug, Clone, Default)]
+struct LoopState{
+    //locals declared before the loop body, everything above is popped on break/continue
+    local_count: usize,
+    break_jumps: Vec<usize>,
+    continue_jumps: Vec<usize>
+}
+
+#[derive(Debug, Clone)]
+struct FunctionCompiler{
+    function: Function,
+    kind: FunctionKind,
+    locals: Vec<Local>,
+    scope_depth: usize,
+    loops: Vec<LoopState>
+}
+
+impl FunctionCompiler{
+    fn new(name: String, kind: FunctionKind) -> Self{
+        //Slot 0 holds the callee, or the receiver for methods
+        let receiver = match kind{
+            FunctionKind::Method | FunctionKind::Initializer => format!("this"),
+            _ => format!("")
+        };
+        FunctionCompiler {
+            function: Function { name: name, arity: 0, upvalues: Vec::new(), chunk: Chunk::new() },
+            kind: kind,
+            locals: vec![Local { name: receiver, depth: 0, is_captured: false }],
+            scope_depth: 0,
+            loops: Vec::new()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileError{
+    message: String,
+    line: usize,
+    column: i64
+}
+
+impl CompileError{
+    pub fn return_error(&self) -> String{
+        return self.message.clone();
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic{
+        return Diagnostic::new(DiagnosticKind::Compile, self.message.clone(), self.line, self.column);
+    }
+}
+
+pub struct Compiler{
+    frames: Vec<FunctionCompiler>,
+    line: usize,
+    col: i64
+}
+
+pub fn compile(statements: &[Stmt]) -> Result<Rc<Function>, CompileError>{
+    let mut compiler = Compiler{
+        frames: vec![FunctionCompiler::new(format!("<script>"), FunctionKind::Script)],
+        line: 0,
+        col: 0
+    };
+    for stmt in statements{
+        compiler.statement(stmt)?;
+    }
+    compiler.emit(OpCode::Nil);
+    compiler.emit(OpCode::Return);
+    let script = compiler.frames.pop().unwrap();
+    return Ok(Rc::new(script.function));
+}
+
+impl Compiler{
+    fn current(&mut self) -> &mut FunctionCompiler{
+        return self.frames.last_mut().unwrap();
+    }
+
+    fn chunk(&mut self) -> &mut Chunk{
+        return &mut self.current().function.chunk;
+    }
+
+    fn emit(&mut self, op: OpCode) -> usize{
+        let (line, col) = (self.line, self.col);
+        return self.chunk().write(op, line, col);
+    }
+
+    fn mark(&mut self, line: usize, col: i64){
+        self.line = line;
+        self.col = col;
+    }
+
+    fn patch_jump(&mut self, at: usize){
+        let target = self.chunk().code.len();
+        match self.chunk().code[at]{
+            OpCode::Jump(_) => self.chunk().code[at] = OpCode::Jump(target),
+            OpCode::JumpIfFalse(_) => self.chunk().code[at] = OpCode::JumpIfFalse(target),
+            other => panic!("Cannot patch {:?} as a jump", other)
+        }
+    }
+
+    fn identifier(&mut self, name: &str) -> usize{
+        return self.chunk().add_constant(Constant::String(Rc::from(name)));
+    }
+
+    fn begin_scope(&mut self){
+        self.current().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self){
+        self.current().scope_depth -= 1;
+        loop{
+            let frame = self.current();
+            let captured = match frame.locals.last(){
+                Some(local) if local.depth > frame.scope_depth => local.is_captured,
+                _ => break
+            };
+            frame.locals.pop();
+            if captured{
+                self.emit(OpCode::CloseUpvalue);
+            }
+            else{
+                self.emit(OpCode::Pop);
+            }
+        }
+    }
+
+    //Pops the locals a jump leaves behind without forgetting them, the code
+    //after the jump still sees them in scope
+    fn discard_locals(&mut self, keep: usize){
+        let captured: Vec<bool> = self.current().locals[keep..].iter().rev().map(|local| local.is_captured).collect();
+        for is_captured in captured{
+            if is_captured{
+                self.emit(OpCode::CloseUpvalue);
+            }
+            else{
+                self.emit(OpCode::Pop);
+            }
+        }
+    }
+
+    fn add_local(&mut self, name: &str){
+        let frame = self.current();
+        let depth = frame.scope_depth;
+        frame.locals.push(Local { name: name.to_string(), depth: depth, is_captured: false });
+    }
+
+    fn declare_variable(&mut self, name: &str){
+        if self.current().scope_depth > 0{
+            self.add_local(name);
+        }
+    }
+
+    //Locals are already sitting in their slot, globals get copied off the stack
+    fn define_variable(&mut self, name: &str){
+        if self.current().scope_depth == 0{
+            let index = self.identifier(name);
+            self.emit(OpCode::DefineGlobal(index));
+        }
+    }
+
+    fn resolve_local(&self, frame: usize, name: &str) -> Option<usize>{
+        return self.frames[frame].locals.iter().rposition(|local| local.name == name);
+    }
+
+    fn resolve_upvalue(&mut self, frame: usize, name: &str) -> Option<usize>{
+        if frame == 0{
+            return None;
+        }
+        if let Some(local) = self.resolve_local(frame - 1, name){
+            self.frames[frame - 1].locals[local].is_captured = true;
+            return Some(self.add_upvalue(frame, local, true));
+        }
+        if let Some(upvalue) = self.resolve_upvalue(frame - 1, name){
+            return Some(self.add_upvalue(frame, upvalue, false));
+        }
+        return None;
+    }
+
+    fn add_upvalue(&mut self, frame: usize, index: usize, is_local: bool) -> usize{
+        let upvalue = UpvalueRef { index: index, is_local: is_local };
+        let upvalues = &mut self.frames[frame].function.upvalues;
+        if let Some(existing) = upvalues.iter().position(|known| *known == upvalue){
+            return existing;
+        }
+        upvalues.push(upvalue);
+        return upvalues.len() - 1;
+    }
+
+    fn get_variable(&mut self, name: &str){
+        let frame = self.frames.len() - 1;
+        if let Some(slot) = self.resolve_local(frame, name){
+            self.emit(OpCode::GetLocal(slot));
+        }
+        else if let Some(upvalue) = self.resolve_upvalue(frame, name){
+            self.emit(OpCode::GetUpvalue(upvalue));
+        }
+        else{
+            let index = self.identifier(name);
+            self.emit(OpCode::GetGlobal(index));
+        }
+    }
+
+    fn set_variable(&mut self, name: &str){
+        let frame = self.frames.len() - 1;
+        if let Some(slot) = self.resolve_local(frame, name){
+            self.emit(OpCode::SetLocal(slot));
+        }
+        else if let Some(upvalue) = self.resolve_upvalue(frame, name){
+            self.emit(OpCode::SetUpvalue(upvalue));
+        }
+        else{
+            let index = self.identifier(name);
+            self.emit(OpCode::SetGlobal(index));
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<(), CompileError>{
+        match stmt{
+            Stmt::Expr { expression } => {
+                self.expression(expression)?;
+                self.emit(OpCode::Pop);
+            }
+            Stmt::Print { expression } => {
+                self.expression(expression)?;
+                self.emit(OpCode::Print);
+            }
+            Stmt::Var { name, line, column, initializer } => {
+                self.mark(*line, *column);
+                match initializer{
+                    Some(value) => self.expression(value)?,
+                    None => {
+                        self.emit(OpCode::Nil);
+                    }
+                }
+                self.declare_variable(name);
+                self.define_variable(name);
+            }
+            Stmt::Block { statements } => {
+                self.begin_scope();
+                for statement in statements{
+                    self.statement(statement)?;
+                }
+                self.end_scope();
+            }
+            Stmt::If { condition, then_branch, else_branch } => {
+                self.expression(condition)?;
+                let then_jump = self.emit(OpCode::JumpIfFalse(0));
+                self.emit(OpCode::Pop);
+                self.statement(then_branch)?;
+                let else_jump = self.emit(OpCode::Jump(0));
+                self.patch_jump(then_jump);
+                self.emit(OpCode::Pop);
+                if let Some(else_branch) = else_branch{
+                    self.statement(else_branch)?;
+                }
+                self.patch_jump(else_jump);
+            }
+            Stmt::While { condition, body, increment } => {
+                let loop_start = self.chunk().code.len();
+                self.expression(condition)?;
+                let exit_jump = self.emit(OpCode::JumpIfFalse(0));
+                self.emit(OpCode::Pop);
+                let local_count = self.current().locals.len();
+                self.current().loops.push(LoopState { local_count: local_count, ..LoopState::default() });
+                self.statement(body)?;
+                let state = self.current().loops.pop().unwrap();
+                for jump in state.continue_jumps{
+                    self.patch_jump(jump);
+                }
+                if let Some(increment) = increment{
+                    self.expression(increment)?;
+                    self.emit(OpCode::Pop);
+                }
+                self.emit(OpCode::Loop(loop_start));
+                self.patch_jump(exit_jump);
+                self.emit(OpCode::Pop);
+                //the condition was already popped when a break runs, so it lands past the exit pop
+                for jump in state.break_jumps{
+                    self.patch_jump(jump);
+                }
+            }
+            Stmt::Break { keyword } => {
+                self.mark(keyword.line, keyword.column);
+                let keep = self.current().loops.last().unwrap().local_count;
+                self.discard_locals(keep);
+                let jump = self.emit(OpCode::Jump(0));
+                self.current().loops.last_mut().unwrap().break_jumps.push(jump);
+            }
+            Stmt::Continue { keyword } => {
+                self.mark(keyword.line, keyword.column);
+                let keep = self.current().loops.last().unwrap().local_count;
+                self.discard_locals(keep);
+                let jump = self.emit(OpCode::Jump(0));
+                self.current().loops.last_mut().unwrap().continue_jumps.push(jump);
+            }
+            Stmt::Function { name, line: _ , column: _ , parameters, body } => {
+                self.declare_variable(name);
+                self.function(name.clone(), parameters, body, FunctionKind::Function)?;
+                self.define_variable(name);
+            }
+            Stmt::Return { keyword, value } => {
+                self.mark(keyword.line, keyword.column);
+                if self.current().kind == FunctionKind::Initializer{
+                    self.emit(OpCode::GetLocal(0));
+                }
+                else{
+                    match value{
+                        Some(value) => self.expression(value)?,
+                        None => {
+                            self.emit(OpCode::Nil);
+                        }
+                    }
+                }
+                self.emit(OpCode::Return);
+            }
+            Stmt::Class { name, superclass, methods } => {
+                self.class(name, superclass, methods)?;
+            }
+            Stmt::Throw { keyword, value: _ } | Stmt::Try { keyword, body: _ , catch_name: _ , catch_body: _ , finally_body: _ } => {
+                self.mark(keyword.line, keyword.column);
+                return Err(self.error(format!("Exceptions are not supported by the VM")));
+            }
+        }
+        return Ok(());
+    }
+
+    fn function(&mut self, name: String, parameters: &[crate::scanner::Token], body: &[Stmt], kind: FunctionKind) -> Result<(), CompileError>{
+        self.frames.push(FunctionCompiler::new(name, kind));
+        self.begin_scope();
+        self.current().function.arity = parameters.len();
+        for param in parameters{
+            let param_name = String::from_utf8(param.lexeme.clone()).unwrap();
+            self.add_local(&param_name);
+        }
+        for stmt in body{
+            self.statement(stmt)?;
+        }
+        if kind == FunctionKind::Initializer{
+            self.emit(OpCode::GetLocal(0));
+        }
+        else{
+            self.emit(OpCode::Nil);
+        }
+        self.emit(OpCode::Return);
+        let compiled = self.frames.pop().unwrap();
+        let index = self.chunk().add_constant(Constant::Function(Rc::new(compiled.function)));
+        self.emit(OpCode::Closure(index));
+        return Ok(());
+    }
+
+    fn class(&mut self, name: &str, superclass: &Option<Expr>, methods: &[Stmt]) -> Result<(), CompileError>{
+        let name_index = self.identifier(name);
+        self.declare_variable(name);
+        self.emit(OpCode::Class(name_index));
+        self.define_variable(name);
+
+        if let Some(superclass) = superclass{
+            self.expression(superclass)?;
+            //The superclass stays on the stack as a local named 'super' for the methods to capture
+            self.begin_scope();
+            self.add_local("super");
+            self.get_variable(name);
+            self.emit(OpCode::Inherit);
+        }
+
+        self.get_variable(name);
+        for method in methods{
+            if let Stmt::Function { name: method_name, line: _ , column: _ , parameters, body } = method{
+                let kind = if method_name == "init" { FunctionKind::Initializer } else { FunctionKind::Method };
+                self.function(method_name.clone(), parameters, body, kind)?;
+                let method_index = self.identifier(method_name);
+                self.emit(OpCode::Method(method_index));
+            }
+        }
+        self.emit(OpCode::Pop);
+
+        if superclass.is_some(){
+            self.end_scope();
+        }
+        return Ok(());
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Result<(), CompileError>{
+        match expr{
+            Expr::Literal { value } => {
+                match value{
+                    LiteralType::Number(num) => {
+                        let index = self.chunk().add_constant(Constant::Number(*num));
+                        self.emit(OpCode::Constant(index));
+                    }
+                    LiteralType::Int(num) => {
+                        let index = self.chunk().add_constant(Constant::Int(*num));
+                        self.emit(OpCode::Constant(index));
+                    }
+                    LiteralType::String(str) => {
+                        let index = self.identifier(str);
+                        self.emit(OpCode::Constant(index));
+                    }
+                    LiteralType::True => {
+                        self.emit(OpCode::True);
+                    }
+                    LiteralType::False => {
+                        self.emit(OpCode::False);
+                    }
+                    LiteralType::Nil => {
+                        self.emit(OpCode::Nil);
+                    }
+                }
+            }
+            Expr::Grouping { expression } => self.expression(expression)?,
+            Expr::Unary { operator, right, line, col } => {
+                self.expression(right)?;
+                self.mark(*line, *col);
+                match operator{
+                    UnaryOpType::Minus => {
+                        self.emit(OpCode::Negate);
+                    }
+                    UnaryOpType::Bang => {
+                        self.emit(OpCode::Not);
+                    }
+                    UnaryOpType::BitNot => {
+                        self.emit(OpCode::BitNot);
+                    }
+                    UnaryOpType::Error => return Err(self.error(format!("Unknown unary operator")))
+                }
+            }
+            Expr::Binary { left, operator, right, line, col } => {
+                self.expression(left)?;
+                self.expression(right)?;
+                self.mark(*line, *col);
+                let op = match operator{
+                    BinaryOpType::Less => OpCode::Less,
+                    BinaryOpType::LessEqual => OpCode::LessEqual,
+                    BinaryOpType::EqualEqual => OpCode::Equal,
+                    BinaryOpType::NotEqual => OpCode::NotEqual,
+                    BinaryOpType::Greater => OpCode::Greater,
+                    BinaryOpType::GreaterEqual => OpCode::GreaterEqual,
+                    BinaryOpType::Plus => OpCode::Add,
+                    BinaryOpType::Minus => OpCode::Subtract,
+                    BinaryOpType::Slash => OpCode::Divide,
+                    BinaryOpType::Star => OpCode::Multiply,
+                    BinaryOpType::Mod => OpCode::Modulo,
+                    BinaryOpType::IntDivide => OpCode::IntDivide,
+                    BinaryOpType::Power => OpCode::Power,
+                    BinaryOpType::BitAnd => OpCode::BitAnd,
+                    BinaryOpType::BitOr => OpCode::BitOr,
+                    BinaryOpType::BitXor => OpCode::BitXor,
+                    BinaryOpType::ShiftLeft => OpCode::ShiftLeft,
+                    BinaryOpType::ShiftRight => OpCode::ShiftRight,
+                    BinaryOpType::Error => return Err(self.error(format!("Unknown binary operator")))
+                };
+                self.emit(op);
+            }
+            Expr::Variable { name, line, col, id: _ } => {
+                self.mark(*line, *col);
+                self.get_variable(name);
+            }
+            Expr::Assign { name, line, column, value, id: _ } => {
+                self.expression(value)?;
+                self.mark(*line, *column);
+                self.set_variable(name);
+            }
+            Expr::Logical { left, operator, right } => {
+                self.expression(left)?;
+                self.mark(operator.line, operator.column);
+                if operator.token_type == crate::scanner::TokenType::Or{
+                    let else_jump = self.emit(OpCode::JumpIfFalse(0));
+                    let end_jump = self.emit(OpCode::Jump(0));
+                    self.patch_jump(else_jump);
+                    self.emit(OpCode::Pop);
+                    self.expression(right)?;
+                    self.patch_jump(end_jump);
+                }
+                else{
+                    let end_jump = self.emit(OpCode::JumpIfFalse(0));
+                    self.emit(OpCode::Pop);
+                    self.expression(right)?;
+                    self.patch_jump(end_jump);
+                }
+            }
+            Expr::Call { callee, paren, arguments } => {
+                self.expression(callee)?;
+                for argument in arguments.iter(){
+                    self.expression(argument)?;
+                }
+                self.mark(paren.line, paren.column);
+                self.emit(OpCode::Call(arguments.len()));
+            }
+            Expr::Get { object, name, line, col } => {
+                self.expression(object)?;
+                self.mark(*line, *col);
+                let index = self.identifier(name);
+                self.emit(OpCode::GetProperty(index));
+            }
+            Expr::Set { object, name, value, line, col } => {
+                self.expression(object)?;
+                self.expression(value)?;
+                self.mark(*line, *col);
+                let index = self.identifier(name);
+                self.emit(OpCode::SetProperty(index));
+            }
+            Expr::This { keyword, line, col, id: _ } => {
+                self.mark(*line, *col);
+                self.get_variable(keyword);
+            }
+            Expr::Super { keyword, method, line, col, id: _ } => {
+                self.mark(*line, *col);
+                self.get_variable("this");
+                self.get_variable(keyword);
+                let index = self.identifier(method);
+                self.emit(OpCode::GetSuper(index));
+            }
+            Expr::List { elements, line, col } => {
+                for element in elements.iter(){
+                    self.expression(element)?;
+                }
+                self.mark(*line, *col);
+                self.emit(OpCode::BuildList(elements.len()));
+            }
+            Expr::Map { entries, line, col } => {
+                for (key, value) in entries.iter(){
+                    self.expression(key)?;
+                    self.expression(value)?;
+                }
+                self.mark(*line, *col);
+                self.emit(OpCode::BuildMap(entries.len()));
+            }
+            Expr::Index { object, index, line, col } => {
+                self.expression(object)?;
+                self.expression(index)?;
+                self.mark(*line, *col);
+                self.emit(OpCode::GetIndex);
+            }
+            Expr::IndexSet { object, index, value, line, col } => {
+                self.expression(object)?;
+                self.expression(index)?;
+                self.expression(value)?;
+                self.mark(*line, *col);
+                self.emit(OpCode::SetIndex);
+            }
+            Expr::Lambda { parameters, body, line, col } => {
+                self.mark(*line, *col);
+                self.function(format!("lambda"), parameters, body, FunctionKind::Function)?;
+            }
+            Expr::Interpolation { parts, line, col } => {
+                for part in parts.iter(){
+                    self.expression(part)?;
+                    self.mark(*line, *col);
+                    self.emit(OpCode::ToString);
+                }
+                self.emit(OpCode::BuildString(parts.len()));
+            }
+            Expr::None => return Err(self.error(format!("We dont have that expression type yet bud")))
+        }
+        return Ok(());
+    }
+
+    fn error(&self, message: String) -> CompileError{
+        return CompileError {
+            message: format!("{} at line: {}, column: {}", message, self.line, self.col),
+            line: self.line,
+            column: self.col
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_begin;
+    use crate::scanner::Scanner;
+
+    fn compile_source(source: &str) -> Rc<Function> {
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source.to_string()).expect("Expected source to scan");
+        let stmts = match parse_begin(tokens) {
+            Ok(stmts) => stmts,
+            Err(err) => panic!("Expected source to parse: {}", err.return_error()),
+        };
+        return compile(&stmts).expect("Expected source to compile");
+    }
+
+    fn function_constant(function: &Function, name: &str) -> Rc<Function> {
+        for constant in function.chunk.constants.iter() {
+            if let Constant::Function(inner) = constant {
+                if inner.name == name {
+                    return inner.clone();
+                }
+            }
+        }
+        panic!("No function named {}", name);
+    }
+
+    #[test]
+    fn test_global_var_and_print() {
+        let script = compile_source("var a = 1 + 2;\nprint a;");
+        assert_eq!(script.chunk.code, vec![
+            OpCode::Constant(0),
+            OpCode::Constant(1),
+            OpCode::Add,
+            OpCode::DefineGlobal(2),
+            OpCode::GetGlobal(2),
+            OpCode::Print,
+            OpCode::Nil,
+            OpCode::Return
+        ]);
+        assert_eq!(script.chunk.positions[2], (1, 11));
+    }
+
+    #[test]
+    fn test_block_locals_use_slots() {
+        let script = compile_source("{ var a = 1; var b = a; b = 2; }");
+        assert_eq!(script.chunk.code, vec![
+            OpCode::Constant(0),
+            OpCode::GetLocal(1),
+            OpCode::Constant(1),
+            OpCode::SetLocal(2),
+            OpCode::Pop,
+            OpCode::Pop,
+            OpCode::Pop,
+            OpCode::Nil,
+            OpCode::Return
+        ]);
+    }
+
+    #[test]
+    fn test_captured_locals_become_upvalues() {
+        let script = compile_source("fun outer() { var x = 1; fun inner() { return x; } return inner; }");
+        let outer = function_constant(&script, "outer");
+        let inner = function_constant(&outer, "inner");
+
+        assert_eq!(inner.upvalues, vec![UpvalueRef { index: 1, is_local: true }]);
+        assert!(inner.chunk.code.contains(&OpCode::GetUpvalue(0)));
+        assert_eq!(outer.arity, 0);
+    }
+
+    #[test]
+    fn test_break_pops_loop_locals() {
+        let script = compile_source("while (true) { var a = 1; break; }");
+        let code = &script.chunk.code;
+        let jump = code.iter().position(|op| matches!(op, OpCode::Jump(_))).unwrap();
+
+        assert_eq!(code[jump - 1], OpCode::Pop, "Expected the loop local to be popped before the break jump");
+        if let OpCode::Jump(target) = code[jump] {
+            assert_eq!(code[target..], [OpCode::Nil, OpCode::Return]);
+        }
+    }
+
+    #[test]
+    fn test_unsupported_statement_is_a_compile_error() {
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens("try { } finally { }".to_string()).unwrap();
+        let stmts = parse_begin(tokens).ok().unwrap();
+        let diagnostic = compile(&stmts).err().unwrap().diagnostic();
+
+        assert_eq!(diagnostic.kind, DiagnosticKind::Compile);
+        assert!(diagnostic.render("test.lox", "try { } finally { }").starts_with("error[compile]: Exceptions are not supported by the VM"));
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+
+//Every error the pipeline can produce is turned into a Diagnostic before it
+//is printed, so scan, parse, resolve and runtime errors all render the same
+//rustc style report: the message, where it happened, and the source line
+//with a caret under the offending column.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DiagnosticKind{
+    Scan,
+    Parse,
+    Resolve,
+    //The program is valid Lox but the bytecode compiler cannot handle it
+    Compile,
+    Runtime
+}
+
+impl DiagnosticKind{
+    pub fn name(&self) -> &'static str{
+        match self{
+            DiagnosticKind::Scan => "scan",
+            DiagnosticKind::Parse => "parse",
+            DiagnosticKind::Resolve => "resolve",
+            DiagnosticKind::Compile => "compile",
+            DiagnosticKind::Runtime => "runtime"
+        }
+    }
+}
+
+//Extra context pointing somewhere else in the source, like a declaration.
+//A line of 0 means the position is unknown and only the message is shown.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Note{
+    pub message: String,
+    pub line: usize,
+    pub column: i64
+}
+
+impl Note{
+    pub fn new(message: String, line: usize, column: i64) -> Self{
+        Note {
+            message: message,
+            line: line,
+            column: column
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic{
+    pub kind: DiagnosticKind,
+    pub message: String,
+    pub line: usize,
+    pub column: i64,
+    pub notes: Vec<Note>
+}
+
+impl Diagnostic{
+    pub fn new(kind: DiagnosticKind, message: String, line: usize, column: i64) -> Self{
+        Diagnostic {
+            kind: kind,
+            message: message,
+            line: line,
+            column: column,
+            notes: Vec::new()
+        }
+    }
+
+    pub fn with_note(mut self, note: Note) -> Self{
+        self.notes.push(note);
+        return self;
+    }
+
+    pub fn render(&self, file_name: &str, source: &str) -> String{
+        let lines: Vec<&str> = source.lines().collect();
+        let mut report = format!("error[{}]: {}\n", self.kind.name(), self.message);
+
+        //Labels are grouped by line so a note on the same line as the error shares its snippet
+        let mut labels: BTreeMap<usize, Vec<(usize, char, Option<&str>)>> = BTreeMap::new();
+        if let Some(index) = char_index(&lines, self.line, self.column){
+            labels.entry(self.line).or_default().push((index, '^', None));
+        }
+        let mut unplaced: Vec<&str> = Vec::new();
+        for note in self.notes.iter(){
+            match char_index(&lines, note.line, note.column){
+                Some(index) => labels.entry(note.line).or_default().push((index, '-', Some(note.message.as_str()))),
+                None => unplaced.push(note.message.as_str())
+            }
+        }
+
+        let gutter = labels.keys().chain([self.line].iter()).max().unwrap_or(&0).to_string().len();
+        let blank = " ".repeat(gutter);
+        match char_index(&lines, self.line, self.column){
+            Some(index) => report.push_str(&format!("{}--> {}:{}:{}\n", blank, file_name, self.line, index + 1)),
+            None => report.push_str(&format!("{}--> {}\n", blank, file_name))
+        }
+        if !labels.is_empty(){
+            report.push_str(&format!("{} |\n", blank));
+        }
+        let mut previous: Option<usize> = None;
+        for (line, line_labels) in labels.iter_mut(){
+            if let Some(previous) = previous{
+                if *line > previous + 1{
+                    report.push_str("...\n");
+                }
+            }
+            let text = lines[*line - 1];
+            report.push_str(&format!("{:>width$} | {}\n", line, text, width = gutter));
+            line_labels.sort_by_key(|label| label.0);
+            for (index, marker, message) in line_labels.iter(){
+                let padding: String = text.chars().take(*index).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+                match message{
+                    Some(message) => report.push_str(&format!("{} | {}{} {}\n", blank, padding, marker, message)),
+                    None => report.push_str(&format!("{} | {}{}\n", blank, padding, marker))
+                }
+            }
+            previous = Some(*line);
+        }
+        for message in unplaced{
+            report.push_str(&format!("{} = note: {}\n", blank, message));
+        }
+        return report;
+    }
+}
+
+//Turns a reported (line, column) into a character offset within that line.
+//Columns are one based on every line.
+fn char_index(lines: &[&str], line: usize, column: i64) -> Option<usize>{
+    if line == 0 || line > lines.len(){
+        return None;
+    }
+    let index = column - 1;
+    let length = lines[line - 1].chars().count();
+    if length == 0{
+        return Some(0);
+    }
+    return Some(index.clamp(0, length as i64 - 1) as usize);
+}
+
+pub fn report(diagnostics: &[Diagnostic], file_name: &str, source: &str) -> (){
+    for diagnostic in diagnostics{
+        println!("{}", diagnostic.render(file_name, source));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_points_at_column() {
+        let source = "var a = 1;\n  var x = 1 / 0;\n";
+        let diagnostic = Diagnostic::new(DiagnosticKind::Runtime, "Divide by zero error".to_string(), 2, 13);
+
+        assert_eq!(diagnostic.render("test.lox", source),
+            "error[runtime]: Divide by zero error\n \
+            --> test.lox:2:13\n  \
+            |\n\
+            2 |   var x = 1 / 0;\n  \
+            |             ^\n");
+    }
+
+    #[test]
+    fn test_first_line_columns_are_one_based() {
+        let source = "print x;";
+        let diagnostic = Diagnostic::new(DiagnosticKind::Resolve, "oops at line: 1, column: 7".to_string(), 1, 7);
+        let rendered = diagnostic.render("test.lox", source);
+
+        assert!(rendered.contains("--> test.lox:1:7"), "{}", rendered);
+        assert!(rendered.ends_with("1 | print x;\n  |       ^\n"), "{}", rendered);
+    }
+
+    #[test]
+    fn test_notes_share_or_split_snippets() {
+        let source = "{\n  var x;\n  print x;\n\n\n  print x;\n}";
+        let diagnostic = Diagnostic::new(DiagnosticKind::Runtime, "use of undefined variable 'x'".to_string(), 6, 9)
+        .with_note(Note::new("variable declared here".to_string(), 2, 7))
+        .with_note(Note::new("no position".to_string(), 0, 0));
+        let rendered = diagnostic.render("test.lox", source);
+
+        assert_eq!(rendered,
+            "error[runtime]: use of undefined variable 'x'\n \
+            --> test.lox:6:9\n  \
+            |\n\
+            2 |   var x;\n  \
+            |       - variable declared here\n\
+            ...\n\
+            6 |   print x;\n  \
+            |         ^\n  \
+            = note: no position\n");
+    }
+
+    #[test]
+    fn test_unknown_position_has_no_snippet() {
+        let diagnostic = Diagnostic::new(DiagnosticKind::Parse, "Cannot have more than 255 arguments".to_string(), 0, 0);
+
+        assert_eq!(diagnostic.render("test.lox", "print 1;"),
+            "error[parse]: Cannot have more than 255 arguments\n --> test.lox\n");
+    }
+}
@@ -1,6 +1,8 @@
-use std::borrow::Borrow;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 use std::env::var;
+use crate::diagnostics::Note;
 use crate::lox_callable::*;
 use crate::expr::{Expr}; 
 use crate::interpreter::{InterpreterError, Value};
@@ -9,8 +11,11 @@ use crate::lox_instance::*;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Environment{
+    //Globals are looked up by name, resolved locals by the slot the resolver gave them
     pub values: HashMap<String, (Option<Value>, VarLocation)>,
-    pub enclosing: Option<Box<Environment>>,
+    pub slots: Vec<(Option<Value>, VarLocation)>,
+    //Frames are shared so closures see later writes to the scopes they captured
+    pub enclosing: Option<Rc<RefCell<Environment>>>,
     pub user_func: HashMap<String, UserDefined>,
     pub classes: HashMap<String, LoxClass>,
     pub instances: HashMap<String, LoxInstance>,
@@ -20,6 +25,7 @@ impl Default for Environment{
     fn default() -> Environment {
         Environment{
             values: HashMap::new(),
+            slots: Vec::new(),
             enclosing: None,
             user_func: HashMap::new(),
             classes: HashMap::new(),
@@ -46,40 +52,38 @@ pub enum LookupResult{
 }
 
 impl Environment{
-    pub fn new(enclosing: Environment) -> Self{
+    pub fn new(enclosing: Rc<RefCell<Environment>>) -> Self{
         Environment{
             values: HashMap::new(),
-            enclosing: Some(Box::new(enclosing)),
+            slots: Vec::new(),
+            enclosing: Some(enclosing),
             user_func: HashMap::new(),
             classes: HashMap::new(),
             instances: HashMap::new()
         }
     }
 
-    pub fn full(vals: HashMap<String, (Option<Value>, VarLocation)>, enclosing: Environment)-> Self{
+    pub fn full(vals: HashMap<String, (Option<Value>, VarLocation)>, enclosing: Rc<RefCell<Environment>>)-> Self{
+        let outer = enclosing.borrow();
         Environment{
             values: vals,
-            enclosing: Some(Box::new(enclosing.clone())),
-            user_func: enclosing.user_func,
-            classes: enclosing.classes,
-            instances: enclosing.instances
+            slots: Vec::new(),
+            enclosing: Some(enclosing.clone()),
+            user_func: outer.user_func.clone(),
+            classes: outer.classes.clone(),
+            instances: outer.instances.clone()
         }
     }
 
-    pub fn set_enclosing(&mut self, enclosing: Option<Environment>) -> (){
-        if enclosing.is_some(){
-            self.enclosing = Some(Box::new(enclosing.unwrap()))
-        }
-        else{
-            self.enclosing = None
-        }
+    pub fn set_enclosing(&mut self, enclosing: Option<Rc<RefCell<Environment>>>) -> (){
+        self.enclosing = enclosing;
     }
 
     pub fn set_values(&mut self, values: HashMap<String, (Option<Value>, VarLocation)>) -> (){
         self.values = values;
     }
 
-    pub fn return_enclosing(&self) -> Option<Box<Environment>>{
+    pub fn return_enclosing(&self) -> Option<Rc<RefCell<Environment>>>{
         return self.enclosing.clone();
     }
 
@@ -101,6 +105,65 @@ impl Environment{
         return ()
     }
 
+    //Locals are declared in the same order the resolver numbered them,
+    //so the next free slot is always the one it handed out
+    pub fn define_slot(&mut self, line: usize, col: i64, possible_val: Option<Value>) -> usize{
+        self.slots.push(
+            (
+                possible_val,
+                VarLocation{
+                    line: line,
+                    col: col
+                }
+            )
+        );
+        return self.slots.len() - 1
+    }
+
+    pub fn get_slot_at(&self, distance: usize, slot: usize, name: &str, line: usize, col: i64) -> Result<Value, InterpreterError>{
+        if distance > 0{
+            if let Some(enclosing) = &self.enclosing{
+                return enclosing.borrow().get_slot_at(distance - 1, slot, name, line, col);
+            }
+        }
+        match self.slots.get(slot){
+            Some((Some(val), _)) => return Ok(val.clone()),
+            Some((None, var_location)) => return Err(InterpreterError::new(
+                format!("use of undefined variable '{}' at line: {}, column: {}",
+                name, line, col),
+                line,
+                col,
+                Value::Nil)
+                .with_note(Note::new(format!("variable declared here"), var_location.line, var_location.col))),
+            None => return Err(InterpreterError::new(
+                format!("use of undefined and undeclared variable '{}' at line: {}, column: {}",
+                name, line, col),
+                line,
+                col,
+                Value::Nil))
+        }
+    }
+
+    pub fn assign_slot_at(&mut self, distance: usize, slot: usize, name: &str, line: usize, col: i64, val: &Value) -> Result<(), InterpreterError>{
+        if distance > 0{
+            if let Some(enclosing) = &self.enclosing{
+                return enclosing.borrow_mut().assign_slot_at(distance - 1, slot, name, line, col, val);
+            }
+        }
+        match self.slots.get_mut(slot){
+            Some(entry) => {
+                entry.0 = Some(val.clone());
+                return Ok(());
+            }
+            None => return Err(InterpreterError::new(
+                format!("Attempting to assign undefined variable '{}' at line: {}, column: {}",
+                name, line, col), 
+                line, 
+                col,
+                Value::Nil))
+        }
+    }
+
     pub fn define_token(&mut self, token: Token, val: Value) -> (){
         self.values.insert(
             String::from_utf8(token.lexeme).unwrap(), 
@@ -130,19 +193,25 @@ impl Environment{
     }
 
     pub fn get(&self, expr: &Expr) -> Result<Value, InterpreterError>{
-        if let Expr::Variable { name, line, col } = expr.clone(){
+        return self.lookup(expr, true);
+    }
+
+    //walk_enclosing is false for resolved variables, which must live in exactly this scope
+    fn lookup(&self, expr: &Expr, walk_enclosing: bool) -> Result<Value, InterpreterError>{
+        if let Expr::Variable { name, line, col, id: _ } = expr.clone(){
             match self.val_lookup(&expr) {
                 LookupResult::Ok(val) => Ok(val),
-                LookupResult::UndefinedButDeclared { line, col } => 
+                LookupResult::UndefinedButDeclared { line: declared_line, col: declared_col } => 
                 Err(InterpreterError::new(format!("use of undefined variable '{}' at line: {}, column: {}",
                 name, line, col),
                 line,
                 col,
-                Value::Nil)),
+                Value::Nil)
+                .with_note(Note::new(format!("variable declared here"), declared_line, declared_col))),
                 LookupResult::UndefinedAndUndeclared => {
                     match &self.enclosing {
-                        Some(enclosing) => enclosing.get(expr),
-                        None => Err(InterpreterError::new(
+                        Some(enclosing) if walk_enclosing => enclosing.borrow().lookup(expr, walk_enclosing),
+                        _ => Err(InterpreterError::new(
                                 format!("use of undefined and undeclared variable '{}' at line: {}, column: {}",
                                 name, line, col),
                                 line,
@@ -152,24 +221,24 @@ impl Environment{
                 }
             }
         }
-        else if let Expr::This { keyword } = expr{
+        else if let Expr::This { keyword: _ , line, col, id: _ } = expr{
             match self.val_lookup(&expr){
                 LookupResult::Ok(val) => Ok(val),
                 _ => Err(InterpreterError::new(
-                    format!("Incorrect use of 'this'"), 
-                    0, 
-                    0, 
+                    format!("Incorrect use of 'this' at line: {}, column: {}", line, col), 
+                    *line, 
+                    *col, 
                     Value::Nil
                 ))
             }
         }
-        else if let Expr::Super { keyword, method } = expr{
+        else if let Expr::Super { keyword: _ , method: _ , line, col, id: _ } = expr{
             match self.val_lookup(&expr){
                 LookupResult::Ok(val) => Ok(val),
                 _ => Err(InterpreterError::new(
-                    format!("Incorrect use of 'super'"), 
-                    0, 
-                    0, 
+                    format!("Incorrect use of 'super' at line: {}, column: {}", line, col), 
+                    *line, 
+                    *col, 
                     Value::Nil
                 ))
             }
@@ -184,8 +253,8 @@ impl Environment{
             self.define(name.clone(), line, col, Some(val.clone()));
             return Ok(());
         }
-        match &mut self.enclosing {
-            Some(enclosing) => return enclosing.assign(name.clone(), line, col, val),
+        match &self.enclosing {
+            Some(enclosing) => return enclosing.borrow_mut().assign(name.clone(), line, col, val),
             None => return Err(InterpreterError::new(
       format!("Attempting to assign undefined variable '{}' at line: {}, column: {}",
                     name, line, col), 
@@ -196,12 +265,25 @@ impl Environment{
     }
 
     pub fn assign_at(&mut self, name: String, line: usize, col: i64, val: &Value, distance: usize) -> Result<(), InterpreterError>{
-        return self.assign(name, line, col, val);
-        
+        if distance > 0{
+            if let Some(enclosing) = &self.enclosing{
+                return enclosing.borrow_mut().assign_at(name, line, col, val, distance - 1);
+            }
+        }
+        else if self.values.contains_key(&name){
+            self.define(name, line, col, Some(val.clone()));
+            return Ok(());
+        }
+        return Err(InterpreterError::new(
+            format!("Attempting to assign undefined variable '{}' at line: {}, column: {}",
+            name, line, col), 
+            line, 
+            col,
+            Value::Nil))
     }
 
     pub fn val_lookup(&self, expr: &Expr) -> LookupResult{
-        if let Expr::Variable { name, line , col } = expr{
+        if let Expr::Variable { name, line: _ , col: _ , id: _ } = expr{
             match self.values.get(name) {
                 Some((maybe_val, var_location)) => match maybe_val{
                     Some(val) => LookupResult::Ok(val.clone()),
@@ -221,7 +303,7 @@ impl Environment{
                 }
             }
         }
-        else if let Expr::This { keyword } = expr{
+        else if let Expr::This { keyword: _ , line: _ , col: _ , id: _ } = expr{
             let name = format!("this");
             match self.values.get(&name){
                 Some((maybe_val, var_location)) => match maybe_val{
@@ -234,7 +316,7 @@ impl Environment{
                 None => LookupResult::UndefinedAndUndeclared
             }
         }
-        else if let Expr::Super { keyword, method } = expr{
+        else if let Expr::Super { keyword: _ , method: _ , line: _ , col: _ , id: _ } = expr{
             let name = format!("super");
             match self.values.get(&name){
                 Some((maybe_val, var_location)) => match maybe_val{
@@ -252,13 +334,19 @@ impl Environment{
         }
     }
 
-    pub fn get_at(&mut self, distance: usize, expr: Expr) -> Result<Value, InterpreterError>{
-        return self.get(&expr);
+    pub fn get_at(&self, distance: usize, expr: Expr) -> Result<Value, InterpreterError>{
+        if distance > 0{
+            if let Some(enclosing) = &self.enclosing{
+                return enclosing.borrow().get_at(distance - 1, expr);
+            }
+        }
+        return self.lookup(&expr, false);
     }
     pub fn ancestor(&self, distance: usize) -> Environment{
         let mut current: Environment = self.clone();
         for _ in 0..distance{
-            current = *current.enclosing.unwrap();
+            let enclosing = current.enclosing.clone().unwrap();
+            current = enclosing.borrow().clone();
         }
         return current;
     }
@@ -275,6 +363,8 @@ mod tests {
     use std::collections::HashMap;
     use crate::lox_callable::{UserDefined, LoxClass};
     use crate::stmt::Stmt;
+    use std::cell::RefCell;
+    use std::rc::Rc;
 
 
     #[test]
@@ -291,12 +381,12 @@ mod tests {
     fn test_environment_new() {
         let enclosing_env = Environment::default();
 
-        let new_env = Environment::new(enclosing_env.clone());
+        let new_env = Environment::new(Rc::new(RefCell::new(enclosing_env.clone())));
 
         assert_eq!(new_env.values, HashMap::new());
         assert!(new_env.enclosing.is_some());
         let unwrapped_enclosing = new_env.enclosing.unwrap();
-        assert_eq!(*unwrapped_enclosing, enclosing_env);
+        assert_eq!(*unwrapped_enclosing.borrow(), enclosing_env);
         assert_eq!(new_env.user_func, HashMap::new());
         assert_eq!(new_env.classes, HashMap::new());
         assert_eq!(new_env.instances, HashMap::new());
@@ -324,13 +414,15 @@ mod tests {
         let testing_func = UserDefined {
             name: "testing_func".to_string(),
             parameters: vec![testing_token.clone()],
-            body: vec![], 
-            declaration: Stmt::Function {
+            body: Rc::new(vec![]), 
+            declaration: Rc::new(Stmt::Function {
                 name: "testing_func".to_string(),
+                line: 0,
+                column: 0,
                 parameters: vec![testing_token.clone()],
                 body: Box::new(vec![]), 
-            },
-            closure: Environment::default(),
+            }),
+            closure: Rc::new(RefCell::new(Environment::default())),
             is_init: false,
         };
 
@@ -345,12 +437,12 @@ mod tests {
         enclosing_env.classes.insert("testing_class".to_string(), testing_class.clone());
         enclosing_env.instances.insert("testing_instance".to_string(), LoxInstance::new(testing_class.clone()));
 
-        let new_env = Environment::full(vals.clone(), enclosing_env.clone());
+        let new_env = Environment::full(vals.clone(), Rc::new(RefCell::new(enclosing_env.clone())));
 
         assert_eq!(new_env.values, vals);
         assert!(new_env.enclosing.is_some());
         let unwrapped_enclosing = new_env.enclosing.unwrap();
-        assert_eq!(*unwrapped_enclosing, enclosing_env);
+        assert_eq!(*unwrapped_enclosing.borrow(), enclosing_env);
         assert_eq!(new_env.user_func, enclosing_env.user_func);
         assert_eq!(new_env.classes, enclosing_env.classes);
         assert_eq!(new_env.instances, enclosing_env.instances);
@@ -366,11 +458,11 @@ mod tests {
         let testing_location = VarLocation { line: 1, col: 1 };
         enclosing_env.define("enclosing_var".to_string(), testing_location.line, testing_location.col, Some(testing_value.clone()));
 
-        base_env.set_enclosing(Some(enclosing_env.clone()));
+        base_env.set_enclosing(Some(Rc::new(RefCell::new(enclosing_env.clone()))));
         
         assert!(base_env.enclosing.is_some());
         let unwrapped_enclosing = base_env.enclosing.as_ref().unwrap();
-        assert!(unwrapped_enclosing.values.contains_key("enclosing_var"));
+        assert!(unwrapped_enclosing.borrow().values.contains_key("enclosing_var"));
         base_env.set_enclosing(None);
         assert!(base_env.enclosing.is_none());
     }
@@ -406,20 +498,20 @@ mod tests {
         let env_without_enclosing = Environment::default();
         assert_eq!(env_without_enclosing.return_enclosing(), None);
 
-        let mut env_with_enclosing = Environment::new(env_without_enclosing.clone());
+        let mut env_with_enclosing = Environment::new(Rc::new(RefCell::new(env_without_enclosing.clone())));
 
         let returned_enclosing = env_with_enclosing.return_enclosing();
         assert!(returned_enclosing.is_some());
 
         let enclosed_env = returned_enclosing.unwrap();
-        assert_eq!(*enclosed_env, env_without_enclosing);
+        assert_eq!(*enclosed_env.borrow(), env_without_enclosing);
 
-        let second_env = Environment::new(env_with_enclosing.clone());
+        let second_env = Environment::new(Rc::new(RefCell::new(env_with_enclosing.clone())));
         let second_env_enclosing = second_env.return_enclosing();
         assert!(second_env_enclosing.is_some());
 
         let inner_enclosed_env = second_env_enclosing.unwrap();
-        assert_eq!(*inner_enclosed_env, env_with_enclosing);
+        assert_eq!(*inner_enclosed_env.borrow(), env_with_enclosing);
     }
 
     #[test]
@@ -427,7 +519,7 @@ mod tests {
         let mut env = Environment::default();
         
         let testing_value_1 = (Some(Value::Number(42.0)), VarLocation { line: 1, col: 0 });
-        let testing_value_2 = (Some(Value::String("test".to_string())), VarLocation { line: 2, col: 1 });
+        let testing_value_2 = (Some(Value::String("test".into())), VarLocation { line: 2, col: 1 });
 
         let mut values_map = HashMap::new();
         values_map.insert("var1".to_string(), testing_value_1.clone());
@@ -463,7 +555,7 @@ mod tests {
         let var_loc_1 = VarLocation { line: 1, col: 0 };
 
         let var_name_2 = "var2".to_string();
-        let value_2 = Some(Value::String("test".to_string()));
+        let value_2 = Some(Value::String("test".into()));
         let var_loc_2 = VarLocation { line: 2, col: 1 };
 
         env.define(var_name_1.clone(), var_loc_1.line, var_loc_1.col, value_1.clone());
@@ -513,7 +605,7 @@ mod tests {
             literal: None
         };
 
-        let value_2 = Value::String("test".to_string());
+        let value_2 = Value::String("test".into());
 
         env.define_token(token_2.clone(), value_2.clone());
 
@@ -535,7 +627,7 @@ mod tests {
         assert_eq!(stored_value_1, &(Some(value_1), VarLocation { line: 0, col: 0 }));
 
         let var_name_2 = "var2".to_string();
-        let value_2 = Value::String("test".to_string());
+        let value_2 = Value::String("test".into());
 
         env.define_string(var_name_2.clone(), value_2.clone());
 
@@ -555,6 +647,7 @@ mod tests {
             name: var_name.clone(),
             line: 1,
             col: 1,
+            id: 0,
         };
 
         let result = env.get(&expr);
@@ -573,6 +666,7 @@ mod tests {
             name: "y".to_string(),
             line: 1,
             col: 1,
+            id: 0,
         };
 
         let result = env.get(&expr);
@@ -593,6 +687,9 @@ mod tests {
 
         let expr = Expr::This {
             keyword: "this".to_string(),
+            line: 0,
+            col: 0,
+            id: 0,
         };
 
         let result = env.get(&expr);
@@ -612,6 +709,9 @@ mod tests {
         let expr = Expr::Super {
             keyword: "super".to_string(),
             method: "someMethod".to_string(),
+            line: 0,
+            col: 0,
+            id: 0,
         };
 
         let result = env.get(&expr);
@@ -632,6 +732,7 @@ mod tests {
             name: var_name.clone(),
             line: 2,
             col: 3,
+            id: 0,
         };
 
         let result = env.get(&expr);
@@ -661,6 +762,7 @@ mod tests {
             name: var_name.clone(),
             line: 2,
             col: 1,
+            id: 0,
         }).unwrap();
 
         assert_eq!(updated_value, new_value);
@@ -683,20 +785,21 @@ mod tests {
 
     #[test]
     fn test_assign_variable_in_nested_environment() {
-        let mut outer_env = Environment::default();
-        outer_env.define("x".to_string(), 1, 1, Some(Value::Number(5.0)));
+        let outer_env = Rc::new(RefCell::new(Environment::default()));
+        outer_env.borrow_mut().define("x".to_string(), 1, 1, Some(Value::Number(5.0)));
     
         let mut inner_env = Environment::new(outer_env.clone()); 
     
         inner_env.assign("x".to_string(), 2, 1, &Value::Number(30.0)).unwrap();
     
-        let value_inner = inner_env.get(&Expr::Variable { name: "x".to_string(), line: 2, col: 1 }).unwrap();
+        let value_inner = inner_env.get(&Expr::Variable { name: "x".to_string(), line: 2, col: 1, id: 0 }).unwrap();
 
         assert_eq!(value_inner, Value::Number(30.0));
 
-        let value_outer = outer_env.get(&Expr::Variable { name: "x".to_string(), line: 1, col: 1 }).unwrap();
+        //The frames are shared, so the write is visible through the outer scope too
+        let value_outer = outer_env.borrow().get(&Expr::Variable { name: "x".to_string(), line: 1, col: 1, id: 0 }).unwrap();
         
-        assert_eq!(value_outer, Value::Number(5.0));
+        assert_eq!(value_outer, Value::Number(30.0));
     }
 
     
@@ -705,13 +808,13 @@ mod tests {
         let mut outer_env = Environment::default();
         outer_env.define("x".to_string(), 1, 1, Some(Value::Number(10.0)));
 
-        let mut inner_env = Environment::new(outer_env);
+        let mut inner_env = Environment::new(Rc::new(RefCell::new(outer_env)));
 
         let result = inner_env.assign_at("x".to_string(), 2, 1, &Value::Number(20.0), 1);
 
         assert!(result.is_ok());
 
-        let updated_value = inner_env.get(&Expr::Variable { name: "x".to_string(), line: 1, col: 1 }).unwrap();
+        let updated_value = inner_env.get(&Expr::Variable { name: "x".to_string(), line: 1, col: 1, id: 0 }).unwrap();
 
         assert_eq!(updated_value, Value::Number(20.0));
     }
@@ -740,7 +843,7 @@ mod tests {
         let keyword_str = String::from_utf8(keyword_token.lexeme.clone()).unwrap();
         let method_str = String::from_utf8(method_token.lexeme.clone()).unwrap();
     
-        let expr = Expr::Super { keyword: keyword_str, method: method_str };
+        let expr = Expr::Super { keyword: keyword_str, method: method_str, line: method_token.line, col: method_token.column, id: 0 };
         let result = env.val_lookup(&expr);
     
         match result {
@@ -755,7 +858,7 @@ mod tests {
         let mut outer_env = Environment::default();
         outer_env.define("x".to_string(), 1, 1, Some(Value::Number(42.0)));
     
-        let mut inner_env = Environment::new(outer_env);
+        let mut inner_env = Environment::new(Rc::new(RefCell::new(outer_env)));
     
         inner_env.define("y".to_string(), 2, 1, Some(Value::Number(100.0)));
 
@@ -763,12 +866,14 @@ mod tests {
             name: "x".to_string(),
             line: 1,
             col: 1,
+            id: 0,
         };
 
         let expr_y = Expr::Variable {
             name: "y".to_string(),
             line: 2,
             col: 1,
+            id: 0,
         };
 
         let result_y = inner_env.get_at(0, expr_y.clone());
@@ -785,28 +890,31 @@ mod tests {
         let mut outer_env = Environment::default();
         outer_env.define("x".to_string(), 1, 1, Some(Value::Number(42.0)));
     
-        let mut middle_env = Environment::new(*Box::new(outer_env.clone()));
+        let mut middle_env = Environment::new(Rc::new(RefCell::new(outer_env.clone())));
         middle_env.define("y".to_string(), 2, 1, Some(Value::Number(100.0)));
     
-        let mut inner_env = Environment::new(*Box::new(middle_env.clone()));
+        let mut inner_env = Environment::new(Rc::new(RefCell::new(middle_env.clone())));
         inner_env.define("z".to_string(), 3, 1, Some(Value::Number(200.0)));
     
         let expr_x = Expr::Variable {
             name: "x".to_string(),
             line: 1,
             col: 1,
+            id: 0,
         };
     
         let expr_y = Expr::Variable {
             name: "y".to_string(),
             line: 2,
             col: 1,
+            id: 0,
         };
     
         let expr_z = Expr::Variable {
             name: "z".to_string(),
             line: 3,
             col: 1,
+            id: 0,
         };
     
         let result_z = inner_env.get_at(0, expr_z.clone());
@@ -836,6 +944,7 @@ mod tests {
             name: "undefined_var".to_string(),
             line: 1,
             col: 1,
+            id: 0,
         };
 
         let result = env.get(&expr_undefined);
@@ -855,11 +964,87 @@ mod tests {
             name: "x".to_string(),
             line: 1,
             col: 1,
+            id: 0,
         };
 
         let result = env.get(&expr_x);
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), Value::Number(100.0));
     }
-    
+
+    #[test]
+    fn test_get_at_skips_shadowing_scope() {
+        let mut outer_env = Environment::default();
+        outer_env.define("x".to_string(), 1, 1, Some(Value::Number(1.0)));
+
+        let mut inner_env = Environment::new(Rc::new(RefCell::new(outer_env)));
+        inner_env.define("x".to_string(), 2, 1, Some(Value::Number(2.0)));
+
+        let expr_x = Expr::Variable {
+            name: "x".to_string(),
+            line: 3,
+            col: 1,
+            id: 0,
+        };
+
+        assert_eq!(inner_env.get_at(0, expr_x.clone()).unwrap(), Value::Number(2.0));
+        assert_eq!(inner_env.get_at(1, expr_x.clone()).unwrap(), Value::Number(1.0));
+
+        inner_env.assign_at("x".to_string(), 3, 1, &Value::Number(10.0), 1).unwrap();
+        assert_eq!(inner_env.get_at(0, expr_x.clone()).unwrap(), Value::Number(2.0));
+        assert_eq!(inner_env.get_at(1, expr_x).unwrap(), Value::Number(10.0));
+    }
+
+    #[test]
+    fn test_get_at_does_not_walk_further() {
+        let mut outer_env = Environment::default();
+        outer_env.define("x".to_string(), 1, 1, Some(Value::Number(1.0)));
+
+        let mut inner_env = Environment::new(Rc::new(RefCell::new(outer_env)));
+
+        let expr_x = Expr::Variable {
+            name: "x".to_string(),
+            line: 2,
+            col: 1,
+            id: 0,
+        };
+
+        assert!(inner_env.get_at(0, expr_x).is_err());
+        assert!(inner_env.assign_at("x".to_string(), 2, 1, &Value::Number(5.0), 0).is_err());
+    }
+
+    #[test]
+    fn test_define_slot_and_get_slot_at() {
+        let outer_env = Rc::new(RefCell::new(Environment::new(Rc::new(RefCell::new(Environment::default())))));
+        let first = outer_env.borrow_mut().define_slot(1, 5, Some(Value::Number(1.0)));
+        let second = outer_env.borrow_mut().define_slot(2, 5, None);
+
+        let mut inner_env = Environment::new(outer_env.clone());
+        inner_env.define_slot(3, 5, Some(Value::Number(3.0)));
+
+        assert_eq!((first, second), (0, 1));
+        assert_eq!(inner_env.get_slot_at(0, 0, "c", 4, 1).unwrap(), Value::Number(3.0));
+        assert_eq!(inner_env.get_slot_at(1, 0, "a", 4, 1).unwrap(), Value::Number(1.0));
+
+        match inner_env.get_slot_at(1, 1, "b", 4, 1) {
+            Err(err) => {
+                assert_eq!(err.return_error(), "use of undefined variable 'b' at line: 4, column: 1");
+                assert_eq!(err.diagnostic().notes, vec![Note::new("variable declared here".to_string(), 2, 5)]);
+            }
+            Ok(val) => panic!("Expected an error for a declared but unset slot, got {:?}", val),
+        }
+        assert!(inner_env.get_slot_at(0, 5, "missing", 4, 1).is_err());
+    }
+
+    #[test]
+    fn test_assign_slot_at_is_shared() {
+        let outer_env = Rc::new(RefCell::new(Environment::new(Rc::new(RefCell::new(Environment::default())))));
+        outer_env.borrow_mut().define_slot(1, 5, Some(Value::Number(1.0)));
+
+        let mut inner_env = Environment::new(outer_env.clone());
+        inner_env.assign_slot_at(1, 0, "a", 2, 1, &Value::Number(2.0)).unwrap();
+
+        assert_eq!(outer_env.borrow().get_slot_at(0, 0, "a", 3, 1).unwrap(), Value::Number(2.0));
+        assert!(inner_env.assign_slot_at(0, 0, "a", 2, 1, &Value::Number(2.0)).is_err());
+    }
 }
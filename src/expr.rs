@@ -1,4 +1,5 @@
 use crate::scanner::{Token, TokenType};
+use crate::stmt::Stmt;
 //fixing commit messages
 
 #[derive(Debug, Clone, PartialEq)]
@@ -22,16 +23,19 @@ pub enum Expr {
         line: usize,
         col: i64,
     },
+    //id is unique per parsed expression, the resolver keys scope depths by it
     Variable {
         name: String,
         line: usize,
-        col: i64
+        col: i64,
+        id: usize
     },
     Assign{
         name: String,
         line: usize,
         column: i64,
-        value: Box<Expr>
+        value: Box<Expr>,
+        id: usize
     },
     Logical{
         left: Box<Expr>,
@@ -45,27 +49,87 @@ pub enum Expr {
     },
     Get{
         object: Box<Expr>,
-        name: String
+        name: String,
+        line: usize,
+        col: i64
     },
     Set{
         object: Box<Expr>,
         name: String,
-        value: Box<Expr>
+        value: Box<Expr>,
+        line: usize,
+        col: i64
     },
     This{
-        keyword: String
+        keyword: String,
+        line: usize,
+        col: i64,
+        id: usize
     },
+    //line and col point at the method name
     Super{
         keyword: String,
-        method: String
+        method: String,
+        line: usize,
+        col: i64,
+        id: usize
+    },
+    List{
+        elements: Box<Vec<Expr>>,
+        line: usize,
+        col: i64
+    },
+    Index{
+        object: Box<Expr>,
+        index: Box<Expr>,
+        line: usize,
+        col: i64
+    },
+    IndexSet{
+        object: Box<Expr>,
+        index: Box<Expr>,
+        value: Box<Expr>,
+        line: usize,
+        col: i64
+    },
+    Map{
+        entries: Box<Vec<(Expr, Expr)>>,
+        line: usize,
+        col: i64
+    },
+    Lambda{
+        parameters: Vec<Token>,
+        body: Box<Vec<Stmt>>,
+        line: usize,
+        col: i64
+    },
+    //A string with "${expr}" segments, the parts are joined after converting each to a string
+    Interpolation{
+        parts: Box<Vec<Expr>>,
+        line: usize,
+        col: i64
     },
     None
 }
 
+impl Expr{
+    //Only expressions that name a variable can be resolved to a scope
+    pub fn resolution_id(&self) -> Option<usize>{
+        match self{
+            Expr::Variable { name: _ , line: _ , col: _ , id } => Some(*id),
+            Expr::Assign { name: _ , line: _ , column: _ , value: _ , id } => Some(*id),
+            Expr::This { keyword: _ , line: _ , col: _ , id } => Some(*id),
+            Expr::Super { keyword: _ , method: _ , line: _ , col: _ , id } => Some(*id),
+            _ => None
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum UnaryOpType{
     Minus,
     Bang,
+    BitNot,
     Error
 }
 
@@ -74,6 +138,7 @@ impl UnaryOpType{
         match token_type{
             TokenType:: Minus => UnaryOpType::Minus,
             TokenType::Bang => UnaryOpType::Bang,
+            TokenType::Tilde => UnaryOpType::BitNot,
             _ => UnaryOpType::Error,
         }
     }
@@ -91,6 +156,14 @@ pub enum BinaryOpType{
     Minus,
     Slash,
     Star,
+    Mod,
+    IntDivide,
+    Power,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
     Error
 }
 impl BinaryOpType{
@@ -106,14 +179,27 @@ impl BinaryOpType{
             TokenType::Minus => BinaryOpType::Minus,
             TokenType::Slash => BinaryOpType::Slash,
             TokenType::Star => BinaryOpType::Star,
+            TokenType::Mod => BinaryOpType::Mod,
+            TokenType::TildeSlash => BinaryOpType::IntDivide,
+            TokenType::StarStar => BinaryOpType::Power,
+            TokenType::Ampersand => BinaryOpType::BitAnd,
+            TokenType::Pipe => BinaryOpType::BitOr,
+            TokenType::Caret => BinaryOpType::BitXor,
+            TokenType::LessLess => BinaryOpType::ShiftLeft,
+            TokenType::GreaterGreater => BinaryOpType::ShiftRight,
             _ => BinaryOpType::Error,
         }
     }
+
+    pub fn is_bitwise(&self) -> bool{
+        return matches!(self, BinaryOpType::BitAnd | BinaryOpType::BitOr | BinaryOpType::BitXor | BinaryOpType::ShiftLeft | BinaryOpType::ShiftRight);
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum LiteralType{
     Number(f64),
+    Int(i64),
     String(String),
     True,
     False,
@@ -0,0 +1,173 @@
+use std::cell::Cell;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+//Live heap accounting for the tree-walker. Every tracked allocation holds a
+//HeapCharge, which adds its estimated size to a shared counter and takes it
+//off again when the allocation is dropped. The counter is an estimate of what
+//the script's values hold right now, not of everything it has ever made.
+
+#[derive(Debug, Clone, Default)]
+pub struct HeapCounter{
+    bytes: Rc<Cell<usize>>
+}
+
+impl HeapCounter{
+    pub fn live(&self) -> usize{
+        return self.bytes.get();
+    }
+
+    pub fn charge(&self, bytes: usize) -> HeapCharge{
+        self.bytes.set(self.bytes.get() + bytes);
+        return HeapCharge {
+            counter: self.bytes.clone(),
+            bytes: Cell::new(bytes)
+        };
+    }
+}
+
+impl PartialEq for HeapCounter{
+    fn eq(&self, other: &Self) -> bool{
+        return Rc::ptr_eq(&self.bytes, &other.bytes);
+    }
+}
+
+#[derive(Debug)]
+pub struct HeapCharge{
+    counter: Rc<Cell<usize>>,
+    bytes: Cell<usize>
+}
+
+impl HeapCharge{
+    //For allocations that change size after they are made, like an instance gaining a field
+    pub fn grow(&self, bytes: usize) -> (){
+        self.bytes.set(self.bytes.get() + bytes);
+        self.counter.set(self.counter.get() + bytes);
+    }
+
+    pub fn shrink(&self, bytes: usize) -> (){
+        let bytes: usize = bytes.min(self.bytes.get());
+        self.bytes.set(self.bytes.get() - bytes);
+        self.counter.set(self.counter.get() - bytes);
+    }
+}
+
+//A copy is a separate allocation, so it is charged again
+impl Clone for HeapCharge{
+    fn clone(&self) -> Self{
+        self.counter.set(self.counter.get() + self.bytes.get());
+        return HeapCharge {
+            counter: self.counter.clone(),
+            bytes: Cell::new(self.bytes.get())
+        };
+    }
+}
+
+impl Drop for HeapCharge{
+    fn drop(&mut self){
+        self.counter.set(self.counter.get() - self.bytes.get());
+    }
+}
+
+//Charges never make two values unequal
+impl PartialEq for HeapCharge{
+    fn eq(&self, _other: &Self) -> bool{
+        return true;
+    }
+}
+
+//A String or Vec along with the charge for its allocation. Ones made outside the
+//interpreter's allocation points, like literals and test values, carry no charge.
+#[derive(Debug, Clone, Default)]
+pub struct Tracked<T>{
+    value: T,
+    charge: Option<HeapCharge>
+}
+
+impl<T> Tracked<T>{
+    pub fn new(value: T, charge: HeapCharge) -> Self{
+        Tracked {
+            value: value,
+            charge: Some(charge)
+        }
+    }
+
+    //Charges a value that was allocated before its size was known
+    pub fn track(&mut self, charge: HeapCharge) -> (){
+        self.charge = Some(charge);
+    }
+}
+
+impl<T> From<T> for Tracked<T>{
+    fn from(value: T) -> Self{
+        Tracked {
+            value: value,
+            charge: None
+        }
+    }
+}
+
+impl From<&str> for Tracked<String>{
+    fn from(value: &str) -> Self{
+        return Tracked::from(value.to_string());
+    }
+}
+
+impl<T> Deref for Tracked<T>{
+    type Target = T;
+
+    fn deref(&self) -> &T{
+        return &self.value;
+    }
+}
+
+impl<T> DerefMut for Tracked<T>{
+    fn deref_mut(&mut self) -> &mut T{
+        return &mut self.value;
+    }
+}
+
+impl<T: PartialEq> PartialEq for Tracked<T>{
+    fn eq(&self, other: &Self) -> bool{
+        return self.value == other.value;
+    }
+}
+
+impl<T: Hash> Hash for Tracked<T>{
+    fn hash<H: Hasher>(&self, state: &mut H){
+        self.value.hash(state);
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Tracked<T>{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        return self.value.fmt(f);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_charges_are_returned_when_dropped() {
+        let counter = HeapCounter::default();
+        let first = Tracked::new(String::from("abc"), counter.charge(10));
+        let second = first.clone();
+        assert_eq!(counter.live(), 20);
+
+        drop(first);
+        assert_eq!(counter.live(), 10);
+        second.charge.as_ref().unwrap().grow(5);
+        second.charge.as_ref().unwrap().shrink(8);
+        assert_eq!(counter.live(), 7);
+
+        drop(second);
+        assert_eq!(counter.live(), 0);
+        let untracked: Tracked<String> = "abc".into();
+        assert_eq!(counter.live(), 0);
+        assert_eq!(untracked, Tracked::new(String::from("abc"), counter.charge(1)));
+    }
+}
@@ -1,10 +1,17 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::mem;
 use std::rc::Rc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
+use crate::diagnostics::{Diagnostic, DiagnosticKind, Note};
 use crate::environment::*;
+use crate::heap::{HeapCharge, HeapCounter, Tracked};
 use crate::lox_callable::*;
 use crate::lox_instance::LoxInstance;
+use crate::lox_map::LoxMap;
 use crate::scanner::TokenType;
 use crate::expr::{self, BinaryOpType, UnaryOpType};
 use crate::expr::Expr;
@@ -14,36 +21,45 @@ use crate::stmt::*;
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value{
     Number(f64),
-    String(String),
+    Int(i64),
+    String(Tracked<String>),
     Bool(bool),
     UserDefined(UserDefined),
     NativeFunction(NativeFunction),
     LoxClass(LoxClass),
     LoxInstance(Rc<LoxInstance>),
+    List(Rc<RefCell<Tracked<Vec<Value>>>>),
+    Map(Rc<RefCell<LoxMap>>),
     Nil,
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
 pub enum Type{
     Number,
+    Int,
     String,
     Bool,
     UserDefined,
     NativeFunction,
     LoxClass,
     LoxInstance,
+    List,
+    Map,
     Nil
 }
 impl Value{
     pub fn value_type(value: Value) -> Type{
         match value{
             Value::Number(_) => Type::Number,
+            Value::Int(_) => Type::Int,
             Value::String(_) => Type::String,
             Value::Bool(_) => Type::Bool,
             Value::UserDefined(_) => Type::UserDefined,
             Value::NativeFunction(_) => Type::NativeFunction,
             Value::LoxClass(_) => Type::LoxClass,
             Value::LoxInstance(_) => Type::LoxInstance,
+            Value::List(_) => Type::List,
+            Value::Map(_) => Type::Map,
             Value::Nil => Type::Nil
             
         }
@@ -52,41 +68,194 @@ impl Value{
     pub fn value_to_string(value: Value) -> String{
         match value{
             Value::Number(num) => format!("{}", num),
+            Value::Int(num) => format!("{}", num),
             Value::String(str) => format!("{}", str),
             Value::Bool(bool) => format!("{}", bool),
             Value::NativeFunction(nat) => format!("{}", nat.name),
             Value::UserDefined(user) => format!("{}", user.name),
             Value::LoxClass(class) => format!("{}", class.name),
             Value::LoxInstance(inst) => format!("{} instance", inst.klass.name),
+            Value::List(list) => {
+                let elements: Vec<String> = list.borrow()
+                .iter()
+                .map(|element| Value::value_to_string(element.clone()))
+                .collect();
+                format!("[{}]", elements.join(", "))
+            },
+            Value::Map(map) => {
+                let entries: Vec<String> = map.borrow()
+                .entries()
+                .iter()
+                .map(|(key, value)| format!("{}: {}", Value::value_to_string(key.clone()), Value::value_to_string(value.clone())))
+                .collect();
+                format!("{{{}}}", entries.join(", "))
+            },
             Value::Nil => format!("nil")
         }
     }
+
+    //Exact comparison, so a float only equals an Int when it holds that same whole number
+    pub fn int_equals_number(int: i64, num: f64) -> bool{
+        return Value::whole_number(num) == Some(int);
+    }
+
+    //The Int a float holds, if it is a whole number in range
+    pub fn whole_number(num: f64) -> Option<i64>{
+        if num.fract() == 0.0 && num >= i64::MIN as f64 && num < i64::MAX as f64{
+            return Some(num as i64);
+        }
+        return None;
+    }
+
+    //Bitwise operators on two integers, shared by both backends so the error text matches.
+    //'>>' is arithmetic and '<<' drops bits shifted past the top instead of overflowing.
+    pub fn bitwise(operator: BinaryOpType, num1: i64, num2: i64) -> Result<i64, String>{
+        match operator{
+            BinaryOpType::BitAnd => return Ok(num1 & num2),
+            BinaryOpType::BitOr => return Ok(num1 | num2),
+            BinaryOpType::BitXor => return Ok(num1 ^ num2),
+            BinaryOpType::ShiftLeft | BinaryOpType::ShiftRight => {
+                if !(0..64).contains(&num2){
+                    return Err(format!("Shift amount must be between 0 and 63 but got {}", num2));
+                }
+                if operator == BinaryOpType::ShiftLeft{
+                    return Ok(num1 << num2);
+                }
+                return Ok(num1 >> num2);
+            }
+            _ => panic!("Unreachable Bitwise Error")
+        }
+    }
 }
 
 impl Type{
     pub fn type_to_string(in_type: Type) -> String{
         match in_type{
             Type::Number => format!("Number"),
+            Type::Int => format!("Int"),
             Type::String => format!("String"),
             Type::Bool => format!("Bool"),
             Type::NativeFunction => format!("Native Function"),
             Type::UserDefined => format!("User Defined Function"),
             Type::LoxClass => format!("User Defined Class"),
             Type::LoxInstance => format!("User Defined Class Instance"),
+            Type::List => format!("List"),
+            Type::Map => format!("Map"),
             Type::Nil => format!("Nil")
         }
     }
 }
 
+//Each Lox call recurses on the Rust stack, scanner::run gives the interpreter a thread
+//with room for this many of them
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+//How many steps of a traceback are shown before the middle is elided
+const TRACE_LIMIT: usize = 10;
+
+//Approximate heap cost of an instance, a new field on one and a map entry
+const INSTANCE_BYTES: usize = mem::size_of::<LoxInstance>() + mem::size_of::<LoxClass>();
+pub const FIELD_BYTES: usize = mem::size_of::<String>() + mem::size_of::<Value>();
+pub const MAP_ENTRY_BYTES: usize = 2 * mem::size_of::<Value>();
+//and of a string, list or map before counting what is in it
+const STRING_BYTES: usize = mem::size_of::<String>();
+const LIST_BYTES: usize = mem::size_of::<RefCell<Tracked<Vec<Value>>>>();
+const MAP_BYTES: usize = mem::size_of::<RefCell<LoxMap>>();
+
+//The clock is only read every this many steps, reading it every step slows everything down
+const DEADLINE_CHECK_INTERVAL: u64 = 256;
+
+//Limits a script runs under, set on Interpreter::config before interpreting
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterpreterConfig{
+    //User function and class calls that may be running at once before "Stack overflow"
+    pub max_call_depth: usize,
+    //Statements and expressions evaluated, counted across every interpret call
+    pub max_steps: Option<u64>,
+    pub deadline: Option<Instant>,
+    pub interrupt: Option<InterruptFlag>,
+    //Cap on Interpreter::heap_bytes before "Out of memory"
+    pub max_heap_bytes: Option<usize>
+}
+
+impl Default for InterpreterConfig{
+    fn default() -> Self{
+        InterpreterConfig {
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_steps: None,
+            deadline: None,
+            interrupt: None,
+            max_heap_bytes: None
+        }
+    }
+}
+
+//Shared with a host thread, which calls interrupt() to stop the script at its next step
+#[derive(Debug, Clone, Default)]
+pub struct InterruptFlag{
+    flag: Arc<AtomicBool>
+}
+
+impl InterruptFlag{
+    pub fn interrupt(&self) -> (){
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_interrupted(&self) -> bool{
+        return self.flag.load(Ordering::Relaxed);
+    }
+}
+
+impl InterpreterConfig{
+    //Checked once per step by both backends, steps is how many have run including this one
+    pub fn check_step(&self, steps: u64) -> Result<(), (ErrorKind, String)>{
+        if let Some(max_steps) = self.max_steps{
+            if steps > max_steps{
+                return Err((ErrorKind::StepLimit, format!("Step limit of {} exceeded", max_steps)));
+            }
+        }
+        if let Some(interrupt) = &self.interrupt{
+            if interrupt.is_interrupted(){
+                return Err((ErrorKind::Interrupted, format!("Execution interrupted")));
+            }
+        }
+        if let Some(deadline) = self.deadline{
+            if steps % DEADLINE_CHECK_INTERVAL == 1 && Instant::now() >= deadline{
+                return Err((ErrorKind::Timeout, format!("Execution timed out")));
+            }
+        }
+        return Ok(());
+    }
+}
+
+impl PartialEq for InterruptFlag{
+    fn eq(&self, other: &Self) -> bool{
+        return Arc::ptr_eq(&self.flag, &other.flag);
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Interpreter{
     pub statements: Vec<Stmt>,
-    pub globals: Environment,
-    pub environment: Environment,
+    pub globals: Rc<RefCell<Environment>>,
+    pub environment: Rc<RefCell<Environment>>,
     pub return_value: Option<Value>,
-    pub locals: HashMap<String, (Expr, usize)>,
+    pub locals: HashMap<usize, (usize, usize)>,
     pub classes: HashMap<String, LoxClass>,
     pub instances: HashMap<String, LoxInstance>,
+    //One frame per user function or class call still running, innermost last
+    pub call_stack: Vec<CallFrame>,
+    pub config: InterpreterConfig,
+    pub steps: u64,
+    //Approximate bytes held by the strings, instances, fields, lists and maps the script has made
+    pub heap: HeapCounter,
+}
+
+//A call in progress: what was called and where it was called from
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallFrame{
+    pub name: String,
+    pub line: usize,
+    pub column: i64
 }
 
 impl Default for Interpreter{
@@ -97,26 +266,45 @@ impl Default for Interpreter{
         //     environment: Environment::default()
         // }
         let mut globals_env: HashMap<String, (Option<Value>, VarLocation)> = HashMap::new();
-        globals_env.insert(String::from("clock"),
-        (
-            Some(Value::NativeFunction(NativeFunction{ 
-                name: format!("clock"), 
-                arity: 0, 
-                callable: |_, _|{
-                    let start_time = SystemTime::now();
-                    let since_epoch = start_time.duration_since(UNIX_EPOCH).unwrap();
-                    Ok(Value::Number(since_epoch.as_millis() as f64))
-                }, 
-            })),
-            VarLocation{
-                line: 0,
-                col: 0
+        globals_env.insert(String::from("clock"), native(format!("clock"), 0, |_, _|{
+            let start_time = SystemTime::now();
+            let since_epoch = start_time.duration_since(UNIX_EPOCH).unwrap();
+            Ok(Value::Number(since_epoch.as_millis() as f64))
+        }));
+        globals_env.insert(String::from("len"), native(format!("len"), 1, |_, args|{
+            match &args[0]{
+                Value::List(list) => Ok(Value::Int(list.borrow().len() as i64)),
+                Value::Map(map) => Ok(Value::Int(map.borrow().len() as i64)),
+                Value::String(str) => Ok(Value::Int(str.chars().count() as i64)),
+                other => Err(format!("len() expects a list, map or string but got {}",
+                Type::type_to_string(Value::value_type(other.clone()))))
             }
-        ));
+        }));
+        globals_env.insert(String::from("keys"), native(format!("keys"), 1, |_, args|{
+            let map = ensure_map(&args[0], "keys")?;
+            let keys = map.borrow().keys();
+            Ok(Value::List(Rc::new(RefCell::new(keys.into()))))
+        }));
+        globals_env.insert(String::from("values"), native(format!("values"), 1, |_, args|{
+            let map = ensure_map(&args[0], "values")?;
+            let values = map.borrow().values();
+            Ok(Value::List(Rc::new(RefCell::new(values.into()))))
+        }));
+        globals_env.insert(String::from("has"), native(format!("has"), 2, |_, args|{
+            let map = ensure_map(&args[0], "has")?;
+            let found = map.borrow().contains(args[1].clone())?;
+            Ok(Value::Bool(found))
+        }));
+        globals_env.insert(String::from("remove"), native(format!("remove"), 2, |_, args|{
+            let map = ensure_map(&args[0], "remove")?;
+            let removed = map.borrow_mut().remove(args[1].clone())?;
+            Ok(removed.unwrap_or(Value::Nil))
+        }));
 
         let mut globals = Environment::default();
         globals.set_values(globals_env);
         globals.set_enclosing(None);
+        let globals = Rc::new(RefCell::new(globals));
 
         Interpreter { 
             statements: Vec::new(), 
@@ -125,11 +313,37 @@ impl Default for Interpreter{
             return_value: None,
             locals: HashMap::new(),
             classes: HashMap::new(),
-            instances: HashMap::new()
+            instances: HashMap::new(),
+            call_stack: Vec::new(),
+            config: InterpreterConfig::default(),
+            steps: 0,
+            heap: HeapCounter::default()
         }   
     }
 }
 
+fn native(name: String, arity: usize, callable: fn(&mut Interpreter, &[Value]) -> Result<Value, String>) -> (Option<Value>, VarLocation){
+    (
+        Some(Value::NativeFunction(NativeFunction{ 
+            name: name, 
+            arity: arity, 
+            callable: callable, 
+        })),
+        VarLocation{
+            line: 0,
+            col: 0
+        }
+    )
+}
+
+fn ensure_map(value: &Value, function: &str) -> Result<Rc<RefCell<LoxMap>>, String>{
+    match value{
+        Value::Map(map) => Ok(map.clone()),
+        other => Err(format!("{}() expects a map but got {}",
+        function, Type::type_to_string(Value::value_type(other.clone()))))
+    }
+}
+
 impl Interpreter{
     pub fn new(statements: Vec<Stmt>) -> Self{
 // Interpreter{
@@ -146,10 +360,11 @@ impl Interpreter{
     pub fn interpret(&mut self, statements: Vec<Stmt>) -> Result<(), InterpreterError>{
         //let mut interp: Interpreter = Interpreter::new(statements.clone());
         for stmt in statements{
-            let execution: Result<(), InterpreterError> = self.execute(stmt);
+            let execution: Result<(), Unwind> = self.execute(stmt);
             match execution{
                 Ok(stmt) => (),
-                Err(err) => return Err(err)
+                Err(Unwind::Error(err)) => return Err(self.with_traceback(err)),
+                Err(unwind) => return Err(self.with_traceback(unwind.stray_error()))
             }
         }
         return Ok(())
@@ -162,7 +377,32 @@ impl Interpreter{
     //     }
     // }
 
-    fn visit_expression_stmt(&mut self, stmt: Stmt) -> Result<(), InterpreterError>{
+    //Failed calls leave their frames on the call stack, so an error that reaches the top
+    //still has the chain of calls that led to it. Reporting it clears the stack.
+    fn with_traceback(&mut self, err: InterpreterError) -> InterpreterError{
+        let frames: Vec<CallFrame> = mem::take(&mut self.call_stack);
+        let innermost = match frames.last(){
+            Some(frame) => frame,
+            None => return err
+        };
+        let mut steps: Vec<String> = match err.line{
+            0 => vec![format!("in {}", innermost.name)],
+            line => vec![format!("in {} (line {})", innermost.name, line)]
+        };
+        for (depth, frame) in frames.iter().enumerate().rev(){
+            let caller: &str = if depth == 0 { "<script>" } else { &frames[depth - 1].name };
+            steps.push(format!("called from {} (line {})", caller, frame.line));
+        }
+        //Runaway recursion would list every frame, so keep both ends of the chain
+        if steps.len() > TRACE_LIMIT{
+            let hidden: usize = steps.len() - TRACE_LIMIT;
+            let kept: usize = TRACE_LIMIT / 2;
+            steps.splice(kept..kept + hidden, [format!("... {} more calls ...", hidden)]);
+        }
+        return err.with_note(Note::new(steps.join(" "), 0, 0));
+    }
+
+    fn visit_expression_stmt(&mut self, stmt: Stmt) -> Result<(), Unwind>{
         if let Stmt::Expr { expression } = stmt{
             self.evaluate(*expression)?;
             return Ok(());
@@ -172,29 +412,30 @@ impl Interpreter{
         }
     }
 
-    fn visit_function_stmt(&mut self, stmt: Stmt) -> Result<(), InterpreterError>{
-        if let Stmt::Function { name, parameters, body } = stmt.clone(){
-            if self.globals.return_values().contains_key(&name){
+    fn visit_function_stmt(&mut self, stmt: Stmt) -> Result<(), Unwind>{
+        if let Stmt::Function { name, line, column, parameters, body } = stmt.clone(){
+            let shadows_native = matches!(self.globals.borrow().values.get(&name), Some((Some(Value::NativeFunction(_)), _)));
+            if shadows_native{
                 return Err(InterpreterError { 
-                    error_message: format!("Function already defined"), 
-                    line: 0, 
-                    column: 0,
-                    value: Value::Nil 
-                })
+                    error_message: format!("Function already defined at line: {}, column: {}", line, column), 
+                    line: line, 
+                    column: column,
+                    value: Value::Nil,
+                    notes: Vec::new(),
+                    kind: ErrorKind::Runtime
+                }.into())
             }
             else{
                 let function_inside = UserDefined{
                     name: name.clone(),
                     parameters: parameters,
-                    body: *body,
-                    declaration: stmt.clone(),
+                    body: Rc::new(*body),
+                    declaration: Rc::new(stmt.clone()),
                     closure: self.environment.clone(),
                     is_init: false
                 };
-                let function = Value::UserDefined(function_inside.clone());
-                self.environment.define(name.clone(), 0, 0, Some(function));
-                self.environment.user_func.insert(name.clone(), function_inside.clone());
-                self.globals.user_func.insert(name.clone(), function_inside);
+                let function = Value::UserDefined(function_inside);
+                self.define_variable(name, line, column, Some(function));
                 return Ok(())
             }
         }
@@ -203,7 +444,7 @@ impl Interpreter{
         }
     }
 
-    fn visit_if_stmt(&mut self, stmt: Stmt) -> Result<(), InterpreterError>{
+    fn visit_if_stmt(&mut self, stmt: Stmt) -> Result<(), Unwind>{
         if let Stmt::If { condition, then_branch, else_branch } = stmt{
             if Interpreter::is_truthy(self.evaluate(*condition)?){
                 self.execute(*then_branch)?;
@@ -220,10 +461,18 @@ impl Interpreter{
         }
     }
 
-    fn visit_while_stmt(&mut self, stmt: Stmt) -> Result<(), InterpreterError>{
-        if let Stmt::While { condition, body } = stmt{
+    fn visit_while_stmt(&mut self, stmt: Stmt) -> Result<(), Unwind>{
+        if let Stmt::While { condition, body, increment } = stmt{
             while Interpreter::is_truthy(self.evaluate(condition.clone())?){
-                self.execute(*body.clone())?;
+                match self.execute(*body.clone()){
+                    Ok(()) => (),
+                    Err(Unwind::Break) => break,
+                    Err(Unwind::Continue) => (),
+                    Err(unwind) => return Err(unwind)
+                }
+                if let Some(increment) = increment.clone(){
+                    self.evaluate(increment)?;
+                }
             }
             return Ok(());
         }
@@ -232,7 +481,7 @@ impl Interpreter{
         }
     }
 
-    fn visit_print_stmt(&mut self, stmt: Stmt) -> Result<(), InterpreterError>{
+    fn visit_print_stmt(&mut self, stmt: Stmt) -> Result<(), Unwind>{
         if let Stmt::Print { expression } = stmt{
             let value = self.evaluate(*expression)?;
             println!("{}", Value::value_to_string(value));
@@ -243,7 +492,7 @@ impl Interpreter{
         }
     }
 
-    fn visit_var_stmt(&mut self, stmt: Stmt) -> Result<(), InterpreterError>{
+    fn visit_var_stmt(&mut self, stmt: Stmt) -> Result<(), Unwind>{
         if let Stmt::Var { name, line, column, initializer } = stmt{
             let mut val: Value = Value::Nil;
             let mut opt: Option<Value> = None;
@@ -251,11 +500,11 @@ impl Interpreter{
                 val = self.evaluate(initializer.unwrap())?;
             }
             if val == Value::Nil{
-                self.environment.define(name.clone(), line, column, opt.clone());
+                self.define_variable(name.clone(), line, column, opt.clone());
             }
             else{
                 opt.insert(val.clone());
-                self.environment.define(name.clone(), line, column, opt.clone());
+                self.define_variable(name.clone(), line, column, opt.clone());
             }
             // if self.environment == self.globals{
             //     if val == Value::Nil{
@@ -273,16 +522,32 @@ impl Interpreter{
         }
     }
 
-    fn visit_return_stmt(&mut self, stmt: Stmt) -> Result<(), InterpreterError>{
-        if let Stmt::Return { keyword, value } = stmt{
-            match value.clone(){
-                Some(ret) => (),
-                None => return Err(InterpreterError::new(format!("RETURN"), 0, 0, Value::Nil))
-            }
-            let retval = self.evaluate(value.unwrap());
-            match retval{
-                Ok(val) => Err(InterpreterError::new(format!("RETURN"), 0, 0, val)),
-                Err(none) => Err(InterpreterError::new(format!("RETURN"), 0, 0, Value::Nil)) 
+    fn visit_break_stmt(&mut self, stmt: Stmt) -> Result<(), Unwind>{
+        if let Stmt::Break { keyword: _ } = stmt{
+            return Err(Unwind::Break);
+        }
+        else{
+            panic!("Unreachable break error");
+        }
+    }
+
+    fn visit_continue_stmt(&mut self, stmt: Stmt) -> Result<(), Unwind>{
+        if let Stmt::Continue { keyword: _ } = stmt{
+            return Err(Unwind::Continue);
+        }
+        else{
+            panic!("Unreachable continue error");
+        }
+    }
+
+    fn visit_return_stmt(&mut self, stmt: Stmt) -> Result<(), Unwind>{
+        if let Stmt::Return { keyword: _ , value } = stmt{
+            match value{
+                Some(ret) => {
+                    let retval = self.evaluate(ret)?;
+                    return Err(Unwind::Return(retval));
+                }
+                None => return Err(Unwind::Return(Value::Nil))
             }
         }
         else{
@@ -290,18 +555,99 @@ impl Interpreter{
         }
     }
 
+    fn visit_throw_stmt(&mut self, stmt: Stmt) -> Result<(), Unwind>{
+        if let Stmt::Throw { keyword, value } = stmt{
+            let thrown: Value = self.evaluate(value)?;
+            //An object with a message field, like a caught error being rethrown, reports that message
+            let described: String = match &thrown{
+                Value::LoxInstance(instance) if instance.fields.borrow().contains_key("message") => {
+                    Value::value_to_string(instance.fields.borrow()["message"].clone())
+                }
+                _ => self.stringify(thrown.clone(), keyword.line, keyword.column)?
+            };
+            return Err(InterpreterError::thrown(
+                format!("Uncaught exception: {} at line: {}, column: {}", described, keyword.line, keyword.column), 
+                keyword.line, 
+                keyword.column, 
+                thrown
+            ).into());
+        }
+        else{
+            panic!("Unreachable throw error");
+        }
+    }
+
+    //Only errors are caught, a return, break or continue passes through after the finally block runs.
+    //Hitting one of the config's limits skips both the catch and the finally block.
+    fn visit_try_stmt(&mut self, stmt: Stmt) -> Result<(), Unwind>{
+        if let Stmt::Try { keyword: _ , body, catch_name, catch_body, finally_body } = stmt{
+            let depth: usize = self.call_stack.len();
+            let mut result: Result<(), Unwind> = self.execute_block(body, None);
+            if Interpreter::is_aborted(&result){
+                return result;
+            }
+            if let (Some(catch_name), Some(catch_body)) = (catch_name, catch_body){
+                if let Err(Unwind::Error(err)) = result{
+                    //Frames left behind by calls that failed inside the try block
+                    self.call_stack.truncate(depth);
+                    let caught: Value = self.caught_value(err);
+                    let mut environment: Environment = Environment::new(self.environment.clone());
+                    environment.define_slot(catch_name.line, catch_name.column, Some(caught));
+                    result = self.execute_block(catch_body, Some(environment));
+                }
+            }
+            if Interpreter::is_aborted(&result){
+                return result;
+            }
+            if let Some(finally_body) = finally_body{
+                //Leaving the finally block early replaces whatever the try or catch was doing
+                result = self.execute_block(finally_body, None).and(result);
+            }
+            //Failed calls leave their frames for the traceback, so drop them unless an error is still on its way up
+            if !matches!(result, Err(Unwind::Error(_))){
+                self.call_stack.truncate(depth);
+            }
+            return result;
+        }
+        else{
+            panic!("Unreachable try error");
+        }
+    }
+
+    fn is_aborted(result: &Result<(), Unwind>) -> bool{
+        return matches!(result, Err(Unwind::Error(err)) if !err.kind.is_catchable());
+    }
+
+    //A thrown value is caught as is, a built-in error becomes an Error object with message, line and column
+    fn caught_value(&mut self, err: InterpreterError) -> Value{
+        if err.kind == ErrorKind::Thrown{
+            return err.value;
+        }
+        let position = format!(" at line: {}, column: {}", err.line, err.column);
+        let message: String = err.error_message.strip_suffix(&position).unwrap_or(&err.error_message).to_string();
+        let error_class = LoxClass{
+            name: format!("Error"),
+            superclass: Box::new(None),
+            methods: HashMap::new()
+        };
+        let instance = LoxInstance::new(error_class);
+        instance.set(format!("message"), Value::String(message.into()));
+        instance.set(format!("line"), Value::Int(err.line as i64));
+        instance.set(format!("column"), Value::Int(err.column));
+        return Value::LoxInstance(Rc::new(instance));
+    }
+
     fn visit_assign_expr(&mut self, expr: Expr) -> Result<Value, InterpreterError>{
-        if let Expr::Assign { name, line, column, value } = expr{
+        if let Expr::Assign { name, line, column, value, id } = expr{
             let val: Value = self.evaluate(*value)?;
             //let expression = self.environment.assign(name, line, column, &val.clone());
 
             let expression: Result<(), InterpreterError>;
-            if let Some(result) = self.locals.get(&name){
-                //println!("Checking for {} at depth {}", name.clone(), result.1.clone());
-                expression = self.environment.assign_at(name, line, column, &val, result.1);
+            if let Some((distance, slot)) = self.locals.get(&id).copied(){
+                expression = self.environment.borrow_mut().assign_slot_at(distance, slot, &name, line, column, &val);
             }
             else{
-                expression = self.globals.assign(name, line, column, &val);
+                expression = self.globals.borrow_mut().assign(name, line, column, &val);
             }
             
             match expression{
@@ -317,7 +663,8 @@ impl Interpreter{
     fn visit_literal_expr(&mut self, literal: expr::LiteralType) -> Value{
         match literal{
             expr::LiteralType::Number(num) => return Value::Number(num),
-            expr::LiteralType::String(str) => return Value::String(str),
+            expr::LiteralType::Int(num) => return Value::Int(num),
+            expr::LiteralType::String(str) => return Value::String(str.into()),
             expr::LiteralType::True => return Value::Bool(true),
             expr::LiteralType::False => return Value::Bool(false),
             expr::LiteralType::Nil => return Value::Nil
@@ -329,13 +676,22 @@ impl Interpreter{
             let right_val: Value = self.evaluate(*right)?;
             match (operator, right_val.clone()){
                 (UnaryOpType::Minus, Value::Number(num)) => return Ok(Value::Number(-num)),
+                (UnaryOpType::Minus, Value::Int(num)) => {
+                    match num.checked_neg(){
+                        Some(negated) => return Ok(Value::Int(negated)),
+                        None => return Err(Interpreter::overflow_error(line, col))
+                    }
+                }
                 (UnaryOpType::Bang, _) => return Ok(Value::Bool(!Interpreter::is_truthy(right_val.clone()))),
+                (UnaryOpType::BitNot, _) => return Ok(Value::Int(!Interpreter::bitwise_operand(right_val, line, col)?)),
                 (_, _) => return Err(InterpreterError { 
                     error_message: format!("Incorrect use of unary operator {:?} on object of type {:?} at line: {}, column: {}", 
                     operator, Type::type_to_string(Value::value_type(right_val)), line, col), 
                     line: line, 
                     column: col,
-                    value: Value::Nil
+                    value: Value::Nil,
+                    notes: Vec::new(),
+                    kind: ErrorKind::Runtime
                 })
             }
         }
@@ -348,12 +704,32 @@ impl Interpreter{
         if let Expr::Binary { left, operator , right, line , col } = expr{
             let left_val: Value = self.evaluate(*left)?;
             let right_val: Value = self.evaluate(*right)?;
+            if operator.is_bitwise(){
+                let num1: i64 = Interpreter::bitwise_operand(left_val, line, col)?;
+                let num2: i64 = Interpreter::bitwise_operand(right_val, line, col)?;
+                match Value::bitwise(operator, num1, num2){
+                    Ok(result) => return Ok(Value::Int(result)),
+                    Err(message) => return Err(InterpreterError::new(format!("{} at line: {}, column: {}", message, line, col), line, col, Value::Nil))
+                }
+            }
+            if let (Value::Int(num1), Value::Int(num2)) = (&left_val, &right_val){
+                if let Some(result) = Interpreter::int_binary(operator, *num1, *num2, line, col)?{
+                    return Ok(result);
+                }
+            }
+            //Mixing an Int with a float promotes the Int
+            let (left_val, right_val) = match (left_val, right_val){
+                (Value::Int(num1), Value::Number(num2)) => (Value::Number(num1 as f64), Value::Number(num2)),
+                (Value::Number(num1), Value::Int(num2)) => (Value::Number(num1), Value::Number(num2 as f64)),
+                (left_val, right_val) => (left_val, right_val)
+            };
             match(operator, left_val.clone(), right_val.clone()){
                 (BinaryOpType::Plus, Value::Number(num1), Value::Number(num2)) => {
                     return Ok(Value::Number(num1 + num2))
                 },
                 (BinaryOpType::Plus, Value::String(str1), Value::String(str2)) => {
-                    return Ok(Value::String(format!("{}{}", str1, str2)))
+                    let charge = self.charge(STRING_BYTES + str1.len() + str2.len(), line, col)?;
+                    return Ok(Value::String(Tracked::new(format!("{}{}", str1, str2), charge)))
                 },
                 (BinaryOpType::Minus, Value::Number(num1), Value::Number(num2)) => {
                     return Ok(Value::Number(num1 - num2))
@@ -364,7 +740,9 @@ impl Interpreter{
                             error_message: format!("Divide by zero error at line: {}, column: {}", line, col), 
                             line: line, 
                             column: col,
-                            value: Value::Nil 
+                            value: Value::Nil,
+                            notes: Vec::new(),
+                            kind: ErrorKind::Runtime
                         })
                     }
                     else {
@@ -374,6 +752,35 @@ impl Interpreter{
                 (BinaryOpType::Star, Value::Number(num1), Value::Number(num2)) => {
                     return Ok(Value::Number(num1 * num2))
                 },
+                (BinaryOpType::Mod, Value::Number(num1), Value::Number(num2)) => {
+                    //Truncated remainder: the result takes the sign of the dividend, so -7 % 3 is -1
+                    if num2 == 0.0 {
+                        return Err(InterpreterError { 
+                            error_message: format!("Modulo by zero error at line: {}, column: {}", line, col), 
+                            line: line, 
+                            column: col,
+                            value: Value::Nil,
+                            notes: Vec::new(),
+                            kind: ErrorKind::Runtime
+                        })
+                    }
+                    else {
+                        return Ok(Value::Number(num1 % num2))
+                    }
+                },
+                (BinaryOpType::IntDivide, Value::Number(num1), Value::Number(num2)) => {
+                    if num2 == 0.0 {
+                        return Err(Interpreter::divide_by_zero_error(line, col))
+                    }
+                    let quotient = (num1 / num2).trunc();
+                    if !quotient.is_finite() || quotient < i64::MIN as f64 || quotient >= i64::MAX as f64 {
+                        return Err(Interpreter::overflow_error(line, col))
+                    }
+                    return Ok(Value::Int(quotient as i64))
+                },
+                (BinaryOpType::Power, Value::Number(num1), Value::Number(num2)) => {
+                    return Ok(Value::Number(num1.powf(num2)))
+                },
                 (BinaryOpType::Greater, Value::Number(num1), Value::Number(num2)) => {
                     return Ok(Value::Bool(num1 > num2))
                 },
@@ -398,7 +805,9 @@ impl Interpreter{
                         operator, Type::type_to_string(Value::value_type(left_val)), Type::type_to_string(Value::value_type(right_val)), line, col), 
                         line: line, 
                         column: col,
-                        value: Value::Nil 
+                        value: Value::Nil,
+                        notes: Vec::new(),
+                        kind: ErrorKind::Runtime
                     })
                 }
             }
@@ -408,6 +817,74 @@ impl Interpreter{
         }
     }
 
+    //Returns None for operators that are not arithmetic or comparisons, like ==.
+    //Int arithmetic is checked, overflowing raises an error instead of wrapping.
+    //'/' always gives a float, '~/' is the truncating integer division.
+    fn int_binary(operator: BinaryOpType, num1: i64, num2: i64, line: usize, col: i64) -> Result<Option<Value>, InterpreterError>{
+        let result = match operator{
+            BinaryOpType::Plus => num1.checked_add(num2),
+            BinaryOpType::Minus => num1.checked_sub(num2),
+            BinaryOpType::Star => num1.checked_mul(num2),
+            BinaryOpType::Slash => {
+                if num2 == 0 {
+                    return Err(Interpreter::divide_by_zero_error(line, col));
+                }
+                return Ok(Some(Value::Number(num1 as f64 / num2 as f64)));
+            }
+            BinaryOpType::IntDivide => {
+                if num2 == 0 {
+                    return Err(Interpreter::divide_by_zero_error(line, col));
+                }
+                num1.checked_div(num2)
+            }
+            BinaryOpType::Mod => {
+                if num2 == 0 {
+                    return Err(InterpreterError::new(format!("Modulo by zero error at line: {}, column: {}", line, col), line, col, Value::Nil));
+                }
+                num1.checked_rem(num2)
+            }
+            BinaryOpType::Power => {
+                //A negative exponent can't stay an Int, so it gives a float like '/'
+                if num2 < 0 {
+                    return Ok(Some(Value::Number((num1 as f64).powf(num2 as f64))));
+                }
+                u32::try_from(num2).ok().and_then(|exponent| num1.checked_pow(exponent))
+            }
+            BinaryOpType::Greater => return Ok(Some(Value::Bool(num1 > num2))),
+            BinaryOpType::GreaterEqual => return Ok(Some(Value::Bool(num1 >= num2))),
+            BinaryOpType::Less => return Ok(Some(Value::Bool(num1 < num2))),
+            BinaryOpType::LessEqual => return Ok(Some(Value::Bool(num1 <= num2))),
+            _ => return Ok(None)
+        };
+        match result{
+            Some(num) => return Ok(Some(Value::Int(num))),
+            None => return Err(Interpreter::overflow_error(line, col))
+        }
+    }
+
+    //Bitwise operators take Ints, or floats holding a whole number
+    fn bitwise_operand(value: Value, line: usize, col: i64) -> Result<i64, InterpreterError>{
+        let found: String = match value{
+            Value::Int(num) => return Ok(num),
+            Value::Number(num) => {
+                if let Some(whole) = Value::whole_number(num){
+                    return Ok(whole);
+                }
+                Value::value_to_string(Value::Number(num))
+            }
+            other => Type::type_to_string(Value::value_type(other))
+        };
+        return Err(InterpreterError::new(format!("Bitwise operators need integers but got {} at line: {}, column: {}", found, line, col), line, col, Value::Nil));
+    }
+
+    fn overflow_error(line: usize, col: i64) -> InterpreterError{
+        return InterpreterError::new(format!("Integer overflow at line: {}, column: {}", line, col), line, col, Value::Nil);
+    }
+
+    fn divide_by_zero_error(line: usize, col: i64) -> InterpreterError{
+        return InterpreterError::new(format!("Divide by zero error at line: {}, column: {}", line, col), line, col, Value::Nil);
+    }
+
     fn visit_call_expr(&mut self, expr: Expr) -> Result<Value, InterpreterError>{
         if let Expr::Call { callee , paren , arguments } = expr{
             let callee_value: Value = self.evaluate(*callee)?;
@@ -423,23 +900,34 @@ impl Interpreter{
                 Value::NativeFunction(function) =>{
                     if args.len() != function.arity() {
                         return Err(InterpreterError { 
-                            error_message: format!("Expected {} arguments but got {}",
-                            function.arity(), args.len()), 
-                            line: 0, 
-                            column: 0,
-                            value: Value::Nil 
+                            error_message: format!("Expected {} arguments but got {} at line: {}, column: {}",
+                            function.arity(), args.len(), paren.line, paren.column), 
+                            line: paren.line, 
+                            column: paren.column,
+                            value: Value::Nil,
+                            notes: Vec::new(),
+                            kind: ErrorKind::Runtime
                         })
                     }
                     else{
 
                         let func =  function.call(self, &args);
                         match func{
+                            //keys() and values() build new lists. Natives cannot be stopped before they
+                            //allocate, but the list is no bigger than the map it was copied from.
+                            Ok(Value::List(list)) => {
+                                let charge = self.charge(LIST_BYTES + list.borrow().len() * mem::size_of::<Value>(), paren.line, paren.column)?;
+                                list.borrow_mut().track(charge);
+                                return Ok(Value::List(list))
+                            }
                             Ok(func) => return Ok(func),
                             Err(err) => return Err(InterpreterError { 
-                                error_message: err, 
-                                line: 0, 
-                                column: 0,
-                                value: Value::Nil  
+                                error_message: format!("{} at line: {}, column: {}", err, paren.line, paren.column), 
+                                line: paren.line, 
+                                column: paren.column,
+                                value: Value::Nil,
+                                notes: Vec::new(),
+                                kind: ErrorKind::Runtime
                             })
                         }
                     }
@@ -447,20 +935,26 @@ impl Interpreter{
                 Value::UserDefined(function) =>{
                     if args.len() != function.arity() {
                         return Err(InterpreterError { 
-                            error_message: format!("Expected {} arguments but got {}",
-                            function.arity(), args.len()), 
-                            line: 0, 
-                            column: 0,
-                            value: Value::Nil 
+                            error_message: format!("Expected {} arguments but got {} at line: {}, column: {}",
+                            function.arity(), args.len(), paren.line, paren.column), 
+                            line: paren.line, 
+                            column: paren.column,
+                            value: Value::Nil,
+                            notes: Vec::new(),
+                            kind: ErrorKind::Runtime
                         })
                     }
                     else{
                         //println!("User func call {}", args.len());
                         //let current_interp = self.environment.enclosing.clone();
+                        self.push_frame(function.name.clone(), paren.line, paren.column)?;
                         let func =  function.call(self, &args);
                         //self.environment.enclosing = current_interp;
                         match func{
-                            Ok(func) => return Ok(func),
+                            Ok(func) => {
+                                self.call_stack.pop();
+                                return Ok(func)
+                            }
                             Err(err) => return Err(err)
                         }
                     }
@@ -468,27 +962,37 @@ impl Interpreter{
                 Value::LoxClass(class) =>{
                     if args.len() != class.arity() {
                         return Err(InterpreterError { 
-                            error_message: format!("Expected {} arguments but got {}",
-                            class.arity(), args.len()), 
-                            line: 0, 
-                            column: 0,
-                            value: Value::Nil 
+                            error_message: format!("Expected {} arguments but got {} at line: {}, column: {}",
+                            class.arity(), args.len(), paren.line, paren.column), 
+                            line: paren.line, 
+                            column: paren.column,
+                            value: Value::Nil,
+                            notes: Vec::new(),
+                            kind: ErrorKind::Runtime
                         })
                     }
                     else{
+                        //create_instance charges for the instance once there is one
+                        self.reserve(INSTANCE_BYTES, paren.line, paren.column)?;
+                        self.push_frame(class.name.clone(), paren.line, paren.column)?;
                         let klas = class.call(self, &args);
                         match klas{
-                            Ok(klas) => return Ok(klas),
+                            Ok(klas) => {
+                                self.call_stack.pop();
+                                return Ok(klas)
+                            }
                             Err(err) => return Err(err)
                         }
                     }
                 }
                 _ => {
                     return Err(InterpreterError { 
-                        error_message: format!("Can only call functions and classes"), 
-                        line: 0, 
-                        column: 0,
-                        value: Value::Nil 
+                        error_message: format!("Can only call functions and classes at line: {}, column: {}", paren.line, paren.column), 
+                        line: paren.line, 
+                        column: paren.column,
+                        value: Value::Nil,
+                        notes: Vec::new(),
+                        kind: ErrorKind::Runtime
                     });
                 }
             }
@@ -499,9 +1003,21 @@ impl Interpreter{
         }
     }
 
+    fn push_frame(&mut self, name: String, line: usize, column: i64) -> Result<(), InterpreterError>{
+        if self.call_stack.len() >= self.config.max_call_depth{
+            return Err(InterpreterError::new(format!("Stack overflow at line: {}, column: {}", line, column), line, column, Value::Nil));
+        }
+        self.call_stack.push(CallFrame{
+            name: name,
+            line: line,
+            column: column
+        });
+        return Ok(());
+    }
+
     fn visit_this_expr(&mut self, expr: Expr) -> Result<Value, InterpreterError>{
-        if let Expr::This { keyword } = expr.clone(){
-            return self.lookup_variable(keyword, expr);
+        if let Expr::This { keyword: _ , line: _ , col: _ , id: _ } = expr{
+            return self.lookup_variable(&expr);
         }
         else{
             panic!("Unreachable This Error");
@@ -509,46 +1025,38 @@ impl Interpreter{
     }
 
     fn visit_super_expr(&mut self, expr: Expr) -> Result<Value, InterpreterError>{
-        if let Expr::Super { keyword, method } = expr.clone(){
-            let val = self.lookup_variable(keyword, expr)?;
-            let env = self.environment.clone();
-            match val {
-                Value::LoxClass(mut clas) =>{
-                    let super_method = clas.find_superclass_method(method);
-                    match super_method{
-                        Ok(mut sup) => {
-                            let inst = self.environment.get(&Expr::This { keyword: format!("this") })?;
-                            if let Value::LoxInstance(instance) = inst{
-                                //println!("HEREHERE");
-                                let ret = Value::UserDefined(sup.bind(&instance));
-                                self.environment = env;
-                                return Ok(ret);
-                            }
-                            else {
-                                return Err(InterpreterError::new(
-                                    format!("Cannot access super method"), 
-                                    0, 
-                                    0, 
-                                    Value::Nil
-                                ))
-                            }
-                        }
-                        Err(err) => return Err(InterpreterError::new(
-                            format!("Cannot access super method"), 
-                            0, 
-                            0, 
+        if let Expr::Super { keyword, method, line, col, id } = expr{
+            let (distance, slot) = match self.locals.get(&id){
+                Some(resolved) => *resolved,
+                None => return Err(InterpreterError::new(
+                    format!("Cannot access super method at line: {}, column: {}", line, col), 
+                    line, 
+                    col, 
+                    Value::Nil
+                ))
+            };
+            let superclass = self.environment.borrow().get_slot_at(distance, slot, &keyword, line, col)?;
+            //bind() puts 'this' in the first slot of the scope just inside the one holding 'super'
+            let object = self.environment.borrow().get_slot_at(distance - 1, 0, "this", line, col)?;
+            match (superclass, object){
+                (Value::LoxClass(superclass), Value::LoxInstance(instance)) => {
+                    match superclass.find_method(method.clone()){
+                        Ok(mut super_method) => return Ok(Value::UserDefined(super_method.bind(&instance))),
+                        Err(_) => return Err(InterpreterError::new(
+                            format!("Undefined property '{}' at line: {}, column: {}", method, line, col), 
+                            line, 
+                            col, 
                             Value::Nil
                         ))
                     }
                 }
                 _ => return Err(InterpreterError::new(
-                     format!("Cannot access super method"), 
-                     0, 
-                     0, 
+                     format!("Cannot access super method at line: {}, column: {}", line, col), 
+                     line, 
+                     col, 
                      Value::Nil
                 ))
             }
-
         }
         else{
             panic!("Unreachable Super Error");
@@ -556,10 +1064,10 @@ impl Interpreter{
     }
 
     fn visit_get_expr(&mut self, expr: Expr) -> Result<Value, InterpreterError>{
-        if let Expr::Get { object, name } = expr{
+        if let Expr::Get { object, name, line, col } = expr{
             let value = self.evaluate(*object)?;
-            let inst = Self::ensure_instance(value)?;
-            return Ok(inst.get(name))?;
+            let inst = Self::ensure_instance(value, line, col)?;
+            return Ok(inst.get(name, line, col))?;
             // if let Value::LoxInstance(val) = value{
             //     return Ok(val.)?;
             // }
@@ -578,15 +1086,19 @@ impl Interpreter{
     }
 
     fn visit_set_expr(&mut self, expr: Expr) -> Result<Value, InterpreterError>{
-        if let Expr::Set { object, name, value } = expr{
+        if let Expr::Set { object, name, value, line, col } = expr{
             let old_value = self.evaluate(*object)?;
-            let mut instance = Interpreter::ensure_instance(old_value.clone())?;
+            let mut instance = Interpreter::ensure_instance(old_value.clone(), line, col)?;
             //let new_val = self.evaluate(*value)?;
             //instance.set(name.clone(), new_val.clone());
             //self.environment.instances.insert(name.clone(), instance);
             //return Ok(new_val)
             //if let Value::LoxInstance( val) = old_value{
                 let new_val = self.evaluate(*value)?;
+                //The instance charges itself for the new field
+                if !instance.fields.borrow().contains_key(&name){
+                    self.reserve(FIELD_BYTES + name.len(), line, col)?;
+                }
                 instance.set(name, new_val.clone());
                 return Ok(new_val);
             //}
@@ -596,24 +1108,251 @@ impl Interpreter{
         }
     }
 
-    fn ensure_instance(val: Value) -> Result<Rc<LoxInstance>, InterpreterError>{
+    fn visit_list_expr(&mut self, expr: Expr) -> Result<Value, InterpreterError>{
+        if let Expr::List { elements, line, col } = expr{
+            let charge = self.charge(LIST_BYTES + elements.len() * mem::size_of::<Value>(), line, col)?;
+            let mut values: Vec<Value> = Vec::with_capacity(elements.len());
+            for element in *elements{
+                values.push(self.evaluate(element)?);
+            }
+            return Ok(Value::List(Rc::new(RefCell::new(Tracked::new(values, charge)))));
+        }
+        else{
+            panic!("Unreachable List Error");
+        }
+    }
+
+    fn visit_map_expr(&mut self, expr: Expr) -> Result<Value, InterpreterError>{
+        if let Expr::Map { entries, line, col } = expr{
+            //Entries charge themselves as they are inserted
+            let mut map = LoxMap::new();
+            map.track(self.charge(MAP_BYTES, line, col)?);
+            for (key, value) in *entries{
+                let key_val = self.evaluate(key)?;
+                let value_val = self.evaluate(value)?;
+                //A repeated key replaces its value without a new entry
+                if !map.contains(key_val.clone()).unwrap_or(true){
+                    self.reserve(MAP_ENTRY_BYTES, line, col)?;
+                }
+                if let Err(err) = map.insert(key_val, value_val){
+                    return Err(InterpreterError { 
+                        error_message: format!("{} at line: {}, column: {}", err, line, col), 
+                        line: line, 
+                        column: col, 
+                        value: Value::Nil,
+                        notes: Vec::new(),
+                        kind: ErrorKind::Runtime
+                    })
+                }
+            }
+            return Ok(Value::Map(Rc::new(RefCell::new(map))));
+        }
+        else{
+            panic!("Unreachable Map Error");
+        }
+    }
+
+    fn visit_lambda_expr(&mut self, expr: Expr) -> Result<Value, InterpreterError>{
+        if let Expr::Lambda { parameters, body, line, col } = expr{
+            let function = UserDefined{
+                name: format!("lambda"),
+                parameters: parameters.clone(),
+                body: Rc::new(*body.clone()),
+                declaration: Rc::new(Stmt::Function { name: format!("lambda"), line: line, column: col, parameters: parameters, body: body }),
+                closure: self.environment.clone(),
+                is_init: false
+            };
+            return Ok(Value::UserDefined(function));
+        }
+        else{
+            panic!("Unreachable Lambda Error");
+        }
+    }
+
+    fn visit_interpolation_expr(&mut self, expr: Expr) -> Result<Value, InterpreterError>{
+        if let Expr::Interpolation { parts, line, col } = expr{
+            let mut text = String::new();
+            let charge: HeapCharge = self.charge(STRING_BYTES, line, col)?;
+            for part in *parts{
+                let value = self.evaluate(part)?;
+                let piece: String = self.stringify(value, line, col)?;
+                self.reserve(piece.len(), line, col)?;
+                charge.grow(piece.len());
+                text.push_str(&piece);
+            }
+            return Ok(Value::String(Tracked::new(text, charge)));
+        }
+        else{
+            panic!("Unreachable Interpolation Error");
+        }
+    }
+
+    //Instances whose class defines a toString() method are printed through it
+    fn stringify(&mut self, value: Value, line: usize, col: i64) -> Result<String, InterpreterError>{
+        if let Value::LoxInstance(instance) = &value{
+            if let Ok(mut method) = instance.klass.find_method(format!("toString")){
+                if method.arity() == 0{
+                    self.push_frame(format!("toString"), line, col)?;
+                    let result = method.bind(instance).call(self, &Vec::new())?;
+                    self.call_stack.pop();
+                    return Ok(Value::value_to_string(result));
+                }
+            }
+        }
+        return Ok(Value::value_to_string(value));
+    }
+
+    fn visit_index_expr(&mut self, expr: Expr) -> Result<Value, InterpreterError>{
+        if let Expr::Index { object, index, line, col } = expr{
+            let object_val = self.evaluate(*object)?;
+            let index_val = self.evaluate(*index)?;
+            match object_val{
+                Value::List(list) => {
+                    let position = Interpreter::list_position(list.borrow().len(), index_val, line, col)?;
+                    return Ok(list.borrow()[position].clone());
+                }
+                Value::Map(map) => {
+                    let found = map.borrow().get(index_val.clone());
+                    match found{
+                        Ok(Some(val)) => return Ok(val),
+                        Ok(None) => return Err(InterpreterError { 
+                            error_message: format!("Undefined key '{}' at line: {}, column: {}",
+                            Value::value_to_string(index_val), line, col), 
+                            line: line, 
+                            column: col, 
+                            value: Value::Nil,
+                            notes: Vec::new(),
+                            kind: ErrorKind::Runtime
+                        }),
+                        Err(err) => return Err(InterpreterError { 
+                            error_message: format!("{} at line: {}, column: {}", err, line, col), 
+                            line: line, 
+                            column: col, 
+                            value: Value::Nil,
+                            notes: Vec::new(),
+                            kind: ErrorKind::Runtime
+                        })
+                    }
+                }
+                _ => return Err(InterpreterError { 
+                    error_message: format!("Can only index into lists and maps, found {} at line: {}, column: {}",
+                    Type::type_to_string(Value::value_type(object_val)), line, col), 
+                    line: line, 
+                    column: col, 
+                    value: Value::Nil,
+                    notes: Vec::new(),
+                    kind: ErrorKind::Runtime
+                })
+            }
+        }
+        else{
+            panic!("Unreachable Index Error");
+        }
+    }
+
+    fn visit_index_set_expr(&mut self, expr: Expr) -> Result<Value, InterpreterError>{
+        if let Expr::IndexSet { object, index, value, line, col } = expr{
+            let object_val = self.evaluate(*object)?;
+            let index_val = self.evaluate(*index)?;
+            let new_val = self.evaluate(*value)?;
+            match object_val{
+                Value::List(list) => {
+                    let position = Interpreter::list_position(list.borrow().len(), index_val, line, col)?;
+                    list.borrow_mut()[position] = new_val.clone();
+                    return Ok(new_val);
+                }
+                Value::Map(map) => {
+                    //The map charges itself for a new entry
+                    if !map.borrow().contains(index_val.clone()).unwrap_or(true){
+                        self.reserve(MAP_ENTRY_BYTES, line, col)?;
+                    }
+                    let inserted = map.borrow_mut().insert(index_val, new_val.clone());
+                    match inserted{
+                        Ok(()) => return Ok(new_val),
+                        Err(err) => return Err(InterpreterError { 
+                            error_message: format!("{} at line: {}, column: {}", err, line, col), 
+                            line: line, 
+                            column: col, 
+                            value: Value::Nil,
+                            notes: Vec::new(),
+                            kind: ErrorKind::Runtime
+                        })
+                    }
+                }
+                _ => return Err(InterpreterError { 
+                    error_message: format!("Can only index into lists and maps, found {} at line: {}, column: {}",
+                    Type::type_to_string(Value::value_type(object_val)), line, col), 
+                    line: line, 
+                    column: col, 
+                    value: Value::Nil,
+                    notes: Vec::new(),
+                    kind: ErrorKind::Runtime
+                })
+            }
+        }
+        else{
+            panic!("Unreachable Index Set Error");
+        }
+    }
+
+    fn list_position(length: usize, index: Value, line: usize, col: i64) -> Result<usize, InterpreterError>{
+        match index{
+            Value::Int(num) => {
+                if num < 0 || num >= length as i64{
+                    return Err(InterpreterError::new(
+                        format!("Index {} out of bounds for list of length {} at line: {}, column: {}", num, length, line, col), 
+                        line, 
+                        col, 
+                        Value::Nil))
+                }
+                return Ok(num as usize);
+            }
+            Value::Number(num) if num.fract() == 0.0 => {
+                if num < 0.0 || num >= length as f64{
+                    return Err(InterpreterError { 
+                        error_message: format!("Index {} out of bounds for list of length {} at line: {}, column: {}",
+                        num, length, line, col), 
+                        line: line, 
+                        column: col, 
+                        value: Value::Nil,
+                        notes: Vec::new(),
+                        kind: ErrorKind::Runtime
+                    })
+                }
+                return Ok(num as usize);
+            }
+            _ => return Err(InterpreterError { 
+                error_message: format!("List index must be a whole number, found {} at line: {}, column: {}",
+                Value::value_to_string(index), line, col), 
+                line: line, 
+                column: col, 
+                value: Value::Nil,
+                notes: Vec::new(),
+                kind: ErrorKind::Runtime
+            })
+        }
+    }
+
+    fn ensure_instance(val: Value, line: usize, col: i64) -> Result<Rc<LoxInstance>, InterpreterError>{
         if let Value::LoxInstance(inst) = val{
             Ok(inst)
         }
         else{
             return Err(InterpreterError { 
-                error_message: format!("Only objects have properties"), 
-                line: 0, 
-                column: 0, 
-                value: Value::Nil 
+                error_message: format!("Only objects have properties at line: {}, column: {}", line, col), 
+                line: line, 
+                column: col, 
+                value: Value::Nil,
+                notes: Vec::new(),
+                kind: ErrorKind::Runtime
             })
         }
     }
 
     fn visit_variable_expr(&mut self, expr: Expr) -> Result<Value, InterpreterError>{
-        if let Expr::Variable { name , line: _ , col: _ } = expr.clone(){
+        if let Expr::Variable { name: _ , line: _ , col: _ , id: _ } = expr{
             //return self.environment.get(&expr);
-            return self.lookup_variable(name, expr);
+            return self.lookup_variable(&expr);
         }
         panic!("Unreachable Variable Error");
     }
@@ -654,13 +1393,19 @@ impl Interpreter{
         match(left_value, right_value){
             (Value::Nil, Value::Nil) => true,
             (Value::Number(num1), Value::Number(num2)) => return num1.eq(&num2),
+            (Value::Int(num1), Value::Int(num2)) => return num1 == num2,
+            (Value::Int(num1), Value::Number(num2)) => return Value::int_equals_number(num1, num2),
+            (Value::Number(num1), Value::Int(num2)) => return Value::int_equals_number(num2, num1),
             (Value::String(str1), Value::String(str2)) => return str1 == str2,
             (Value::Bool(bool1), Value::Bool(bool2)) => bool1 == bool2,
+            (Value::List(list1), Value::List(list2)) => Rc::ptr_eq(&list1, &list2),
+            (Value::Map(map1), Value::Map(map2)) => Rc::ptr_eq(&map1, &map2),
             (_, _) => false
         }
     }
 
     pub fn evaluate(&mut self, expr: Expr) -> Result<Value, InterpreterError>{
+        self.step()?;
         if let Expr::Grouping { expression } = expr{
             return self.evaluate(*expression);
         }
@@ -673,10 +1418,10 @@ impl Interpreter{
         else if let Expr::Literal { value } = expr{
             return Ok(self.visit_literal_expr(value));
         }
-        else if let Expr::Assign { name: _ , line: _, column: _, value: _ } = expr{
+        else if let Expr::Assign { name: _ , line: _, column: _, value: _ , id: _ } = expr{
             return Ok(self.visit_assign_expr(expr))?;
         }
-        else if let Expr::Variable { name: _ , line: _ , col: _ } = expr{
+        else if let Expr::Variable { name: _ , line: _ , col: _ , id: _ } = expr{
             return Ok(self.visit_variable_expr(expr))?;
         }
         else if let Expr::Logical { left: _ , operator: _ , right: _ } = expr{
@@ -685,88 +1430,151 @@ impl Interpreter{
         else if let Expr::Call { callee: _ , paren: _ , arguments: _ } = expr{
             return Ok(self.visit_call_expr(expr))?;
         }
-        else if let Expr::Get { object: _ , name: _ } = expr{
+        else if let Expr::Get { object: _ , name: _ , line: _ , col: _ } = expr{
             return Ok(self.visit_get_expr(expr))?;
         }
-        else if let Expr::Set { object: _ , name: _ , value: _ } = expr{
+        else if let Expr::Set { object: _ , name: _ , value: _ , line: _ , col: _ } = expr{
             return Ok(self.visit_set_expr(expr))?;
         }
-        else if let Expr::This { keyword: _ } = expr{
+        else if let Expr::This { keyword: _ , line: _ , col: _ , id: _ } = expr{
             return Ok(self.visit_this_expr(expr))?;
         }
-        else if let Expr::Super { keyword: _ , method: _ } = expr{
+        else if let Expr::Super { keyword: _ , method: _ , line: _ , col: _ , id: _ } = expr{
             return Ok(self.visit_super_expr(expr))?;
         }
+        else if let Expr::List { elements: _ , line: _ , col: _ } = expr{
+            return Ok(self.visit_list_expr(expr))?;
+        }
+        else if let Expr::Index { object: _ , index: _ , line: _ , col: _ } = expr{
+            return Ok(self.visit_index_expr(expr))?;
+        }
+        else if let Expr::IndexSet { object: _ , index: _ , value: _ , line: _ , col: _ } = expr{
+            return Ok(self.visit_index_set_expr(expr))?;
+        }
+        else if let Expr::Map { entries: _ , line: _ , col: _ } = expr{
+            return Ok(self.visit_map_expr(expr))?;
+        }
+        else if let Expr::Lambda { parameters: _ , body: _ , line: _ , col: _ } = expr{
+            return Ok(self.visit_lambda_expr(expr))?;
+        }
+        else if let Expr::Interpolation { parts: _ , line: _ , col: _ } = expr{
+            return Ok(self.visit_interpolation_expr(expr))?;
+        }
         else{
             return Err(InterpreterError { 
                 error_message: format!("We dont have that expression type yet bud"), 
                 line: 0, 
                 column: 0,
-                value: Value::Nil 
+                value: Value::Nil,
+                notes: Vec::new(),
+                kind: ErrorKind::Runtime
             })    
         }
     }
 
-    pub fn execute(&mut self, stmt: Stmt) -> Result<(), InterpreterError>{
+    pub fn execute(&mut self, stmt: Stmt) -> Result<(), Unwind>{
+        self.step()?;
         if let Stmt::Expr { expression: _ } = stmt{
-            return Ok(self.visit_expression_stmt(stmt)?);
+            return self.visit_expression_stmt(stmt);
         }
         else if let Stmt::Print { expression: _ } = stmt{
-            return Ok(self.visit_print_stmt(stmt))?;
+            return self.visit_print_stmt(stmt);
         }
         else if let Stmt::Var { name: _, line: _ , column: _ , initializer: _ } = stmt{
-            return Ok(self.visit_var_stmt(stmt))?;
+            return self.visit_var_stmt(stmt);
         }
         else if let Stmt::Block { statements: _ } = stmt{
-            return Ok(self.visit_block_stmt(stmt))?;
+            return self.visit_block_stmt(stmt);
         }
         else if let Stmt::If { condition: _ , then_branch: _ , else_branch: _ } = stmt{
-            return Ok(self.visit_if_stmt(stmt))?;
+            return self.visit_if_stmt(stmt);
+        }
+        else if let Stmt::While { condition: _ , body: _ , increment: _ } = stmt{
+            return self.visit_while_stmt(stmt);
+        }
+        else if let Stmt::Break { keyword: _ } = stmt{
+            return self.visit_break_stmt(stmt);
         }
-        else if let Stmt::While { condition: _ , body: _ } = stmt{
-            return Ok(self.visit_while_stmt(stmt))?;
+        else if let Stmt::Continue { keyword: _ } = stmt{
+            return self.visit_continue_stmt(stmt);
         }
-        else if let Stmt::Function { name: _ , parameters: _ , body: _ } = stmt{
-            return Ok(self.visit_function_stmt(stmt))?;
+        else if let Stmt::Function { name: _ , line: _ , column: _ , parameters: _ , body: _ } = stmt{
+            return self.visit_function_stmt(stmt);
         }
         else if let Stmt::Return { keyword: _ , value: _ } = stmt{
-            return Ok(self.visit_return_stmt(stmt))?;
+            return self.visit_return_stmt(stmt);
         }
         else if let Stmt::Class { name: _ , superclass: _ , methods: _ } = stmt{
-            return Ok(self.visit_class_stmt(stmt))?;
+            return self.visit_class_stmt(stmt);
+        }
+        else if let Stmt::Throw { keyword: _ , value: _ } = stmt{
+            return self.visit_throw_stmt(stmt);
+        }
+        else if let Stmt::Try { keyword: _ , body: _ , catch_name: _ , catch_body: _ , finally_body: _ } = stmt{
+            return self.visit_try_stmt(stmt);
         }
         else{
             return Err(InterpreterError { 
                 error_message: format!("We dont have that statement type yet bud"), 
                 line: 0, 
                 column: 0,
-                value: Value::Nil 
-            })
+                value: Value::Nil,
+                notes: Vec::new(),
+                kind: ErrorKind::Runtime
+            }.into())
         }
     }
 
-    pub fn execute_block(&mut self, statements: Vec<Stmt>, env: Option<Environment>) -> Result<(), InterpreterError>{
-        match env{
-            Some(enviro) => self.environment = enviro,
-            None => self.environment = Environment::new(self.environment.clone()),
+    //Counts one statement or expression against the config's limits
+    fn step(&mut self) -> Result<(), InterpreterError>{
+        self.steps += 1;
+        return self.config.check_step(self.steps).map_err(|(kind, message)| InterpreterError::aborted(kind, message));
+    }
+
+    pub fn heap_bytes(&self) -> usize{
+        return self.heap.live();
+    }
+
+    //Checks an allocation of bytes fits under the config's heap cap before it is made
+    fn reserve(&self, bytes: usize, line: usize, col: i64) -> Result<(), InterpreterError>{
+        if let Some(max_heap_bytes) = self.config.max_heap_bytes{
+            if self.heap.live() + bytes > max_heap_bytes{
+                let mut err = InterpreterError::new(format!("Out of memory, the heap limit of {} bytes was exceeded at line: {}, column: {}", max_heap_bytes, line, col), line, col, Value::Nil);
+                err.kind = ErrorKind::OutOfMemory;
+                return Err(err);
+            }
         }
+        return Ok(());
+    }
+
+    //Reserves bytes and charges them to the allocation about to be made
+    fn charge(&self, bytes: usize, line: usize, col: i64) -> Result<HeapCharge, InterpreterError>{
+        self.reserve(bytes, line, col)?;
+        return Ok(self.heap.charge(bytes));
+    }
+
+    pub fn execute_block(&mut self, statements: Vec<Stmt>, env: Option<Environment>) -> Result<(), Unwind>{
+        let block_env = match env{
+            Some(enviro) => enviro,
+            None => Environment::new(self.environment.clone()),
+        };
+        let previous = mem::replace(&mut self.environment, Rc::new(RefCell::new(block_env)));
         for stmt in statements{
-            let execute: Result<(), InterpreterError> = self.execute(stmt);
+            let execute: Result<(), Unwind> = self.execute(stmt);
             match execute{
                 Ok(void) => (),
-                Err(err) => return Err(err)
+                Err(err) => {
+                    //Leave the block's scope even when unwinding out of it
+                    self.environment = previous;
+                    return Err(err)
+                }
             }
         }
-        if let Some(enclosing) = self.environment.enclosing.clone(){
-            self.environment = *enclosing;
-        }
-        else{
-            panic!("Unreachable hell");
-        }
+        self.environment = previous;
         return Ok(());
     }
 
-    fn visit_block_stmt(&mut self, stmt: Stmt) -> Result<(), InterpreterError>{
+    fn visit_block_stmt(&mut self, stmt: Stmt) -> Result<(), Unwind>{
         if let Stmt::Block { statements } = stmt{
             let execute = self.execute_block(statements, None);
             match execute{
@@ -779,7 +1587,7 @@ impl Interpreter{
         }
     }
 
-    fn visit_class_stmt(&mut self, stmt: Stmt) -> Result<(), InterpreterError>{
+    fn visit_class_stmt(&mut self, stmt: Stmt) -> Result<(), Unwind>{
         if let Stmt::Class { name, superclass, methods } = stmt{
             let mut class_super: Value;
             let mut insert_class_super: Option<LoxClass>;
@@ -787,35 +1595,43 @@ impl Interpreter{
             match superclass{
                 Some(sup) =>{
                     is_super = true;
+                    //The parser only ever produces a Variable here
+                    let (line, col) = match &sup{
+                        Expr::Variable { name: _ , line, col, id: _ } => (*line, *col),
+                        _ => (0, 0)
+                    };
                     class_super = self.evaluate(sup)?;
                     if let Value::LoxClass(klas) = class_super{
                         insert_class_super = Some(klas);
                     }
                     else{
                         return Err(InterpreterError { 
-                            error_message: format!("Superclass must be a class"), 
-                            line: 0, 
-                            column: 0, 
-                            value: Value::Nil  
-                        })
+                            error_message: format!("Superclass must be a class at line: {}, column: {}", line, col), 
+                            line: line, 
+                            column: col, 
+                            value: Value::Nil,
+                            notes: Vec::new(),
+                            kind: ErrorKind::Runtime
+                        }.into())
                     }
                 }
                 None => insert_class_super = None
             }
+            let class_slot = self.environment.borrow().slots.len();
+            self.define_variable(name.clone(), 0, 0, None);
             if is_super{
-                self.environment = Environment::new(self.environment.clone());
-                self.environment.define(format!("super"), 0, 0, Some(Value::LoxClass(insert_class_super.clone().unwrap())));
+                self.environment = Rc::new(RefCell::new(Environment::new(self.environment.clone())));
+                self.environment.borrow_mut().define_slot(0, 0, Some(Value::LoxClass(insert_class_super.clone().unwrap())));
             }
-            self.environment.define(name.clone(), 0, 0, None);
             let mut method_hash: HashMap<String, UserDefined> = HashMap::new();
             let method_vec = *methods;
             for method in method_vec{
-                if let Stmt::Function { name, parameters, body } = method.clone(){
+                if let Stmt::Function { name, line: _ , column: _ , parameters, body } = method.clone(){
                     let insert_method = UserDefined {
                         name: name.clone(),
                         parameters: parameters,
-                        body: *body,
-                        declaration: method.clone(),
+                        body: Rc::new(*body),
+                        declaration: Rc::new(method.clone()),
                         closure: self.environment.clone(),
                         is_init: name.clone().eq(&format!("init"))
                     };
@@ -823,12 +1639,16 @@ impl Interpreter{
                 }
             }
             if is_super{
-                //self.environment = *self.environment.enclosing.clone().unwrap();
+                let enclosing = self.environment.borrow().enclosing.clone().unwrap();
+                self.environment = enclosing;
             } 
             let klass: LoxClass = LoxClass { name: name.clone(),superclass: Box::new(insert_class_super), methods: method_hash };
-            self.environment.assign(name.clone(), 0, 0, &Value::LoxClass(klass.clone()))?;
-            self.environment.classes.insert(name.clone(), klass.clone());
-            self.globals.classes.insert(name.clone(), klass.clone());
+            if Rc::ptr_eq(&self.environment, &self.globals){
+                self.globals.borrow_mut().define(name, 0, 0, Some(Value::LoxClass(klass)));
+            }
+            else{
+                self.environment.borrow_mut().assign_slot_at(0, class_slot, &name, 0, 0, &Value::LoxClass(klass))?;
+            }
             return Ok(());
         }
         else{
@@ -836,24 +1656,69 @@ impl Interpreter{
         }
     }
 
-    pub fn resolve_local(&mut self, name: String, depth: usize, expr: Expr) -> (){
-        self.locals.insert(name, (expr, depth));
+    pub fn resolve_local(&mut self, expr: &Expr, depth: usize, slot: usize) -> (){
+        if let Some(id) = expr.resolution_id(){
+            self.locals.insert(id, (depth, slot));
+        }
     }
 
-    fn lookup_variable(&mut self, name: String, expr: Expr) -> Result<Value, InterpreterError>{
-        if let Some(result) = self.locals.get(&name){
-            return Ok(self.environment.get_at(result.1, result.0.clone()))?;
+    fn lookup_variable(&mut self, expr: &Expr) -> Result<Value, InterpreterError>{
+        let resolved = expr.resolution_id().and_then(|id| self.locals.get(&id).copied());
+        match (resolved, expr){
+            (Some((distance, slot)), Expr::Variable { name, line, col, id: _ }) => {
+                return self.environment.borrow().get_slot_at(distance, slot, name, *line, *col);
+            }
+            (Some((distance, slot)), Expr::This { keyword, line, col, id: _ }) => {
+                return self.environment.borrow().get_slot_at(distance, slot, keyword, *line, *col);
+            }
+            _ => return self.globals.borrow().get(expr)
+        }
+    }
+
+    //Top level declarations are globals, anything nested was given a slot by the resolver
+    fn define_variable(&mut self, name: String, line: usize, col: i64, val: Option<Value>) -> (){
+        if Rc::ptr_eq(&self.environment, &self.globals){
+            self.globals.borrow_mut().define(name, line, col, val);
         }
         else{
-            return Ok(self.globals.get(&expr))?;
+            self.environment.borrow_mut().define_slot(line, col, val);
         }
     }
 
+
     pub fn create_instance(&mut self, class: LoxClass) -> Value{
-        let inst = LoxInstance::new(class);
-        self.environment.instances.insert(format!("Test"), inst.clone());
-        //POSSIBLE ISSUE
-        return Value::LoxInstance(Rc::new(inst.clone()));
+        let mut inst = LoxInstance::new(class);
+        inst.charge = Some(self.heap.charge(INSTANCE_BYTES));
+        return Value::LoxInstance(Rc::new(inst));
+    }
+}
+
+//Reasons a statement can stop before finishing. Only Error is a failure,
+//the rest carry control flow out to the loop or call that handles them.
+#[derive(Debug)]
+pub enum Unwind{
+    Return(Value),
+    Break,
+    Continue,
+    Error(InterpreterError)
+}
+
+impl Unwind{
+    //Control flow that escaped everything that could have handled it.
+    //The resolver rejects these programs, so this only guards hand built ASTs.
+    pub fn stray_error(self) -> InterpreterError{
+        match self{
+            Unwind::Return(_) => InterpreterError::new(format!("Can't return from top-level code"), 0, 0, Value::Nil),
+            Unwind::Break => InterpreterError::new(format!("Can't use 'break' outside of a loop"), 0, 0, Value::Nil),
+            Unwind::Continue => InterpreterError::new(format!("Can't use 'continue' outside of a loop"), 0, 0, Value::Nil),
+            Unwind::Error(err) => err
+        }
+    }
+}
+
+impl From<InterpreterError> for Unwind{
+    fn from(err: InterpreterError) -> Self{
+        Unwind::Error(err)
     }
 }
 
@@ -862,7 +1727,28 @@ pub struct InterpreterError{
     error_message: String,
     line: usize,
     column: i64,
-    pub value: Value
+    pub value: Value,
+    notes: Vec<Note>,
+    kind: ErrorKind
+}
+
+//What raised an error, which decides whether a try statement may handle it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorKind{
+    Runtime,
+    //From 'throw', the error's value holds what was thrown
+    Thrown,
+    //The config's limits abort the script, no catch or finally block runs
+    StepLimit,
+    Timeout,
+    Interrupted,
+    OutOfMemory
+}
+
+impl ErrorKind{
+    pub fn is_catchable(&self) -> bool{
+        return matches!(self, ErrorKind::Runtime | ErrorKind::Thrown);
+    }
 }
 
 impl InterpreterError{
@@ -871,13 +1757,43 @@ impl InterpreterError{
             error_message: error_message,
             line: line,
             column: column,
-            value: value
+            value: value,
+            notes: Vec::new(),
+            kind: ErrorKind::Runtime
         }
     }
 
+    pub fn thrown(error_message: String, line: usize, column: i64, value: Value) -> Self{
+        let mut err = InterpreterError::new(error_message, line, column, value);
+        err.kind = ErrorKind::Thrown;
+        return err;
+    }
+
+    //Raised between steps, so there is no source position to point at
+    pub fn aborted(kind: ErrorKind, error_message: String) -> Self{
+        let mut err = InterpreterError::new(error_message, 0, 0, Value::Nil);
+        err.kind = kind;
+        return err;
+    }
+
+    pub fn kind(&self) -> ErrorKind{
+        return self.kind;
+    }
+
+    pub fn with_note(mut self, note: Note) -> Self{
+        self.notes.push(note);
+        return self;
+    }
+
     pub fn return_error(&self) -> String{
         return self.error_message.clone();
     }
+
+    pub fn diagnostic(&self) -> Diagnostic{
+        let mut diagnostic = Diagnostic::new(DiagnosticKind::Runtime, self.error_message.clone(), self.line, self.column);
+        diagnostic.notes = self.notes.clone();
+        return diagnostic;
+    }
 }
 
 
@@ -886,6 +1802,31 @@ mod tests {
     use super::*;
     use crate::expr::{Expr, LiteralType};
     use crate::stmt::Stmt;
+    use crate::parser::parse_begin;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+    use std::time::Duration;
+
+    fn run_source(source: &str) -> Result<Interpreter, String> {
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source.to_string())
+        .map_err(|errs| errs.iter().map(|err| err.error.clone()).collect::<Vec<String>>().join("\n"))?;
+        let stmts = parse_begin(tokens).map_err(|err| err.return_error())?;
+        let mut resolver = Resolver::new(Interpreter::new(stmts.clone()));
+        let (resolved, interpreter) = resolver.resolve(stmts.clone());
+        let mut interpreter = interpreter.clone();
+        resolved.map_err(|errs| errs.join("\n"))?;
+        interpreter.interpret(stmts).map_err(|err| err.return_error())?;
+        Ok(interpreter)
+    }
+
+    fn global(interpreter: &Interpreter, name: &str) -> Value {
+        let expr = Expr::Variable { name: name.to_string(), line: 0, col: 0, id: 0 };
+        match interpreter.globals.borrow().get(&expr) {
+            Ok(val) => val,
+            Err(err) => panic!("Could not read '{}': {}", name, err.return_error()),
+        }
+    }
 
     #[test]
     fn simple_addition() {
@@ -1054,4 +1995,761 @@ mod tests {
             Err(err) => panic!("Error when interpreting: {}", err.return_error()),
         }
     }
+
+    #[test]
+    fn list_index() {
+        let list = Expr::List {
+            elements: Box::new(vec![
+                Expr::Literal { value: LiteralType::Number(1.0) },
+                Expr::Literal { value: LiteralType::Number(2.0) },
+            ]),
+            line: 1,
+            col: 1,
+        };
+        let expr = Expr::Index {
+            object: Box::new(list),
+            index: Box::new(Expr::Literal { value: LiteralType::Number(1.0) }),
+            line: 1,
+            col: 7,
+        };
+
+        let mut interpreter = Interpreter::new(Vec::new());
+        let result = interpreter.evaluate(expr);
+
+        match result {
+            Ok(val) => assert_eq!(val, Value::Number(2.0)),
+            Err(err) => panic!("Error when interpreting: {}", err.return_error()),
+        }
+    }
+
+    #[test]
+    fn list_index_set_is_shared() {
+        let mut interpreter = Interpreter::new(Vec::new());
+        let list = Rc::new(RefCell::new(vec![Value::Number(1.0), Value::Number(2.0)].into()));
+        interpreter.globals.borrow_mut().define(format!("xs"), 1, 5, Some(Value::List(list.clone())));
+
+        let expr = Expr::IndexSet {
+            object: Box::new(Expr::Variable { name: format!("xs"), line: 2, col: 1, id: 0 }),
+            index: Box::new(Expr::Literal { value: LiteralType::Number(0.0) }),
+            value: Box::new(Expr::Literal { value: LiteralType::String("one".to_string()) }),
+            line: 2,
+            col: 3,
+        };
+        let result = interpreter.evaluate(expr);
+
+        assert!(result.is_ok());
+        assert_eq!(list.borrow()[0], Value::String("one".into()));
+        assert_eq!(Value::value_to_string(Value::List(list)), "[one, 2]");
+    }
+
+    #[test]
+    fn list_index_out_of_bounds() {
+        let list = Expr::List {
+            elements: Box::new(vec![Expr::Literal { value: LiteralType::Number(1.0) }]),
+            line: 3,
+            col: 9,
+        };
+        let expr = Expr::Index {
+            object: Box::new(list),
+            index: Box::new(Expr::Literal { value: LiteralType::Number(3.0) }),
+            line: 3,
+            col: 12,
+        };
+
+        let mut interpreter = Interpreter::new(Vec::new());
+        let result = interpreter.evaluate(expr);
+
+        match result {
+            Ok(_) => panic!("Expected an error when indexing past the end of a list"),
+            Err(err) => {
+                assert_eq!(err.error_message, "Index 3 out of bounds for list of length 1 at line: 3, column: 12");
+                assert_eq!(err.line, 3);
+                assert_eq!(err.column, 12);
+            }
+        }
+    }
+
+    #[test]
+    fn list_index_must_be_whole_number() {
+        let list = Expr::List {
+            elements: Box::new(vec![Expr::Literal { value: LiteralType::Number(1.0) }]),
+            line: 1,
+            col: 1,
+        };
+        let expr = Expr::Index {
+            object: Box::new(list),
+            index: Box::new(Expr::Literal { value: LiteralType::Number(0.5) }),
+            line: 1,
+            col: 4,
+        };
+
+        let mut interpreter = Interpreter::new(Vec::new());
+        let result = interpreter.evaluate(expr);
+
+        match result {
+            Ok(_) => panic!("Expected an error when indexing with a fraction"),
+            Err(err) => assert_eq!(err.error_message, "List index must be a whole number, found 0.5 at line: 1, column: 4"),
+        }
+    }
+
+    #[test]
+    fn map_lookup_and_insert() {
+        let mut interpreter = Interpreter::new(Vec::new());
+        let map = Expr::Map {
+            entries: Box::new(vec![
+                (Expr::Literal { value: LiteralType::String("a".to_string()) }, Expr::Literal { value: LiteralType::Number(1.0) }),
+            ]),
+            line: 1,
+            col: 9,
+        };
+        let map_val = interpreter.evaluate(map).unwrap();
+        interpreter.globals.borrow_mut().define(format!("m"), 1, 5, Some(map_val.clone()));
+
+        let set = Expr::IndexSet {
+            object: Box::new(Expr::Variable { name: format!("m"), line: 2, col: 1, id: 0 }),
+            index: Box::new(Expr::Literal { value: LiteralType::Nil }),
+            value: Box::new(Expr::Literal { value: LiteralType::True }),
+            line: 2,
+            col: 2,
+        };
+        assert!(interpreter.evaluate(set).is_ok());
+
+        let get = Expr::Index {
+            object: Box::new(Expr::Variable { name: format!("m"), line: 3, col: 1, id: 0 }),
+            index: Box::new(Expr::Literal { value: LiteralType::String("a".to_string()) }),
+            line: 3,
+            col: 2,
+        };
+        assert_eq!(interpreter.evaluate(get).unwrap(), Value::Number(1.0));
+        assert_eq!(Value::value_to_string(map_val), "{a: 1, nil: true}");
+    }
+
+    #[test]
+    fn map_missing_key() {
+        let map = Expr::Map {
+            entries: Box::new(Vec::new()),
+            line: 4,
+            col: 1,
+        };
+        let get = Expr::Index {
+            object: Box::new(map),
+            index: Box::new(Expr::Literal { value: LiteralType::String("b".to_string()) }),
+            line: 4,
+            col: 3,
+        };
+
+        let mut interpreter = Interpreter::new(Vec::new());
+        match interpreter.evaluate(get) {
+            Ok(_) => panic!("Expected an error when reading a missing key"),
+            Err(err) => assert_eq!(err.error_message, "Undefined key 'b' at line: 4, column: 3"),
+        }
+    }
+
+    #[test]
+    fn map_unhashable_key() {
+        let map = Expr::Map {
+            entries: Box::new(vec![
+                (Expr::List { elements: Box::new(Vec::new()), line: 1, col: 2 }, Expr::Literal { value: LiteralType::Nil }),
+            ]),
+            line: 1,
+            col: 1,
+        };
+
+        let mut interpreter = Interpreter::new(Vec::new());
+        match interpreter.evaluate(map) {
+            Ok(_) => panic!("Expected an error when using a list as a key"),
+            Err(err) => assert_eq!(err.error_message, "Cannot use a value of type List as a map key at line: 1, column: 1"),
+        }
+    }
+
+    #[test]
+    fn simple_modulo() {
+        let expr = Expr::Binary {
+            left: Box::new(Expr::Literal { value: LiteralType::Number(7.0) }),
+            operator: BinaryOpType::Mod,
+            right: Box::new(Expr::Literal { value: LiteralType::Number(3.0) }),
+            line: 1,
+            col: 1,
+        };
+
+        let mut interpreter = Interpreter::new(Vec::new());
+        let result = interpreter.evaluate(expr);
+
+        match result {
+            Ok(val) => assert_eq!(val, Value::Number(1.0)),
+            Err(err) => panic!("Error when interpreting: {}", err.return_error()),
+        }
+    }
+
+    #[test]
+    fn modulo_negative_operands() {
+        let cases = vec![(-7.0, 3.0, -1.0), (7.0, -3.0, 1.0), (-7.0, -3.0, -1.0), (5.5, 2.0, 1.5)];
+        for (left, right, expected) in cases{
+            let expr = Expr::Binary {
+                left: Box::new(Expr::Literal { value: LiteralType::Number(left) }),
+                operator: BinaryOpType::Mod,
+                right: Box::new(Expr::Literal { value: LiteralType::Number(right) }),
+                line: 1,
+                col: 1,
+            };
+
+            let mut interpreter = Interpreter::new(Vec::new());
+            let result = interpreter.evaluate(expr);
+
+            match result {
+                Ok(val) => assert_eq!(val, Value::Number(expected), "{} % {}", left, right),
+                Err(err) => panic!("Error when interpreting: {}", err.return_error()),
+            }
+        }
+    }
+
+    #[test]
+    fn modulo_by_zero() {
+        let expr = Expr::Binary {
+            left: Box::new(Expr::Literal { value: LiteralType::Number(10.0) }),
+            operator: BinaryOpType::Mod,
+            right: Box::new(Expr::Literal { value: LiteralType::Number(0.0) }),
+            line: 2,
+            col: 4,
+        };
+        let stmt = Stmt::Expr { expression: Box::new(expr) };
+
+        let mut interpreter = Interpreter::new(Vec::new());
+        let result = interpreter.interpret(vec![stmt]);
+
+        match result {
+            Ok(_) => panic!("Expected an error during modulo by zero"),
+            Err(err) => assert_eq!(err.error_message, "Modulo by zero error at line: 2, column: 4"),
+        }
+    }
+
+    #[test]
+    fn lambda_call() {
+        let interpreter = run_source("var add = lambda (a, b) { return a + b; };\nvar sum = add(2, 3);").unwrap();
+
+        assert_eq!(global(&interpreter, "sum"), Value::Int(5));
+        assert_eq!(Value::value_to_string(global(&interpreter, "add")), "lambda");
+    }
+
+    #[test]
+    fn lambda_short_body_captures_enclosing_scope() {
+        let source = "fun makeAdder(n) { return lambda (x) x + n; }\nvar addTen = makeAdder(10);\nvar result = addTen(5);";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "result"), Value::Int(15));
+    }
+
+    #[test]
+    fn lambda_as_argument() {
+        let source = "fun apply(f, v) { return f(v); }\nvar result = apply(lambda (x) x * x, 4);";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "result"), Value::Int(16));
+    }
+
+    #[test]
+    fn while_break() {
+        let source = "var i = 0;\nwhile (true) { i = i + 1; if (i == 5) break; }";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "i"), Value::Int(5));
+    }
+
+    #[test]
+    fn for_continue_runs_increment() {
+        let source = "var sum = 0;\nvar steps = 0;\nfor (var i = 0; i < 6; i = i + 1) { steps = steps + 1; if (i % 2 == 0) continue; sum = sum + i; }";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "sum"), Value::Int(9));
+        assert_eq!(global(&interpreter, "steps"), Value::Int(6));
+    }
+
+    #[test]
+    fn break_only_exits_innermost_loop() {
+        let source = "var count = 0;\nfor (var i = 0; i < 3; i = i + 1) { for (var j = 0; j < 10; j = j + 1) { if (j == 2) break; count = count + 1; } }";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "count"), Value::Int(6));
+    }
+
+    #[test]
+    fn break_outside_loop_is_rejected() {
+        let result = run_source("break;");
+
+        match result {
+            Ok(_) => panic!("Expected the resolver to reject 'break' outside of a loop"),
+            Err(err) => assert_eq!(err, "Can't use 'break' outside of a loop"),
+        }
+    }
+
+    #[test]
+    fn return_value_from_function() {
+        let source = "fun pick(a) { if (a) { while (true) { return \"yes\"; } } return \"no\"; }\nvar first = pick(true);\nvar second = pick(false);";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "first"), Value::String("yes".into()));
+        assert_eq!(global(&interpreter, "second"), Value::String("no".into()));
+    }
+
+    #[test]
+    fn runtime_error_propagates_out_of_call() {
+        let source = "fun divide() {\n  var x = 1 / 0;\n  return x;\n}\nvar result = divide();";
+        let result = run_source(source);
+
+        match result {
+            Ok(_) => panic!("Expected the error inside the function to reach the caller"),
+            Err(err) => assert_eq!(err, "Divide by zero error at line: 2, column: 13"),
+        }
+    }
+
+    #[test]
+    fn runtime_error_in_return_value_propagates() {
+        let source = "fun broken() { return 1 + nil; }\nbroken();";
+        let result = run_source(source);
+
+        assert!(result.is_err(), "Expected the error in the return value to reach the caller");
+    }
+
+    #[test]
+    fn closure_ignores_later_shadowing() {
+        let source = "var a = \"global\";\nvar first;\nvar second;\n{\n  fun showA() { return a; }\n  first = showA();\n  var a = \"block\";\n  second = showA();\n}";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "first"), Value::String("global".into()));
+        assert_eq!(global(&interpreter, "second"), Value::String("global".into()));
+    }
+
+    #[test]
+    fn same_name_in_nested_blocks() {
+        let source = "var result;\n{\n  var x = \"outer\";\n  {\n    var x = \"inner\";\n    x = \"changed\";\n  }\n  result = x;\n}";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "result"), Value::String("outer".into()));
+    }
+
+    #[test]
+    fn local_function_recursion() {
+        let source = "var result;\n{\n  fun fact(n) { if (n <= 1) return 1; return n * fact(n - 1); }\n  result = fact(5);\n}";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "result"), Value::Int(120));
+    }
+
+    #[test]
+    fn super_binds_to_enclosing_class() {
+        let source = "class A { m() { return \"A\"; } }\nclass B < A { m() { return \"B\"; } test() { return super.m(); } }\nclass C < B { m() { return \"C\"; } }\nvar result = C().test();";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "result"), Value::String("A".into()));
+    }
+
+    #[test]
+    fn counter_closure_keeps_state() {
+        let source = "fun makeCounter() {\n  var i = 0;\n  fun count() { i = i + 1; return i; }\n  return count;\n}\nvar counter = makeCounter();\ncounter();\ncounter();\nvar third = counter();";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "third"), Value::Int(3));
+    }
+
+    #[test]
+    fn counters_do_not_share_state() {
+        let source = "fun makeCounter() {\n  var i = 0;\n  return lambda() { i = i + 1; return i; };\n}\nvar first = makeCounter();\nvar second = makeCounter();\nfirst();\nfirst();\nvar a = first();\nvar b = second();";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "a"), Value::Int(3));
+        assert_eq!(global(&interpreter, "b"), Value::Int(1));
+    }
+
+    #[test]
+    fn closures_share_captured_variable() {
+        let source = "var get;\nvar set;\n{\n  var value = \"before\";\n  fun getter() { return value; }\n  fun setter(v) { value = v; }\n  get = getter;\n  set = setter;\n}\nset(\"after\");\nvar result = get();";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "result"), Value::String("after".into()));
+    }
+
+    #[test]
+    fn closure_sees_assignment_after_capture() {
+        let source = "var result;\n{\n  var x = 1;\n  fun read() { return x; }\n  x = 2;\n  result = read();\n}";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "result"), Value::Int(2));
+    }
+
+    #[test]
+    fn nested_closures_reach_every_level() {
+        let source = "fun outer() {\n  var a = 1;\n  fun middle() {\n    var b = 10;\n    fun inner() { a = a + 1; b = b + 1; return a + b; }\n    return inner;\n  }\n  return middle();\n}\nvar inner = outer();\ninner();\nvar result = inner();";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "result"), Value::Int(15));
+    }
+
+    #[test]
+    fn closure_shadowing_parameter() {
+        let source = "var x = \"global\";\nfun f(x) {\n  fun g() { return x; }\n  return g;\n}\nvar result = f(\"param\")();";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "result"), Value::String("param".into()));
+    }
+
+    #[test]
+    fn local_class_and_redeclared_local() {
+        let source = "var result;\n{\n  var x = 1;\n  class Box { get() { return \"box\"; } }\n  var x = 2;\n  result = Box().get() + \" \";\n  result = result + \"done\";\n  x = x + 1;\n  if (x != 3) result = \"wrong slot\";\n}";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "result"), Value::String("box done".into()));
+    }
+
+    fn runtime_error(source: &str) -> String {
+        match run_source(source) {
+            Ok(_) => panic!("Expected '{}' to fail", source),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn call_errors_report_paren_position() {
+        assert_eq!(runtime_error("fun f(a) {}\nf(1, 2);"), "Expected 1 arguments but got 2 at line: 2, column: 7");
+        assert_eq!(runtime_error("var x = 1;\n  x();"), "Can only call functions and classes at line: 2, column: 5");
+        assert_eq!(runtime_error("len(1);"), "len() expects a list, map or string but got Int at line: 1, column: 6");
+        assert_eq!(runtime_error("class A {}\nA(1);"), "Expected 0 arguments but got 1 at line: 2, column: 4");
+    }
+
+    #[test]
+    fn property_errors_report_name_position() {
+        assert_eq!(runtime_error("var x = 1;\nprint x.y;"), "Only objects have properties at line: 2, column: 9");
+        assert_eq!(runtime_error("var x = 1;\nx.y = 2;"), "Only objects have properties at line: 2, column: 3");
+        assert_eq!(runtime_error("class A {}\nprint A().missing;"), "Undefined property 'missing' at line: 2, column: 11");
+    }
+
+    #[test]
+    fn class_errors_report_position() {
+        assert_eq!(runtime_error("var B = 1;\nclass A < B {}"), "Superclass must be a class at line: 2, column: 11");
+        let source = "class A {}\nclass B < A {\n  m() { return super.nope; }\n}\nB().m();";
+        assert_eq!(runtime_error(source), "Undefined property 'nope' at line: 3, column: 22");
+        assert_eq!(runtime_error("fun clock() {}"), "Function already defined at line: 1, column: 5");
+    }
+
+    #[test]
+    fn unset_local_reports_use_site() {
+        let source = "{\n  var x;\n  print x;\n}";
+        let result = run_source(source);
+
+        match result {
+            Ok(_) => panic!("Expected reading an unset local to fail"),
+            Err(err) => assert_eq!(err, "use of undefined variable 'x' at line: 3, column: 9"),
+        }
+    }
+
+    #[test]
+    fn string_interpolation() {
+        let source = "
+            class Animal {
+              init(sound) { this.sound = sound; }
+              toString() { return \"Animal(${this.sound})\"; }
+            }
+            var n = 3;
+            var m = {\"k\": [1, 2]};
+            var a = \"n is ${n}, next ${n + 1}\";
+            var b = \"map ${m[\"k\"]} and ${ {\"x\": 1}[\"x\"] }\";
+            var c = \"${Animal(\"moo\")} says \\${n}\";
+            var d = \"outer ${\"inner ${n * 2}\"}\";";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "a"), Value::String("n is 3, next 4".into()));
+        assert_eq!(global(&interpreter, "b"), Value::String("map [1, 2] and 1".into()));
+        assert_eq!(global(&interpreter, "c"), Value::String("Animal(moo) says ${n}".into()));
+        assert_eq!(global(&interpreter, "d"), Value::String("outer inner 6".into()));
+    }
+
+    #[test]
+    fn int_arithmetic() {
+        let source = "var a = 7 + 2 * 3;\nvar b = 7 / 2;\nvar c = 7 ~/ 2;\nvar d = -7 ~/ 2;\nvar e = 7.5 ~/ 2;\nvar f = 1 + 0.5;\nvar g = 1 == 1.0;\nvar h = 9007199254740993;\nvar i = -7 % 3;";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "a"), Value::Int(13));
+        assert_eq!(global(&interpreter, "b"), Value::Number(3.5));
+        assert_eq!(global(&interpreter, "c"), Value::Int(3));
+        assert_eq!(global(&interpreter, "d"), Value::Int(-3));
+        assert_eq!(global(&interpreter, "e"), Value::Int(3));
+        assert_eq!(global(&interpreter, "f"), Value::Number(1.5));
+        assert_eq!(global(&interpreter, "g"), Value::Bool(true));
+        assert_eq!(Value::value_to_string(global(&interpreter, "h")), "9007199254740993");
+        assert_eq!(global(&interpreter, "i"), Value::Int(-1));
+    }
+
+    #[test]
+    fn int_overflow_and_division_errors() {
+        assert_eq!(runtime_error("var x = 9223372036854775807;\nx + 1;"), "Integer overflow at line: 2, column: 3");
+        assert_eq!(runtime_error("var x = 3037000500;\nx * x;"), "Integer overflow at line: 2, column: 3");
+        assert_eq!(runtime_error("var x = -9223372036854775807 - 1;\n-x;"), "Integer overflow at line: 2, column: 1");
+        assert_eq!(runtime_error("var x = 1;\nx ~/ 0;"), "Divide by zero error at line: 2, column: 3");
+    }
+
+    #[test]
+    fn bitwise_and_power_operators() {
+        let source = "var a = 12 & 10;\nvar b = 12 | 3;\nvar c = 12 ^ 10;\nvar d = ~5;\nvar e = 1 << 62;\nvar f = -16 >> 2;\nvar g = 6.0 & 3;\nvar h = 2 ** 10;\nvar i = 2 ** -1;\nvar j = 2.0 ** 0.5;\nvar k = -2 ** 2;\nvar l = 2 ** 3 ** 2;\nvar m = 1 | 4 == 5;";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "a"), Value::Int(8));
+        assert_eq!(global(&interpreter, "b"), Value::Int(15));
+        assert_eq!(global(&interpreter, "c"), Value::Int(6));
+        assert_eq!(global(&interpreter, "d"), Value::Int(-6));
+        assert_eq!(global(&interpreter, "e"), Value::Int(1 << 62));
+        assert_eq!(global(&interpreter, "f"), Value::Int(-4));
+        assert_eq!(global(&interpreter, "g"), Value::Int(2));
+        assert_eq!(global(&interpreter, "h"), Value::Int(1024));
+        assert_eq!(global(&interpreter, "i"), Value::Number(0.5));
+        assert_eq!(global(&interpreter, "j"), Value::Number(2.0f64.sqrt()));
+        assert_eq!(global(&interpreter, "k"), Value::Int(-4));
+        assert_eq!(global(&interpreter, "l"), Value::Int(512));
+        assert_eq!(global(&interpreter, "m"), Value::Bool(true));
+    }
+
+    #[test]
+    fn bitwise_operand_errors() {
+        assert_eq!(runtime_error("var x = 2.5;\nx & 1;"), "Bitwise operators need integers but got 2.5 at line: 2, column: 3");
+        assert_eq!(runtime_error("var x = \"a\";\n~x;"), "Bitwise operators need integers but got String at line: 2, column: 1");
+        assert_eq!(runtime_error("var x = 1;\nx << 64;"), "Shift amount must be between 0 and 63 but got 64 at line: 2, column: 3");
+        assert_eq!(runtime_error("var x = 10;\nx ** 19;"), "Integer overflow at line: 2, column: 3");
+    }
+
+    #[test]
+    fn catch_thrown_values() {
+        let source = "
+            fun fail(v) { throw v; }
+            var a; var b; var c;
+            try { fail(\"boom\"); } catch (e) { a = e; }
+            try { try { fail(1); } catch (e) { throw e + 1; } } catch (e) { b = e; }
+            class Oops { init(code) { this.code = code; } }
+            try { throw Oops(7); a = \"skipped\"; } catch (e) { c = e.code; }";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "a"), Value::String("boom".into()));
+        assert_eq!(global(&interpreter, "b"), Value::Int(2));
+        assert_eq!(global(&interpreter, "c"), Value::Int(7));
+    }
+
+    #[test]
+    fn catch_runtime_errors_as_objects() {
+        let source = "var m1; var m2; var m3; var where;
+try { print missing; } catch (e) { m1 = e.message; }
+try { var x = 1 + nil; } catch (e) { m2 = e.message; where = [e.line, e.column]; }
+class A {}
+try { A().nope; } catch (e) { m3 = e.message; }";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "m1"), Value::String("use of undefined and undeclared variable 'missing'".into()));
+        assert_eq!(global(&interpreter, "m2"), Value::String("Incorrect use of unary operator Plus on objects of type \"Int\" and \"Nil\"".into()));
+        assert_eq!(Value::value_to_string(global(&interpreter, "where")), "[3, 17]");
+        assert_eq!(global(&interpreter, "m3"), Value::String("Undefined property 'nope'".into()));
+    }
+
+    #[test]
+    fn finally_always_runs() {
+        let source = "
+            var log = \"\";
+            fun early() { try { return 1; } finally { log = log + \"r\"; } }
+            var r = early();
+            try { log = log + \"t\"; } finally { log = log + \"f\"; }
+            try { try { throw 0; } finally { log = log + \"x\"; } } catch (e) { log = log + \"c\"; }
+            for (var i = 0; i < 3; i = i + 1) { try { if (i == 1) break; } finally { log = log + \"l\"; } }";
+        let interpreter = run_source(source).unwrap();
+
+        assert_eq!(global(&interpreter, "r"), Value::Int(1));
+        assert_eq!(global(&interpreter, "log"), Value::String("rtfxcll".into()));
+    }
+
+    #[test]
+    fn uncaught_throw_reports_value() {
+        assert_eq!(runtime_error("throw \"boom\";"), "Uncaught exception: boom at line: 1, column: 1");
+        assert_eq!(runtime_error("try { nil.x; } catch (e) {\n  throw e;\n}"), "Uncaught exception: Only objects have properties at line: 2, column: 3");
+        assert_eq!(runtime_error("try { throw 1; } finally { }"), "Uncaught exception: 1 at line: 1, column: 7");
+    }
+
+    fn traceback(source: &str) -> Vec<String> {
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source.to_string()).unwrap();
+        let stmts = parse_begin(tokens).unwrap();
+        let mut resolver = Resolver::new(Interpreter::new(stmts.clone()));
+        let (resolved, interpreter) = resolver.resolve(stmts.clone());
+        let mut interpreter = interpreter.clone();
+        resolved.unwrap();
+        let err = interpreter.interpret(stmts).err().unwrap();
+        assert!(interpreter.call_stack.is_empty(), "Expected reporting the error to clear the call stack");
+        return err.diagnostic().notes.into_iter().map(|note| note.message).collect();
+    }
+
+    #[test]
+    fn uncaught_errors_carry_a_traceback() {
+        let source = "fun fib(n) {\n  if (n < 2) return n;\n  return fib(n - 1) + nil;\n}\nfib(1);\nfib(2);";
+        assert_eq!(traceback(source), vec!["in fib (line 3) called from <script> (line 6)".to_string()]);
+
+        let source = "class Shape {\n  init(sides) { this.sides = sides; }\n  area() { return this.missing; }\n}\nfun measure(s) {\n  return s.area();\n}\nmeasure(Shape(3));";
+        assert_eq!(traceback(source), vec!["in area (line 3) called from measure (line 6) called from <script> (line 8)".to_string()]);
+
+        let source = "class Point {\n  init(x) { this.x = x + \"\"; }\n}\nvar p = Point(1);";
+        assert_eq!(traceback(source), vec!["in Point (line 2) called from <script> (line 4)".to_string()]);
+
+        //Errors at the top level and errors caught on the way up leave no trace
+        assert!(traceback("print 1 + nil;").is_empty());
+        let source = "fun f() { throw \"x\"; }\ntry { f(); } catch (e) { }\nfun g() { return nil.y; }\ng();";
+        assert_eq!(traceback(source), vec!["in g (line 3) called from <script> (line 4)".to_string()]);
+
+        //So do errors a finally block swallows by returning or breaking out
+        let source = "fun boom() { return 1 / 0; }\nfun swallow() { try { boom(); } finally { return 0; } }\nswallow();\nwhile (true) { try { boom(); } finally { break; } }\nfun later() { return 1 / nil; }\nlater();";
+        assert_eq!(traceback(source), vec!["in later (line 5) called from <script> (line 6)".to_string()]);
+    }
+
+    fn run_with_config(source: &str, config: InterpreterConfig) -> (Interpreter, Result<(), InterpreterError>) {
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source.to_string()).unwrap();
+        let stmts = parse_begin(tokens).unwrap();
+        let mut resolver = Resolver::new(Interpreter::new(stmts.clone()));
+        let (resolved, interpreter) = resolver.resolve(stmts.clone());
+        let mut interpreter = interpreter.clone();
+        resolved.unwrap();
+        interpreter.config = config;
+        let result = interpreter.interpret(stmts);
+        return (interpreter, result);
+    }
+
+    #[test]
+    fn call_depth_limit_raises_stack_overflow() {
+        let config = InterpreterConfig { max_call_depth: 20, ..InterpreterConfig::default() };
+        let source = "fun down(n) {\n  if (n == 0) return 0;\n  return down(n - 1);\n}\nvar ok = down(19);\ndown(20);";
+        let (interpreter, result) = run_with_config(source, config.clone());
+        let err = result.err().unwrap();
+
+        assert_eq!(global(&interpreter, "ok"), Value::Int(0));
+        assert_eq!(err.return_error(), "Stack overflow at line: 3, column: 20");
+        assert_eq!(err.diagnostic().notes[0].message, "in down (line 3) called from down (line 3) called from down (line 3) called from down (line 3) called from down (line 3) ... 11 more calls ... called from down (line 3) called from down (line 3) called from down (line 3) called from down (line 3) called from <script> (line 6)");
+        assert!(interpreter.call_stack.is_empty());
+    }
+
+    #[test]
+    fn stack_overflow_is_catchable() {
+        let config = InterpreterConfig { max_call_depth: 20, ..InterpreterConfig::default() };
+        let source = "fun forever() { return forever(); }\nvar message;\ntry { forever(); } catch (e) { message = e.message; }\nfun one() { return 1; }\nvar after = one();";
+        let (interpreter, result) = run_with_config(source, config);
+
+        assert!(result.is_ok());
+        assert_eq!(global(&interpreter, "message"), Value::String("Stack overflow".into()));
+        assert_eq!(global(&interpreter, "after"), Value::Int(1));
+        assert!(interpreter.call_stack.is_empty());
+    }
+
+    #[test]
+    fn swallowed_errors_do_not_use_up_call_depth() {
+        let config = InterpreterConfig { max_call_depth: 20, ..InterpreterConfig::default() };
+        let source = "fun boom() { return 1 / 0; }\nfun swallow() { try { boom(); } finally { return 0; } }\nfor (var i = 0; i < 100; i = i + 1) swallow();\nfun b() { return 2; }\nfun a() { return b(); }\nvar after = a();";
+        let (interpreter, result) = run_with_config(source, config);
+
+        assert!(result.is_ok());
+        assert_eq!(global(&interpreter, "after"), Value::Int(2));
+        assert!(interpreter.call_stack.is_empty());
+    }
+
+    #[test]
+    fn step_limit_aborts_past_try() {
+        let config = InterpreterConfig { max_steps: Some(1000), ..InterpreterConfig::default() };
+        let source = "var caught = false;\nvar cleaned = false;\nfun spin() { while (true) {} }\ntry { spin(); } catch (e) { caught = true; } finally { cleaned = true; }";
+        let (interpreter, result) = run_with_config(source, config);
+        let err = result.err().unwrap();
+
+        assert_eq!(err.kind(), ErrorKind::StepLimit);
+        assert_eq!(err.return_error(), "Step limit of 1000 exceeded");
+        assert_eq!(err.diagnostic().notes[0].message, "in spin called from <script> (line 4)");
+        assert_eq!(global(&interpreter, "caught"), Value::Bool(false));
+        assert_eq!(global(&interpreter, "cleaned"), Value::Bool(false));
+
+        //A script that fits in the budget is unaffected
+        let config = InterpreterConfig { max_steps: Some(1000), ..InterpreterConfig::default() };
+        let (_, result) = run_with_config("var i = 0;\nwhile (i < 10) i = i + 1;", config);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn deadline_times_out() {
+        let config = InterpreterConfig { deadline: Some(Instant::now() + Duration::from_millis(20)), ..InterpreterConfig::default() };
+        let (_, result) = run_with_config("while (true) {}", config);
+        let err = result.err().unwrap();
+
+        assert_eq!(err.kind(), ErrorKind::Timeout);
+        assert_eq!(err.return_error(), "Execution timed out");
+    }
+
+    #[test]
+    fn host_thread_can_interrupt() {
+        let interrupt = InterruptFlag::default();
+        let config = InterpreterConfig { interrupt: Some(interrupt.clone()), ..InterpreterConfig::default() };
+        let host = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            interrupt.interrupt();
+        });
+        let (_, result) = run_with_config("var n = 0;\nwhile (true) { n = n + 1; }", config);
+        host.join().unwrap();
+        let err = result.err().unwrap();
+
+        assert_eq!(err.kind(), ErrorKind::Interrupted);
+        assert_eq!(err.return_error(), "Execution interrupted");
+    }
+
+    #[test]
+    fn heap_accounting_counts_live_values() {
+        let source = "var s = \"ab\" + \"cd\";\nvar l = [1, 2, 3];\nvar m = {\"k\": 1};\nm[\"k\"] = 2;\nm[\"j\"] = 3;\nm[\"i\"] = 4;\nremove(m, \"k\");\n{ var t = \"${s}${l}\"; var u = [t, t]; }";
+        let (interpreter, result) = run_with_config(source, InterpreterConfig::default());
+        assert!(result.is_ok());
+
+        //The block's string and list are gone, only what the globals hold is left
+        let string = STRING_BYTES + 4;
+        let list = LIST_BYTES + 3 * mem::size_of::<Value>();
+        let map = MAP_BYTES + 2 * MAP_ENTRY_BYTES;
+        assert_eq!(interpreter.heap_bytes(), string + list + map);
+
+        let source = "class P {}\nvar p = P();\nfor (var i = 0; i < 10; i = i + 1) { var q = P(); q.x = i; }\np.name = 1;\np.name = 2;";
+        let (interpreter, result) = run_with_config(source, InterpreterConfig::default());
+        assert!(result.is_ok());
+
+        //The loop's instances are gone, only p and its field are left
+        assert_eq!(interpreter.heap_bytes(), INSTANCE_BYTES + FIELD_BYTES + "name".len());
+    }
+
+    #[test]
+    fn temporary_allocations_stay_under_the_heap_cap() {
+        let config = InterpreterConfig { max_heap_bytes: Some(20_000), ..InterpreterConfig::default() };
+        let source = "class P { init(n) { this.n = n; } }\nvar total = 0;\nfor (var i = 0; i < 5000; i = i + 1) {\n  var p = P(i);\n  var s = \"item \" + \"${i}\";\n  var m = {\"p\": p, \"l\": [s, s]};\n  total = total + len(m[\"l\"]);\n}";
+        let (interpreter, result) = run_with_config(source, config);
+
+        assert!(result.is_ok(), "{:?}", result.err().map(|err| err.return_error()));
+        assert_eq!(global(&interpreter, "total"), Value::Int(10000));
+
+        //Repeated keys in a map literal only need room for the one entry they make
+        let config = InterpreterConfig { max_heap_bytes: Some(MAP_BYTES + MAP_ENTRY_BYTES), ..InterpreterConfig::default() };
+        let (_, result) = run_with_config("var m = {\"k\": 1, \"k\": 2, \"k\": 3};", config);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn heap_cap_raises_out_of_memory() {
+        let config = InterpreterConfig { max_heap_bytes: Some(100_000), ..InterpreterConfig::default() };
+        let source = "var caught = false;\nvar s = \"x\";\ntry {\n  while (true) s = s + s;\n} catch (e) { caught = true; }";
+        let (interpreter, result) = run_with_config(source, config);
+        let err = result.err().unwrap();
+
+        assert_eq!(err.kind(), ErrorKind::OutOfMemory);
+        assert_eq!(err.return_error(), "Out of memory, the heap limit of 100000 bytes was exceeded at line: 4, column: 22");
+        assert_eq!(global(&interpreter, "caught"), Value::Bool(false));
+        //s and the copies of it being added hold 5 x 16K, so making a 32K string would go over the cap
+        if let Value::String(s) = global(&interpreter, "s"){
+            assert_eq!(s.len(), 16384);
+        }
+        else{
+            panic!("Expected s to still be a string");
+        }
+
+        let config = InterpreterConfig { max_heap_bytes: Some(2_000), ..InterpreterConfig::default() };
+        let (_, result) = run_with_config("class Node { init(next) { this.next = next; } }\nvar head = 0;\nwhile (true) head = Node(head);", config);
+        assert_eq!(result.err().unwrap().kind(), ErrorKind::OutOfMemory);
+    }
 }
@@ -1,4 +1,6 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
 use std::rc::Rc;
 
 use crate::interpreter::*;
@@ -15,16 +17,38 @@ pub struct NativeFunction{
     pub callable: fn(&mut Interpreter, &[Value]) -> Result<Value, String>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Clone)]
 pub struct UserDefined{
     pub name: String,
     pub parameters: Vec<Token>,
-    pub body: Vec<Stmt>,
-    pub declaration: Stmt,
-    pub closure: Environment,
+    //Shared with every copy of the function value so reading a function is cheap
+    pub body: Rc<Vec<Stmt>>,
+    pub declaration: Rc<Stmt>,
+    pub closure: Rc<RefCell<Environment>>,
     pub is_init: bool
 }
 
+//The closure usually holds the function itself, so comparing or printing it
+//field by field would never terminate. Closures compare by identity instead.
+impl PartialEq for UserDefined{
+    fn eq(&self, other: &Self) -> bool{
+        return self.name == other.name
+            && self.declaration == other.declaration
+            && self.is_init == other.is_init
+            && Rc::ptr_eq(&self.closure, &other.closure);
+    }
+}
+
+impl fmt::Debug for UserDefined{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        f.debug_struct("UserDefined")
+            .field("name", &self.name)
+            .field("parameters", &self.parameters)
+            .field("is_init", &self.is_init)
+            .finish()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct LoxClass{
     pub name: String,
@@ -57,7 +81,7 @@ impl LoxClass{
                             let mut class_method = self.clone();
                             class_method.methods.insert(format!("init"), def);
                             let final_inst = LoxInstance::new(class_method);
-                            interpreter.environment.instances.insert(format!("Test"), final_inst.clone());
+                            interpreter.environment.borrow_mut().instances.insert(format!("Test"), final_inst.clone());
                             let final_inst = Value::LoxInstance(Rc::new(final_inst));
                             return Ok(final_inst);
                             //inst.klass.methods.insert(format!("init"), def);
@@ -89,23 +113,6 @@ impl LoxClass{
             // ))
         }
     }
-
-    pub fn find_superclass_method(&mut self, name: String) -> Result<UserDefined, ()>{
-        match *self.superclass.clone(){
-            Some(mut super_class) => return super_class.find_superclass_method(name),
-            None => ()
-            // None => return Err(InterpreterError::new(
-            //     format!("Can't find method {}", name), 
-            //     0, 
-            //     0, 
-            //     Value::Nil
-            // ))
-        }
-        match self.methods.get(&name){
-            Some(method) => return Ok(method.clone()),
-            None => return Err(())//return Err(())
-        }
-    }
 }
 
 impl NativeFunction{
@@ -119,33 +126,26 @@ impl NativeFunction{
 
 impl UserDefined{
     pub fn call(&self, interpreter: &mut Interpreter, args: &Vec<Value>) -> Result<Value, InterpreterError>{
-        if let Stmt::Function { name: _ , parameters , body } = &self.declaration{
+        if let Stmt::Function { name: _ , line: _ , column: _ , parameters , body: _ } = self.declaration.as_ref(){
             //println!("Inside func");
-            let mut environment: Environment = Environment::default();
-            //let mut environment: Environment = Environment::new(self.closure.clone());
-            for entries in self.closure.values.clone(){
-                environment.define(entries.0, 0, 0, entries.1.0);
-            }
+            let mut environment: Environment = Environment::new(self.closure.clone());
             let mut i = 0;
             while i < parameters.len() {
                 let argument = args.get(i).unwrap().clone();
-                environment.define_token(parameters.get(i).unwrap().clone(), argument);
+                let parameter = parameters.get(i).unwrap();
+                environment.define_slot(parameter.line, parameter.column, Some(argument));
                 i += 1;
             }
-            let mut block_env = Environment::full(environment.return_values(), interpreter.environment.clone());
-            //let mut block_env = Environment::full(environment.return_values(), self.closure.clone());
-            block_env.user_func = interpreter.environment.user_func.clone();
-            let current_interp = interpreter.environment.clone();
-            let res = interpreter.execute_block(*body.clone(), Some(block_env));
-            interpreter.environment = current_interp;
-            match res{
-                Ok(nothing) => {
-                    if self.is_init{
-                        return Ok(self.closure.get(&Expr::This { keyword: format!("this") }))?;
-                    }
-                    return Ok(Value::Nil)
+            let res = interpreter.execute_block(self.body.as_ref().clone(), Some(environment));
+            if self.is_init{
+                if let Ok(()) | Err(Unwind::Return(_)) = res{
+                    return Ok(self.closure.borrow().get_slot_at(0, 0, "this", 0, 0))?;
                 }
-                Err(err) => return Ok(err.value)
+            }
+            match res{
+                Ok(nothing) => return Ok(Value::Nil),
+                Err(Unwind::Return(value)) => return Ok(value),
+                Err(unwind) => return Err(unwind.stray_error())
             }
         }
         else{
@@ -157,7 +157,7 @@ impl UserDefined{
     }
 
     pub fn to_string(&self) -> String{
-        if let Stmt::Function { name, parameters: _ , body: _ } = &self.declaration{
+        if let Stmt::Function { name, line: _ , column: _ , parameters: _ , body: _ } = self.declaration.as_ref(){
             return format!("<fn {} >", name);
         }
         else{
@@ -167,21 +167,13 @@ impl UserDefined{
 
     pub fn bind(&mut self, instance: &Rc<LoxInstance>) -> UserDefined{
         let mut environment = Environment::new(self.closure.clone());
-        environment.define(format!("this"), 0, 0, Some(Value::LoxInstance(instance.clone())));
-        let inst = instance.clone();
-        let inst2 = inst.as_ref();
-        let class = inst2.klass.as_ref();
-        let super_c = *class.superclass.clone();
-        match super_c{
-            Some(sup) => environment.define(format!("super"), 0, 0, Some(Value::LoxClass(sup))),
-            None => ()
-        }
+        environment.define_slot(0, 0, Some(Value::LoxInstance(instance.clone())));
         return UserDefined {
             name: self.name.clone(),
             parameters: self.parameters.clone(),
             body: self.body.clone(),
             declaration: self.declaration.clone(),
-            closure: environment,
+            closure: Rc::new(RefCell::new(environment)),
             is_init: self.is_init
         }
     }
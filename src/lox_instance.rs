@@ -2,6 +2,7 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
+use crate::heap::HeapCharge;
 use crate::interpreter::*;
 use crate::lox_callable::*;
 //fixing commit messages
@@ -10,7 +11,9 @@ use crate::lox_callable::*;
 pub struct LoxInstance{
     //pub name: String,
     pub klass: Rc<LoxClass>,
-    pub fields: RefCell<HashMap<String, Value>>
+    pub fields: RefCell<HashMap<String, Value>>,
+    //Set for instances the script makes, grows as fields are added
+    pub charge: Option<HeapCharge>
 }
 
 // impl Default for LoxInstance{
@@ -25,11 +28,12 @@ impl LoxInstance{
     pub fn new(klass: LoxClass) -> Self{
         LoxInstance { 
             klass: Rc::new(klass),
-            fields: RefCell::new(HashMap::new()) 
+            fields: RefCell::new(HashMap::new()),
+            charge: None
         }
     }
 
-    pub fn get(self: &Rc<Self>, name: String) -> Result<Value, InterpreterError>{
+    pub fn get(self: &Rc<Self>, name: String, line: usize, col: i64) -> Result<Value, InterpreterError>{
         match self.fields.borrow_mut().get(&name){
             Some(val) => return Ok(val.clone()),
             None => {
@@ -37,9 +41,9 @@ impl LoxInstance{
                 match method{
                     Ok(mut ret_method) => return Ok(Value::UserDefined(ret_method.bind(self))),
                     Err(_err) => return Err(InterpreterError::new(
-                                    format!("Undefined property '{}'", name),
-                                    0,
-                                    0,
+                                    format!("Undefined property '{}' at line: {}, column: {}", name, line, col),
+                                    line,
+                                    col,
                                     Value::Nil))
                 }
             }
@@ -48,6 +52,11 @@ impl LoxInstance{
     }
 
     pub fn set(&self, name: String, value: Value) -> (){
+        if let Some(charge) = &self.charge{
+            if !self.fields.borrow().contains_key(&name){
+                charge.grow(FIELD_BYTES + name.len());
+            }
+        }
         self.fields.borrow_mut().insert(name, value);
         //println!("insert");
     }
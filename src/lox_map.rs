@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use crate::heap::HeapCharge;
+use crate::interpreter::*;
+
+//Wrapper that gives the hashable Value variants a hash and an equality.
+//Numbers and strings compare by content, instances compare by identity.
+//An Int and a float holding the same whole number are the same key.
+#[derive(Debug, Clone)]
+pub struct MapKey{
+    pub value: Value
+}
+
+impl MapKey{
+    pub fn new(value: Value) -> Result<MapKey, String>{
+        match value{
+            Value::Number(_) | Value::Int(_) | Value::String(_) | Value::Bool(_) | Value::Nil | Value::LoxInstance(_) => {
+                Ok(MapKey { value: value })
+            }
+            other => Err(format!("Cannot use a value of type {} as a map key",
+            Type::type_to_string(Value::value_type(other))))
+        }
+    }
+
+    fn number_bits(num: f64) -> u64{
+        //-0.0 and 0.0 have to land on the same key
+        if num == 0.0{
+            return 0.0_f64.to_bits();
+        }
+        return num.to_bits();
+    }
+}
+
+impl PartialEq for MapKey{
+    fn eq(&self, other: &Self) -> bool{
+        match (&self.value, &other.value){
+            (Value::Number(num1), Value::Number(num2)) => MapKey::number_bits(*num1) == MapKey::number_bits(*num2),
+            (Value::Int(num1), Value::Int(num2)) => num1 == num2,
+            (Value::Int(num1), Value::Number(num2)) | (Value::Number(num2), Value::Int(num1)) => Value::int_equals_number(*num1, *num2),
+            (Value::String(str1), Value::String(str2)) => str1 == str2,
+            (Value::Bool(bool1), Value::Bool(bool2)) => bool1 == bool2,
+            (Value::Nil, Value::Nil) => true,
+            (Value::LoxInstance(inst1), Value::LoxInstance(inst2)) => Rc::ptr_eq(inst1, inst2),
+            (_, _) => false
+        }
+    }
+}
+
+impl Eq for MapKey{}
+
+impl Hash for MapKey{
+    fn hash<H: Hasher>(&self, state: &mut H){
+        match &self.value{
+            Value::Int(num) => {
+                Type::Int.hash(state);
+                num.hash(state);
+            }
+            //Whole floats hash like the Int they equal
+            Value::Number(num) if Value::int_equals_number(*num as i64, *num) => {
+                Type::Int.hash(state);
+                (*num as i64).hash(state);
+            }
+            other => {
+                Value::value_type(other.clone()).hash(state);
+                match other{
+                    Value::Number(num) => MapKey::number_bits(*num).hash(state),
+                    Value::String(str) => str.hash(state),
+                    Value::Bool(bool) => bool.hash(state),
+                    Value::LoxInstance(inst) => (Rc::as_ptr(inst) as usize).hash(state),
+                    _ => ()
+                }
+            }
+        }
+    }
+}
+
+//Entries are kept in insertion order so printing and keys() are deterministic
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LoxMap{
+    entries: Vec<(Value, Value)>,
+    index: HashMap<MapKey, usize>,
+    //Grows and shrinks with the entries once the interpreter tracks the map
+    charge: Option<HeapCharge>
+}
+
+impl LoxMap{
+    pub fn new() -> Self{
+        LoxMap::default()
+    }
+
+    pub fn track(&mut self, charge: HeapCharge) -> (){
+        charge.grow(self.entries.len() * MAP_ENTRY_BYTES);
+        self.charge = Some(charge);
+    }
+
+    pub fn len(&self) -> usize{
+        return self.entries.len();
+    }
+
+    pub fn get(&self, key: Value) -> Result<Option<Value>, String>{
+        let map_key = MapKey::new(key)?;
+        match self.index.get(&map_key){
+            Some(position) => return Ok(Some(self.entries[*position].1.clone())),
+            None => return Ok(None)
+        }
+    }
+
+    pub fn contains(&self, key: Value) -> Result<bool, String>{
+        let map_key = MapKey::new(key)?;
+        return Ok(self.index.contains_key(&map_key));
+    }
+
+    pub fn insert(&mut self, key: Value, value: Value) -> Result<(), String>{
+        let map_key = MapKey::new(key.clone())?;
+        match self.index.get(&map_key){
+            Some(position) => self.entries[*position].1 = value,
+            None => {
+                self.index.insert(map_key, self.entries.len());
+                self.entries.push((key, value));
+                if let Some(charge) = &self.charge{
+                    charge.grow(MAP_ENTRY_BYTES);
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    pub fn remove(&mut self, key: Value) -> Result<Option<Value>, String>{
+        let map_key = MapKey::new(key)?;
+        match self.index.remove(&map_key){
+            Some(position) => {
+                let (_, removed) = self.entries.remove(position);
+                if let Some(charge) = &self.charge{
+                    charge.shrink(MAP_ENTRY_BYTES);
+                }
+                for later in self.index.values_mut(){
+                    if *later > position{
+                        *later -= 1;
+                    }
+                }
+                return Ok(Some(removed));
+            }
+            None => return Ok(None)
+        }
+    }
+
+    pub fn keys(&self) -> Vec<Value>{
+        return self.entries.iter().map(|entry| entry.0.clone()).collect();
+    }
+
+    pub fn values(&self) -> Vec<Value>{
+        return self.entries.iter().map(|entry| entry.1.clone()).collect();
+    }
+
+    pub fn entries(&self) -> &Vec<(Value, Value)>{
+        return &self.entries;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox_callable::LoxClass;
+    use crate::lox_instance::LoxInstance;
+
+    fn test_instance() -> Value{
+        let class = LoxClass {
+            name: "Point".to_string(),
+            superclass: Box::new(None),
+            methods: HashMap::new(),
+        };
+        return Value::LoxInstance(Rc::new(LoxInstance::new(class)));
+    }
+
+    #[test]
+    fn test_insert_and_get() {
+        let mut map = LoxMap::new();
+        map.insert(Value::String("a".into()), Value::Number(1.0)).unwrap();
+        map.insert(Value::Number(2.0), Value::Bool(true)).unwrap();
+        map.insert(Value::Nil, Value::String("nothing".into())).unwrap();
+
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get(Value::String("a".into())).unwrap(), Some(Value::Number(1.0)));
+        assert_eq!(map.get(Value::Number(2.0)).unwrap(), Some(Value::Bool(true)));
+        assert_eq!(map.get(Value::Nil).unwrap(), Some(Value::String("nothing".into())));
+        assert_eq!(map.get(Value::Bool(false)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_insert_overwrites_in_place() {
+        let mut map = LoxMap::new();
+        map.insert(Value::String("a".into()), Value::Number(1.0)).unwrap();
+        map.insert(Value::String("b".into()), Value::Number(2.0)).unwrap();
+        map.insert(Value::String("a".into()), Value::Number(3.0)).unwrap();
+
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.keys(), vec![Value::String("a".into()), Value::String("b".into())]);
+        assert_eq!(map.values(), vec![Value::Number(3.0), Value::Number(2.0)]);
+    }
+
+    #[test]
+    fn test_zero_keys_are_equal() {
+        let mut map = LoxMap::new();
+        map.insert(Value::Number(0.0), Value::Number(1.0)).unwrap();
+
+        assert_eq!(map.get(Value::Number(-0.0)).unwrap(), Some(Value::Number(1.0)));
+    }
+
+    #[test]
+    fn test_ints_and_whole_floats_share_keys() {
+        let mut map = LoxMap::new();
+        map.insert(Value::Int(2), Value::String("two".into())).unwrap();
+        map.insert(Value::Number(2.5), Value::String("half".into())).unwrap();
+
+        assert_eq!(map.get(Value::Number(2.0)).unwrap(), Some(Value::String("two".into())));
+        assert_eq!(map.get(Value::Int(3)).unwrap(), None);
+        map.insert(Value::Number(2.0), Value::Nil).unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.keys(), vec![Value::Int(2), Value::Number(2.5)]);
+    }
+
+    #[test]
+    fn test_instances_keyed_by_identity() {
+        let first = test_instance();
+        let second = test_instance();
+        let mut map = LoxMap::new();
+        map.insert(first.clone(), Value::Number(1.0)).unwrap();
+
+        assert!(map.contains(first).unwrap());
+        assert!(!map.contains(second).unwrap());
+    }
+
+    #[test]
+    fn test_remove_keeps_order() {
+        let mut map = LoxMap::new();
+        map.insert(Value::Number(1.0), Value::Number(10.0)).unwrap();
+        map.insert(Value::Number(2.0), Value::Number(20.0)).unwrap();
+        map.insert(Value::Number(3.0), Value::Number(30.0)).unwrap();
+
+        assert_eq!(map.remove(Value::Number(2.0)).unwrap(), Some(Value::Number(20.0)));
+        assert_eq!(map.remove(Value::Number(2.0)).unwrap(), None);
+        assert_eq!(map.keys(), vec![Value::Number(1.0), Value::Number(3.0)]);
+        assert_eq!(map.get(Value::Number(3.0)).unwrap(), Some(Value::Number(30.0)));
+    }
+
+    #[test]
+    fn test_unhashable_key() {
+        let mut map = LoxMap::new();
+        let list = Value::List(Rc::new(std::cell::RefCell::new(Vec::new().into())));
+        let result = map.insert(list, Value::Nil);
+
+        assert_eq!(result, Err("Cannot use a value of type List as a map key".to_string()));
+    }
+}
@@ -12,6 +12,12 @@ mod environment;
 mod lox_callable;
 mod resolver;
 mod lox_instance;
+mod lox_map;
+mod heap;
+mod chunk;
+mod compiler;
+mod vm;
+mod diagnostics;
 
 fn main() {
     println!("");
@@ -39,11 +45,14 @@ fn main() {
     // print x.int;
     // x.eat();".to_string());
     
-    let args: Vec<String> = args().collect();
+    let mut args: Vec<String> = args().collect();
+    //--vm runs the bytecode backend instead of the tree-walker, it can come before or after the file
+    let use_vm = args.iter().any(|arg| arg == "--vm");
+    args.retain(|arg| arg != "--vm");
     println!("Detected {} main arguments", args.len());
     if args.len() < 2 {
         println!("No file supplied, starting in interactive mode...");
-        interactive_mode(); 
+        interactive_mode(use_vm); 
     } 
     else {
         let file_path = &args[1];
@@ -59,13 +68,13 @@ fn main() {
         let mut file_contents = String::new();
         match input_file.read_to_string(&mut file_contents) {
             Ok(_) => {
-                scanner::run(file_contents);
+                scanner::run(file_path, file_contents, use_vm);
             },
             Err(error) => panic!("Problem reading the file: {error:?}"),
         };
     }
 
-    fn interactive_mode() {
+    fn interactive_mode(use_vm: bool) {
         println!("Welcome to the Lox interpreter! Type 'exit' to quit.");
         
         let mut accumulated_input = String::new(); // Accumulator for inputs
@@ -83,7 +92,7 @@ fn main() {
     
             accumulated_input.push_str(&input);
             
-            scanner::run(accumulated_input.clone());
+            scanner::run("<stdin>", accumulated_input.clone(), use_vm);
         }
     } 
 
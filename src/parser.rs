@@ -3,11 +3,15 @@ use crate::scanner::{Token, TokenType};
 use crate::expr;
 use crate::expr::Expr;
 use crate::stmt::*;
+use crate::diagnostics::{Diagnostic, DiagnosticKind};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Parser{
     tokens: Vec<Token>,
     current: usize,
+    next_id: usize,
+    //Every error found so far, parsing carries on from the next statement after each one
+    errors: Vec<ParserError>,
 }
 
 impl Default for Parser{
@@ -15,6 +19,8 @@ impl Default for Parser{
         Parser{
             tokens: Vec::new(),
             current: 0,
+            next_id: 0,
+            errors: Vec::new(),
         }
     }
 }
@@ -38,10 +44,16 @@ statement      → exprStmt
                | ifStmt
                | printStmt
                | returnStmt
+               | breakStmt
+               | continueStmt
                | whileStmt
                | block ;
 
 returnStmt     → "return" expression? ";" ;
+breakStmt      → "break" ";" ;
+throwStmt      → "throw" expression ";" ;
+tryStmt        → "try" block ( "catch" "(" IDENTIFIER ")" block )? ( "finally" block )? ;
+continueStmt   → "continue" ";" ;
 
 forStmt        → "for" "(" ( varDecl | exprStmt | ";" )
                  expression? ";"
@@ -57,36 +69,60 @@ printStmt      → "print" expression ";" ;
 varDecl        → "var" IDENTIFIER ( "=" expression )? ";" ;
 expression     → assignment ;
 assignment     → ( call "." )? IDENTIFIER "=" assignment
+               | call "[" expression "]" "=" assignment
                | logic_or ;
 logic_or       → logic_and ( "or" logic_and )* ;
 logic_and      → equality ( "and" equality )* ;
 equality       → comparison ( ( "!=" | "==" ) comparison )* ;
-comparison     → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
+comparison     → bit_or ( ( ">" | ">=" | "<" | "<=" ) bit_or )* ;
+bit_or         → bit_xor ( "|" bit_xor )* ;
+bit_xor        → bit_and ( "^" bit_and )* ;
+bit_and        → shift ( "&" shift )* ;
+shift          → term ( ( "<<" | ">>" ) term )* ;
 term           → factor ( ( "-" | "+" ) factor )* ;
-factor         → unary ( ( "/" | "*" ) unary )* ;
-unary          → ( "!" | "-" ) unary | call ;
-call           → primary ( "(" arguments? ")" )* ;
+factor         → unary ( ( "/" | "*" | "%" | "~/" ) unary )* ;
+unary          → ( "!" | "-" | "~" ) unary | power ;
+power          → call ( "**" unary )? ;
+call           → primary ( "(" arguments? ")" | "." IDENTIFIER | "[" expression "]" )* ;
 arguments      → expression ( "," expression )* ;
 primary        → NUMBER | STRING | "true" | "false" | "nil"
                | "(" expression ")" 
+               | INTERPOLATION expression ( INTERPOLATION expression )* STRING
+               | "[" arguments? "]"
+               | "{" ( entry ( "," entry )* )? "}"
+               | "lambda" "(" parameters? ")" ( block | expression )
                | IDENTIFIER ;
+entry          → expression ":" expression ;
 
 
 */
 
 impl Parser{
-    pub fn parse(&mut self) -> Result<Vec<Stmt>, ParserError>{
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, ParserErrors>{
         let mut statements:Vec<Stmt> = Vec::new();
         while !self.is_at_end(){
-            let stmt: Result<Stmt, ParserError> = self.declaration();
-            match stmt{
-                Ok(stmt) => statements.push(stmt),
-                Err(err) => return Err(err)
+            if let Some(stmt) = self.recovering_declaration(){
+                statements.push(stmt);
             }
         }
+        if !self.errors.is_empty(){
+            return Err(ParserErrors { errors: self.errors.clone(), statements: statements });
+        }
         return Ok(statements);
     }
 
+    //Records a failed declaration and skips to the next statement boundary so the rest still parses
+    fn recovering_declaration(&mut self) -> Option<Stmt>{
+        match self.declaration(){
+            Ok(stmt) => return Some(stmt),
+            Err(err) => {
+                self.errors.push(err);
+                self.synchronize();
+                return None;
+            }
+        }
+    }
+
     fn declaration(&mut self) -> Result<Stmt, ParserError>{
         if self.matches(vec![TokenType::Var]){
             return self.var_declaration();
@@ -105,7 +141,7 @@ impl Parser{
         let mut superclass: Option<Expr> = None;
         if self.matches(vec![TokenType::Less]){
             let sup = self.consume(TokenType::Identifier, format!("Expect superclass name"))?;
-            superclass = Some(Expr::Variable { name:String::from_utf8(sup.lexeme).unwrap(), line: sup.line, col: sup.column })
+            superclass = Some(Expr::Variable { name:String::from_utf8(sup.lexeme).unwrap(), line: sup.line, col: sup.column, id: self.expr_id() })
         }
         self.consume(TokenType::LeftBrace, format!("Expect '{{' before class body"))?;
         let mut methods: Vec<Stmt> = Vec::new();
@@ -123,6 +159,19 @@ impl Parser{
     fn function(&mut self, kind: String) -> Result<Stmt, ParserError>{
         let name: Token = self.consume(TokenType::Identifier, format!("Expect {} name", kind))?;
         let l_paren: Token = self.consume(TokenType::LeftParen, format!("Expect '(' after {} name", kind))?;
+        let parameters: Vec<Token> = self.parameters()?;
+        let brace: Token = self.consume(TokenType::LeftBrace, format!("Expect '{{' before {} body", kind))?;
+        let body: Vec<Stmt> = self.block()?;
+        return Ok(Stmt::Function { 
+            name: String::from_utf8(name.lexeme).unwrap(), 
+            line: name.line, 
+            column: name.column, 
+            parameters: parameters, 
+            body: Box::new(body) 
+        })
+    }
+
+    fn parameters(&mut self) -> Result<Vec<Token>, ParserError>{
         let mut parameters: Vec<Token> = Vec::new();
         if !self.check(TokenType::RightParen){
             loop{
@@ -140,13 +189,28 @@ impl Parser{
                 }
             } 
         }
-        let paren: Token = self.consume(TokenType::RightParen, format!("Expect ')' after parameters"))?;
-        let brace: Token = self.consume(TokenType::LeftBrace, format!("Expect '{{' before {} body", kind))?;
-        let body: Vec<Stmt> = self.block()?;
-        return Ok(Stmt::Function { 
-            name: String::from_utf8(name.lexeme).unwrap(), 
+        self.consume(TokenType::RightParen, format!("Expect ')' after parameters"))?;
+        return Ok(parameters);
+    }
+
+    fn lambda(&mut self) -> Result<Expr, ParserError>{
+        let keyword: Token = self.previous();
+        self.consume(TokenType::LeftParen, format!("Expect '(' after 'lambda'"))?;
+        let parameters: Vec<Token> = self.parameters()?;
+        let body: Vec<Stmt>;
+        if self.matches(vec![TokenType::LeftBrace]){
+            body = self.block()?;
+        }
+        else{
+            //Short form: the body is a single expression whose value is returned
+            let value: Expr = self.expression()?;
+            body = vec![Stmt::Return { keyword: keyword.clone(), value: Some(value) }];
+        }
+        return Ok(Expr::Lambda { 
             parameters: parameters, 
-            body: Box::new(body) 
+            body: Box::new(body), 
+            line: keyword.line, 
+            col: keyword.column 
         })
     }
 
@@ -184,6 +248,25 @@ impl Parser{
         if self.matches(vec![TokenType::Return]){
             return self.return_statement();
         }
+        if self.matches(vec![TokenType::Break]){
+            let keyword: Token = self.previous();
+            self.consume(TokenType::Semicolon, format!("Expect ';' after 'break'"))?;
+            return Ok(Stmt::Break { keyword: keyword });
+        }
+        if self.matches(vec![TokenType::Continue]){
+            let keyword: Token = self.previous();
+            self.consume(TokenType::Semicolon, format!("Expect ';' after 'continue'"))?;
+            return Ok(Stmt::Continue { keyword: keyword });
+        }
+        if self.matches(vec![TokenType::Throw]){
+            let keyword: Token = self.previous();
+            let value: Expr = self.expression()?;
+            self.consume(TokenType::Semicolon, format!("Expect ';' after thrown value"))?;
+            return Ok(Stmt::Throw { keyword: keyword, value: value });
+        }
+        if self.matches(vec![TokenType::Try]){
+            return self.try_statement();
+        }
         return self.expression_statement();
     }
 
@@ -211,13 +294,11 @@ impl Parser{
         }
         let semi = self.consume(TokenType::RightParen, format!("Expect ')' after for clauses"))?;
         let mut body: Stmt = self.statement()?;
-        if increment != None{
-            body = Stmt::Block { statements: vec![body, Stmt::Expr { expression: Box::new(increment.unwrap()) }] };
-        }
         if condition == None{
             condition = Some(Expr::Literal { value: expr::LiteralType::True });
         }
-        body = Stmt::While { condition: condition.unwrap(), body: Box::new(body) };
+        //The increment stays out of the body so that 'continue' still runs it
+        body = Stmt::While { condition: condition.unwrap(), body: Box::new(body), increment: increment };
         if initializer != None{
             body = Stmt::Block { statements: vec![initializer.unwrap(), body] };
         }
@@ -249,6 +330,37 @@ impl Parser{
         }
     }
 
+    fn try_statement(&mut self) -> Result<Stmt, ParserError>{
+        let keyword: Token = self.previous();
+        self.consume(TokenType::LeftBrace, format!("Expect '{{' after 'try'"))?;
+        let body: Vec<Stmt> = self.block()?;
+        let mut catch_name: Option<Token> = None;
+        let mut catch_body: Option<Vec<Stmt>> = None;
+        if self.matches(vec![TokenType::Catch]){
+            self.consume(TokenType::LeftParen, format!("Expect '(' after 'catch'"))?;
+            catch_name = Some(self.consume(TokenType::Identifier, format!("Expect error variable name"))?);
+            self.consume(TokenType::RightParen, format!("Expect ')' after error variable"))?;
+            self.consume(TokenType::LeftBrace, format!("Expect '{{' before catch body"))?;
+            catch_body = Some(self.block()?);
+        }
+        let mut finally_body: Option<Vec<Stmt>> = None;
+        if self.matches(vec![TokenType::Finally]){
+            self.consume(TokenType::LeftBrace, format!("Expect '{{' after 'finally'"))?;
+            finally_body = Some(self.block()?);
+        }
+        if catch_body.is_none() && finally_body.is_none(){
+            //Neither clause matched, so this always reports the missing catch
+            self.consume(TokenType::Catch, format!("Expect 'catch' or 'finally' after try block"))?;
+        }
+        return Ok(Stmt::Try { 
+            keyword: keyword, 
+            body: body, 
+            catch_name: catch_name, 
+            catch_body: catch_body, 
+            finally_body: finally_body 
+        });
+    }
+
     fn expression_statement(&mut self) -> Result<Stmt, ParserError>{
         let value: Expr = self.expression()?;
         let correct_end: Result<Token, ParserError> = self.consume(TokenType::Semicolon, format!("Expect ';' after value"));
@@ -278,12 +390,14 @@ impl Parser{
         let condition: Expr = self.expression()?;
         let end = self.consume(TokenType::RightParen, format!("Expect ')' after condition."));
         let body = self.statement()?;
-        return Ok(Stmt::While { condition: condition, body: Box::new(body) });
+        return Ok(Stmt::While { condition: condition, body: Box::new(body), increment: None });
     }
     fn block(&mut self) -> Result<Vec<Stmt>, ParserError>{
         let mut statements: Vec<Stmt> = Vec::new();
         while !self.check(TokenType::RightBrace) && !self.is_at_end(){
-            statements.push(self.declaration()?);
+            if let Some(stmt) = self.recovering_declaration(){
+                statements.push(stmt);
+            }
             //println!("Death please");
         }
         self.consume(TokenType::RightBrace, format!("Expect '}}' after block."))?;
@@ -299,20 +413,32 @@ impl Parser{
             let equals: Token = self.previous();
             let value: Expr = self.assignment()?;
             //println!("Howdy2");
-            if let Expr::Variable { name, line, col } = expr.clone(){
+            if let Expr::Variable { name, line, col, id } = expr.clone(){
                 return Ok(Expr::Assign { 
                     name: name, 
                     line: line, 
                     column: col, 
-                    value: Box::new(value) 
+                    value: Box::new(value),
+                    id: id
                 })
             }
-            else if let Expr::Get { object, name } = expr.clone(){
+            else if let Expr::Get { object, name, line, col } = expr.clone(){
                 //println!("HERE");
                 return Ok(Expr::Set { 
                     object: object, 
                     name: name, 
-                    value: Box::new(value) 
+                    value: Box::new(value),
+                    line: line,
+                    col: col
+                })
+            }
+            else if let Expr::Index { object, index, line, col } = expr.clone(){
+                return Ok(Expr::IndexSet { 
+                    object: object, 
+                    index: index, 
+                    value: Box::new(value), 
+                    line: line, 
+                    col: col 
                 })
             }
             return Err(ParserError { 
@@ -376,7 +502,7 @@ impl Parser{
     }
 
     fn comparison(&mut self) -> Result<Expr, ParserError>{
-        let mut expr: Expr = self.term()?;
+        let mut expr: Expr = self.bit_or()?;
         while self.matches(vec![
             TokenType::Greater, 
             TokenType::GreaterEqual,
@@ -384,7 +510,7 @@ impl Parser{
             TokenType::LessEqual
             ]){
             let operator: Token = self.previous();
-            let right: Expr = self.term()?;
+            let right: Expr = self.bit_or()?;
             let binary_expr: Result<Expr, ParserError> = Parser::binary_expression_match(expr, operator, right);
             match binary_expr{
                 Ok(binary_expr) => expr = binary_expr,
@@ -394,6 +520,49 @@ impl Parser{
         Ok(expr)
     }
 
+    fn bit_or(&mut self) -> Result<Expr, ParserError>{
+        let mut expr: Expr = self.bit_xor()?;
+        while self.matches(vec![TokenType::Pipe]){
+            let operator: Token = self.previous();
+            let right: Expr = self.bit_xor()?;
+            expr = Parser::binary_expression_match(expr, operator, right)?;
+        }
+        Ok(expr)
+    }
+
+    fn bit_xor(&mut self) -> Result<Expr, ParserError>{
+        let mut expr: Expr = self.bit_and()?;
+        while self.matches(vec![TokenType::Caret]){
+            let operator: Token = self.previous();
+            let right: Expr = self.bit_and()?;
+            expr = Parser::binary_expression_match(expr, operator, right)?;
+        }
+        Ok(expr)
+    }
+
+    fn bit_and(&mut self) -> Result<Expr, ParserError>{
+        let mut expr: Expr = self.shift()?;
+        while self.matches(vec![TokenType::Ampersand]){
+            let operator: Token = self.previous();
+            let right: Expr = self.shift()?;
+            expr = Parser::binary_expression_match(expr, operator, right)?;
+        }
+        Ok(expr)
+    }
+
+    fn shift(&mut self) -> Result<Expr, ParserError>{
+        let mut expr: Expr = self.term()?;
+        while self.matches(vec![
+            TokenType::LessLess,
+            TokenType::GreaterGreater
+            ]){
+            let operator: Token = self.previous();
+            let right: Expr = self.term()?;
+            expr = Parser::binary_expression_match(expr, operator, right)?;
+        }
+        Ok(expr)
+    }
+
     fn term(&mut self) -> Result<Expr, ParserError>{
         let mut expr: Expr = self.factor()?;
         while self.matches(vec![
@@ -416,6 +585,8 @@ impl Parser{
         while self.matches(vec![
             TokenType::Slash, 
             TokenType::Star,
+            TokenType::Mod,
+            TokenType::TildeSlash,
             ]){
             let operator: Token = self.previous();
             let right: Expr = self.unary()?;
@@ -431,7 +602,8 @@ impl Parser{
     fn unary(&mut self) -> Result<Expr, ParserError>{
         if self.matches(vec![
             TokenType::Bang,
-            TokenType::Minus
+            TokenType::Minus,
+            TokenType::Tilde
         ]){
             let operator: Token = self.previous();
             let right: Expr = self.unary()?;
@@ -441,7 +613,18 @@ impl Parser{
                 Err(err) => return Err(err),
             }
         }
-        return self.call();
+        return self.power();
+    }
+
+    //'**' binds tighter than a unary on its left, so -2 ** 2 is -4, and is right associative
+    fn power(&mut self) -> Result<Expr, ParserError>{
+        let expr: Expr = self.call()?;
+        if self.matches(vec![TokenType::StarStar]){
+            let operator: Token = self.previous();
+            let right: Expr = self.unary()?;
+            return Parser::binary_expression_match(expr, operator, right);
+        }
+        return Ok(expr);
     }
 
     fn call(&mut self) -> Result<Expr, ParserError>{
@@ -452,7 +635,23 @@ impl Parser{
             }
             else if self.matches(vec![TokenType::Dot]){
                 let name = self.consume(TokenType::Identifier, format!("Expect property name after '.'"))?;
-                expr = Expr::Get { object: Box::new(expr), name: String::from_utf8(name.lexeme).unwrap()  }
+                expr = Expr::Get { 
+                    object: Box::new(expr), 
+                    name: String::from_utf8(name.lexeme).unwrap(), 
+                    line: name.line, 
+                    col: name.column 
+                }
+            }
+            else if self.matches(vec![TokenType::LeftBracket]){
+                let bracket: Token = self.previous();
+                let index: Expr = self.expression()?;
+                self.consume(TokenType::RightBracket, format!("Expect ']' after index"))?;
+                expr = Expr::Index { 
+                    object: Box::new(expr), 
+                    index: Box::new(index), 
+                    line: bracket.line, 
+                    col: bracket.column 
+                }
             }
             else{
                 break;
@@ -487,6 +686,35 @@ impl Parser{
         })
     }
 
+    //"a${x}b${y}c" arrives as Interpolation("a") x InterpolationEnd Interpolation("b") y InterpolationEnd String("c")
+    fn interpolation(&mut self) -> Result<Expr, ParserError>{
+        let start: Token = self.previous();
+        let mut parts: Vec<Expr> = Vec::new();
+        loop{
+            if let Some(Literal::String(text)) = self.previous().literal{
+                if !text.is_empty(){
+                    parts.push(Expr::Literal { value: expr::LiteralType::String(text) });
+                }
+            }
+            parts.push(self.expression()?);
+            self.consume(TokenType::InterpolationEnd, format!("Expect '}}' after interpolated expression"))?;
+            if self.matches(vec![TokenType::Interpolation]){
+                continue;
+            }
+            let end: Token = self.consume(TokenType::String, format!("Expect the rest of the string after interpolated expression"))?;
+            if let Some(Literal::String(text)) = end.literal{
+                if !text.is_empty(){
+                    parts.push(Expr::Literal { value: expr::LiteralType::String(text) });
+                }
+            }
+            return Ok(Expr::Interpolation { 
+                parts: Box::new(parts), 
+                line: start.line, 
+                col: start.column 
+            })
+        }
+    }
+
     fn primary(&mut self) -> Result<Expr, ParserError>{
         if self.matches(vec![TokenType::False]){
             return Ok(Expr::Literal { value: expr::LiteralType::False,})
@@ -502,6 +730,9 @@ impl Parser{
                 Some(scanner::Literal::Number(num)) => {
                     return Ok(Expr::Literal { value: expr::LiteralType::Number(num) })
                 }
+                Some(scanner::Literal::Int(num)) => {
+                    return Ok(Expr::Literal { value: expr::LiteralType::Int(num) })
+                }
                 Some(_) => panic!("Internal parser error when parsing number"),
                 None => return Err(ParserError { 
                     message: format!("Missing literal when parsing number at line: {}, column: {}",
@@ -527,6 +758,9 @@ impl Parser{
                 })
             }
         }
+        if self.matches(vec![TokenType::Interpolation]){
+            return self.interpolation();
+        }
         if self.matches(vec![TokenType::LeftParen]){
             let expr: Expr = self.expression()?;
             let correct_end = self.consume(TokenType::RightParen, "Expect ')' after expression.".to_string());
@@ -535,8 +769,56 @@ impl Parser{
                 Err(err) => return Err(err)
             }
         }
+        if self.matches(vec![TokenType::LeftBracket]){
+            let bracket: Token = self.previous();
+            let mut elements: Vec<Expr> = Vec::new();
+            if !self.check(TokenType::RightBracket){
+                loop{
+                    elements.push(self.expression()?);
+                    if !self.matches(vec![TokenType::Comma]){
+                        break;
+                    }
+                }
+            }
+            self.consume(TokenType::RightBracket, format!("Expect ']' after list elements"))?;
+            return Ok(Expr::List { 
+                elements: Box::new(elements), 
+                line: bracket.line, 
+                col: bracket.column 
+            })
+        }
+        if self.matches(vec![TokenType::LeftBrace]){
+            let brace: Token = self.previous();
+            let mut entries: Vec<(Expr, Expr)> = Vec::new();
+            if !self.check(TokenType::RightBrace){
+                loop{
+                    let key: Expr = self.expression()?;
+                    self.consume(TokenType::Colon, format!("Expect ':' after map key"))?;
+                    let value: Expr = self.expression()?;
+                    entries.push((key, value));
+                    if !self.matches(vec![TokenType::Comma]){
+                        break;
+                    }
+                }
+            }
+            self.consume(TokenType::RightBrace, format!("Expect '}}' after map entries"))?;
+            return Ok(Expr::Map { 
+                entries: Box::new(entries), 
+                line: brace.line, 
+                col: brace.column 
+            })
+        }
+        if self.matches(vec![TokenType::Lambda]){
+            return self.lambda();
+        }
         if self.matches(vec![TokenType::This]) {
-            return Ok(Expr::This { keyword: String::from_utf8(self.previous().lexeme).unwrap() })
+            let keyword: Token = self.previous();
+            return Ok(Expr::This { 
+                keyword: String::from_utf8(keyword.lexeme).unwrap(), 
+                line: keyword.line, 
+                col: keyword.column, 
+                id: self.expr_id() 
+            })
         }
         if self.matches(vec![TokenType::Identifier]){
             //println!("IN HERE");
@@ -546,7 +828,8 @@ impl Parser{
                     return Ok(Expr::Variable { 
                         name: str.clone(), 
                         line: self.previous().line, 
-                        col: self.previous().column 
+                        col: self.previous().column,
+                        id: self.expr_id()
                     })
                 },
                 Some(_) => panic!("Internal parser error when parsing Identifier"),
@@ -557,7 +840,13 @@ impl Parser{
             let keyword: String = String::from_utf8(self.previous().lexeme).unwrap();
             self.consume(TokenType::Dot, format!("Expect '.' after 'super'"))?;
             let method: Token = self.consume(TokenType::Identifier, format!("Expect superclass method name"))?;
-            return Ok(Expr::Super { keyword: keyword, method: String::from_utf8(method.lexeme).unwrap() })
+            return Ok(Expr::Super { 
+                keyword: keyword, 
+                method: String::from_utf8(method.lexeme).unwrap(), 
+                line: method.line, 
+                col: method.column, 
+                id: self.expr_id() 
+            })
         }
         Err(ParserError {
             message: format!("Expected expression at line: {}, column{}",
@@ -641,6 +930,8 @@ impl Parser{
                 TokenType::While => return,
                 TokenType::Print => return,
                 TokenType::Return => return,
+                TokenType::Throw => return,
+                TokenType::Try => return,
                 _ => ()
             }
             self.advance();
@@ -655,6 +946,11 @@ impl Parser{
         return self.tokens[self.current - 1].clone()
     }
 
+    fn expr_id(&mut self) -> usize{
+        self.next_id += 1;
+        return self.next_id
+    }
+
     fn is_at_end(&self) -> bool{
         return self.peek().token_type == scanner::TokenType::Eof
     }
@@ -684,6 +980,7 @@ impl Parser{
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct ParserError{
     message: String,
     token_type: TokenType,
@@ -695,12 +992,35 @@ impl ParserError{
     pub fn return_error(&self) -> String{
         return self.message.clone()
     }
+
+    pub fn diagnostic(&self) -> Diagnostic{
+        return Diagnostic::new(DiagnosticKind::Parse, self.message.clone(), self.line, self.column);
+    }
+}
+
+//Everything that went wrong in one parse, along with the statements that did parse
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParserErrors{
+    pub errors: Vec<ParserError>,
+    pub statements: Vec<Stmt>
+}
+
+impl ParserErrors{
+    pub fn return_error(&self) -> String{
+        return self.errors.iter().map(|err| err.return_error()).collect::<Vec<String>>().join("\n");
+    }
+
+    pub fn diagnostics(&self) -> Vec<Diagnostic>{
+        return self.errors.iter().map(|err| err.diagnostic()).collect();
+    }
 }
 
-pub fn parse_begin(in_tokens: Vec<Token>) -> Result<Vec<Stmt>, ParserError>{
+pub fn parse_begin(in_tokens: Vec<Token>) -> Result<Vec<Stmt>, ParserErrors>{
     let mut parser: Parser = Parser{
         tokens: in_tokens,
-        current: 0
+        current: 0,
+        next_id: 0,
+        errors: Vec::new()
     };
     let expr = parser.parse();
     match expr{
@@ -718,10 +1038,14 @@ pub fn stmt_ident(in_stmt: Stmt) -> String{
             Stmt::Var { name: _ , line: _ , column: _ , initializer: _ } => return "Var".to_string(),
             Stmt::Block { statements: _ } => return "Block".to_string(),
             Stmt::If { condition: _ , then_branch: _ , else_branch: _ } => return "If".to_string(),
-            Stmt::While { condition: _ , body: _ } => return "While".to_string(),
-            Stmt::Function { name: _ , parameters: _ , body: _ } => return "Function".to_string(),
+            Stmt::While { condition: _ , body: _ , increment: _ } => return "While".to_string(),
+            Stmt::Break { keyword: _ } => return "Break".to_string(),
+            Stmt::Continue { keyword: _ } => return "Continue".to_string(),
+            Stmt::Function { name: _ , line: _ , column: _ , parameters: _ , body: _ } => return "Function".to_string(),
             Stmt::Return { keyword: _ , value: _ } => return "Return".to_string(),
             Stmt::Class { name: _ , superclass: _ , methods: _ } => return "Class".to_string(),
+            Stmt::Throw { keyword: _ , value: _ } => return "Throw".to_string(),
+            Stmt::Try { keyword: _ , body: _ , catch_name: _ , catch_body: _ , finally_body: _ } => return "Try".to_string(),
     }
 }
 
@@ -731,14 +1055,20 @@ pub fn expr_ident(in_expr: Expr) -> String{
         Expr::Grouping { expression:_ } => return "Grouping".to_string(),
         Expr::Literal { value:_ } => return "Literal".to_string(),
         Expr::Unary { operator:_, right:_, line:_, col:_ } => return "Unary".to_string(),
-        Expr::Variable { name:_, line:_, col:_ } => return "Variable".to_string(),
-        Expr::Assign { name:_, line:_, column:_, value:_ } => return "Assign".to_string(),
+        Expr::Variable { name:_, line:_, col:_, id:_ } => return "Variable".to_string(),
+        Expr::Assign { name:_, line:_, column:_, value:_, id:_ } => return "Assign".to_string(),
         Expr::Logical { left:_, operator:_, right:_ } => return "Logical".to_string(),
         Expr::Call { callee:_, paren:_, arguments:_ } => return "Call".to_string(),
-        Expr::Get { object:_, name:_ } => return "Get".to_string(),
-        Expr::Set { object:_, name:_, value:_ } => return "Set".to_string(),
-        Expr::This { keyword:_ } => return "This".to_string(),
-        Expr::Super { keyword:_, method:_ } => return "Super".to_string(),
+        Expr::Get { object:_, name:_, line:_, col:_ } => return "Get".to_string(),
+        Expr::Set { object:_, name:_, value:_, line:_, col:_ } => return "Set".to_string(),
+        Expr::This { keyword:_, line:_, col:_, id:_ } => return "This".to_string(),
+        Expr::Super { keyword:_, method:_, line:_, col:_, id:_ } => return "Super".to_string(),
+        Expr::List { elements:_, line:_, col:_ } => return "List".to_string(),
+        Expr::Index { object:_, index:_, line:_, col:_ } => return "Index".to_string(),
+        Expr::IndexSet { object:_, index:_, value:_, line:_, col:_ } => return "IndexSet".to_string(),
+        Expr::Map { entries:_, line:_, col:_ } => return "Map".to_string(),
+        Expr::Lambda { parameters:_, body:_, line:_, col:_ } => return "Lambda".to_string(),
+        Expr::Interpolation { parts:_, line:_, col:_ } => return "Interpolation".to_string(),
         Expr::None => return "None".to_string(),
     }
 }
@@ -760,6 +1090,11 @@ pub fn tokentype_ident(in_token: TokenType) -> String{
         TokenType::Slash => return "Slash".to_string(),
         TokenType::Star => return "Star".to_string(),
         TokenType::Mod => return "Mod".to_string(),
+        TokenType::TildeSlash => return "TildeSlash".to_string(),
+        TokenType::Tilde => return "Tilde".to_string(),
+        TokenType::Ampersand => return "Ampersand".to_string(),
+        TokenType::Pipe => return "Pipe".to_string(),
+        TokenType::Caret => return "Caret".to_string(),
         TokenType::Bang => return "Bang".to_string(),
         TokenType::BangEqual => return "BangEqual".to_string(),
         TokenType::Equal => return "Equal".to_string(),
@@ -768,8 +1103,13 @@ pub fn tokentype_ident(in_token: TokenType) -> String{
         TokenType::GreaterEqual => return "GreaterEqual".to_string(),
         TokenType::Less => return "Less".to_string(),
         TokenType::LessEqual => return "LessEqual".to_string(),
+        TokenType::LessLess => return "LessLess".to_string(),
+        TokenType::GreaterGreater => return "GreaterGreater".to_string(),
+        TokenType::StarStar => return "StarStar".to_string(),
         TokenType::Identifier => return "Identifier".to_string(),
         TokenType::String => return "String".to_string(),
+        TokenType::Interpolation => return "Interpolation".to_string(),
+        TokenType::InterpolationEnd => return "InterpolationEnd".to_string(),
         TokenType::Number => return "Number".to_string(),
         TokenType::And => return "And".to_string(),
         TokenType::Class => return "Class".to_string(),
@@ -788,6 +1128,13 @@ pub fn tokentype_ident(in_token: TokenType) -> String{
         TokenType::Var => return "Var".to_string(),
         TokenType::While => return "While".to_string(),
         TokenType::Lambda => return "Lamba".to_string(),
+        TokenType::Break => return "Break".to_string(),
+        TokenType::Continue => return "Continue".to_string(),
+        TokenType::Throw => return "Throw".to_string(),
+        TokenType::Try => return "Try".to_string(),
+        TokenType::Catch => return "Catch".to_string(),
+        TokenType::Finally => return "Finally".to_string(),
+        TokenType::Error => return "Error".to_string(),
         TokenType::Eof => return "Eof".to_string(),
         TokenType::None => return "None".to_string(),
     }
@@ -803,7 +1150,7 @@ mod tests{
     fn test_expr() {
         let source = "x + 2;".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
         let stmt = parse_begin(tokens.clone());
         match stmt{
             Ok(stmt) => {
@@ -830,7 +1177,7 @@ mod tests{
     fn test_print() {
         let source = "print \"Hello\";".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
         let stmt = parse_begin(tokens.clone());
         match stmt{
             Ok(stmt) => {
@@ -857,7 +1204,7 @@ mod tests{
     fn test_var() {
         let source = "var three = 3;".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
         let stmt = parse_begin(tokens.clone());
 
         match stmt{
@@ -876,7 +1223,7 @@ mod tests{
                     assert_eq!(var_line, &assert_var);
 
                     //column
-                    let assert_col: i64 = 8;
+                    let assert_col: i64 = 5;
                     assert_eq!(var_col, &assert_col);
 
                     //initializer
@@ -902,7 +1249,7 @@ mod tests{
     fn test_block() {
         let source = "{var x = 3;}".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
         let stmt = parse_begin(tokens.clone());
 
         match stmt{
@@ -928,7 +1275,7 @@ mod tests{
     fn test_if() {
         let source = "if (x == 1) {print \"yes\";} else {x = 2;}".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
         let stmt = parse_begin(tokens.clone());
 
         match stmt{
@@ -972,7 +1319,7 @@ mod tests{
     fn test_while() {
         let source = "while (x < 2) {print \"yes\";}".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
         let stmt = parse_begin(tokens.clone());
 
         match stmt{
@@ -982,7 +1329,7 @@ mod tests{
                 assert_eq!(stmt.len(), 1);
                 assert_eq!(stmt_ident(stmt[0].clone()), "While".to_string());
 
-                if let Stmt::While { condition: while_cond, body: while_body } = &stmt[0]{
+                if let Stmt::While { condition: while_cond, body: while_body, increment: _ } = &stmt[0]{
                     //condition
                     assert_eq!(expr_ident(while_cond.clone()), "Binary");
                 
@@ -1003,7 +1350,7 @@ mod tests{
     fn test_return() {
         let source = "return true;".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
         let stmt = parse_begin(tokens.clone());
 
         match stmt{
@@ -1040,7 +1387,7 @@ mod tests{
     fn test_function() {
         let source = "fun addTest(a,b) {if (a == 1) {return false;}} ".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
         let stmt = parse_begin(tokens.clone());
 
         match stmt{
@@ -1050,7 +1397,7 @@ mod tests{
                 assert_eq!(stmt.len(), 1);
                 assert_eq!(stmt_ident(stmt[0].clone()), "Function".to_string());
 
-                if let Stmt::Function { name: fun_name , parameters: fun_param , body: fun_body } = &stmt[0]{
+                if let Stmt::Function { name: fun_name , line: _ , column: _ , parameters: fun_param , body: fun_body } = &stmt[0]{
                     assert_eq!(fun_name, "addTest");
 
                     assert_eq!(fun_param.len(), 2);
@@ -1072,7 +1419,7 @@ mod tests{
     fn test_class() {
         let source = "class Test \n{\nex() \n{print a;}}".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
         let stmt = parse_begin(tokens.clone());
 
         match stmt{
@@ -1107,4 +1454,382 @@ mod tests{
         }
     }
 
+
+    #[test]
+    fn test_list() {
+        let source = "var xs = [1, \"two\", nil];".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+        let stmt = parse_begin(tokens.clone());
+
+        match stmt{
+            Ok(stmt) => {
+                assert_eq!(stmt.len(), 1);
+                assert_eq!(stmt_ident(stmt[0].clone()), "Var".to_string());
+
+                if let Stmt::Var { name: _ , line: _ , column: _ , initializer: Some(init) } = &stmt[0] {
+                    assert_eq!(expr_ident(init.clone()), "List");
+                    if let Expr::List { elements, line: _ , col: _ } = init{
+                        assert_eq!(elements.len(), 3);
+                        assert_eq!(expr_ident(elements[1].clone()), "Literal");
+                    }
+                }
+                else{
+                    panic!("Test_list match for 'var_init' is empty.");
+                }
+            },
+            Err(_) => {
+                panic!("Test_list match for 'stmt' has errored.");
+            }
+        }
+    }
+
+    #[test]
+    fn test_index_and_index_set() {
+        let source = "xs[0] = xs[1][2];".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+        let stmt = parse_begin(tokens.clone());
+
+        match stmt{
+            Ok(stmt) => {
+                assert_eq!(stmt.len(), 1);
+                if let Stmt::Expr { expression } = &stmt[0] {
+                    assert_eq!(expr_ident(*expression.clone()), "IndexSet");
+                    if let Expr::IndexSet { object, index: _ , value, line, col } = *expression.clone(){
+                        assert_eq!(expr_ident(*object), "Variable");
+                        assert_eq!(expr_ident(*value), "Index");
+                        assert_eq!(line, 1);
+                        assert_eq!(col, 3);
+                    }
+                }
+            },
+            Err(_) => {
+                panic!("Test_index_and_index_set match for 'stmt' has errored.");
+            }
+        }
+    }
+
+    #[test]
+    fn test_unclosed_list() {
+        let source = "print [1, 2;".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+        let stmt = parse_begin(tokens.clone());
+
+        match stmt{
+            Ok(_) => panic!("Expected an error for an unclosed list literal"),
+            Err(err) => assert!(err.return_error().starts_with("Expect ']' after list elements")),
+        }
+    }
+
+
+    #[test]
+    fn test_map() {
+        let source = "var m = {\"a\": 1, 2: [3], nil: {}};".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+        let stmt = parse_begin(tokens.clone());
+
+        match stmt{
+            Ok(stmt) => {
+                assert_eq!(stmt.len(), 1);
+                if let Stmt::Var { name: _ , line: _ , column: _ , initializer: Some(init) } = &stmt[0] {
+                    assert_eq!(expr_ident(init.clone()), "Map");
+                    if let Expr::Map { entries, line: _ , col: _ } = init{
+                        assert_eq!(entries.len(), 3);
+                        assert_eq!(expr_ident(entries[1].1.clone()), "List");
+                        assert_eq!(expr_ident(entries[2].1.clone()), "Map");
+                    }
+                }
+                else{
+                    panic!("Test_map match for 'var_init' is empty.");
+                }
+            },
+            Err(_) => {
+                panic!("Test_map match for 'stmt' has errored.");
+            }
+        }
+    }
+
+    #[test]
+    fn test_block_is_not_map() {
+        let source = "{print 1;}".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+        let stmt = parse_begin(tokens.clone());
+
+        match stmt{
+            Ok(stmt) => assert_eq!(stmt_ident(stmt[0].clone()), "Block"),
+            Err(_) => panic!("Test_block_is_not_map match for 'stmt' has errored."),
+        }
+    }
+
+    #[test]
+    fn test_reports_every_error() {
+        let source = "var a = ;\nprint 1;\nvar = 2;\nprint a +;\nprint 2;".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+        let stmt = parse_begin(tokens.clone());
+
+        match stmt{
+            Ok(_) => panic!("Expected three parser errors"),
+            Err(err) => {
+                let lines: Vec<usize> = err.errors.iter().map(|error| error.line).collect();
+                assert_eq!(lines, vec![1, 3, 4]);
+                let kinds: Vec<String> = err.statements.iter().map(|stmt| stmt_ident(stmt.clone())).collect();
+                assert_eq!(kinds, vec!["Print".to_string(), "Print".to_string()]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_recovers_inside_block() {
+        let source = "{\n  var a = ;\n  print 1;\n}\nprint 2;".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+        let stmt = parse_begin(tokens.clone());
+
+        match stmt{
+            Ok(_) => panic!("Expected a parser error"),
+            Err(err) => {
+                assert_eq!(err.errors.len(), 1, "{}", err.return_error());
+                assert_eq!(err.statements.len(), 2);
+                if let Stmt::Block { statements } = &err.statements[0]{
+                    assert_eq!(statements.len(), 1);
+                }
+                else{
+                    panic!("Expected the block to survive the error");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let source = "print \"a${x}${y}\";\nprint \"${x y}\";".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+        let stmt = parse_begin(tokens.clone());
+
+        match stmt{
+            Ok(_) => panic!("Expected an error for two expressions in one interpolation"),
+            Err(err) => {
+                assert_eq!(err.errors.len(), 1);
+                assert!(err.return_error().starts_with("Expect '}' after interpolated expression at line: 2"), "{}", err.return_error());
+                if let Stmt::Print { expression } = &err.statements[0]{
+                    if let Expr::Interpolation { parts, line: _ , col: _ } = &**expression{
+                        let kinds: Vec<String> = parts.iter().map(|part| expr_ident(part.clone())).collect();
+                        assert_eq!(kinds, vec!["Literal", "Variable", "Variable"]);
+                        return;
+                    }
+                }
+                panic!("Expected the first statement to print an interpolation");
+            }
+        }
+    }
+
+    #[test]
+    fn test_incomplete_interpolation() {
+        let cases = vec![
+            //Each points at the '}' where an expression was expected
+            ("print \"a${}b\";", 11),
+            ("print \"a${1+}b\";", 13),
+            ("print \"${x +}b\" \"c\";", 13),
+        ];
+        for (source, column) in cases{
+            let mut scanner = Scanner::default();
+            let tokens = scanner.scan_tokens(source.to_string()).unwrap();
+
+            match parse_begin(tokens){
+                Ok(_) => panic!("Expected '{}' to fail", source),
+                Err(err) => {
+                    assert_eq!(err.errors.len(), 1, "{}", source);
+                    assert!(err.return_error().starts_with("Expected expression"), "{}", err.return_error());
+                    assert_eq!((err.errors[0].line, err.errors[0].column), (1, column));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_map_missing_colon() {
+        let source = "print {\"a\" 1};".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+        let stmt = parse_begin(tokens.clone());
+
+        match stmt{
+            Ok(_) => panic!("Expected an error for a map entry without ':'"),
+            Err(err) => assert!(err.return_error().starts_with("Expect ':' after map key")),
+        }
+    }
+
+
+    #[test]
+    fn test_mod_precedence() {
+        let source = "1 + 7 % 4;".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+        let stmt = parse_begin(tokens.clone());
+
+        match stmt{
+            Ok(stmt) => {
+                if let Stmt::Expr { expression } = &stmt[0] {
+                    if let Expr::Binary { left: _ , operator, right, line: _ , col: _ } = *expression.clone(){
+                        assert_eq!(operator, expr::BinaryOpType::Plus);
+                        if let Expr::Binary { left: _ , operator, right: _ , line: _ , col: _ } = *right{
+                            assert_eq!(operator, expr::BinaryOpType::Mod);
+                        }
+                        else{
+                            panic!("Test_mod_precedence expected '%' to bind tighter than '+'.");
+                        }
+                    }
+                }
+            },
+            Err(_) => {
+                panic!("Test_mod_precedence match for 'stmt' has errored.");
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_statement() {
+        let source = "try { throw 1; } catch (e) { print e; } finally { print 2; }\ntry { } finally { }\ntry { }\nvar after = 1;".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+        let errors = parse_begin(tokens).unwrap_err();
+
+        assert_eq!(errors.errors.len(), 1);
+        assert_eq!(errors.errors[0].message, "Expect 'catch' or 'finally' after try block at line: 4, column: 1");
+        assert_eq!(stmt_ident(errors.statements[0].clone()), "Try");
+        if let Stmt::Try { keyword: _ , body, catch_name, catch_body, finally_body } = errors.statements[0].clone(){
+            assert_eq!(stmt_ident(body[0].clone()), "Throw");
+            assert_eq!(catch_name.unwrap().lexeme, b"e".to_vec());
+            assert_eq!(catch_body.unwrap().len(), 1);
+            assert_eq!(finally_body.unwrap().len(), 1);
+        }
+        //The bad try is dropped, and synchronizing skips the token it stopped at
+        assert_eq!(errors.statements.len(), 2);
+    }
+
+    #[test]
+    fn test_power_precedence() {
+        //-2 ** 3 ** 2 is -(2 ** (3 ** 2))
+        let source = "-2 ** 3 ** 2;".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+        let stmt = parse_begin(tokens).unwrap();
+
+        if let Stmt::Expr { expression } = &stmt[0] {
+            if let Expr::Unary { operator, right, line: _ , col: _ } = *expression.clone(){
+                assert_eq!(operator, expr::UnaryOpType::Minus);
+                if let Expr::Binary { left: _ , operator, right, line: _ , col: _ } = *right{
+                    assert_eq!(operator, expr::BinaryOpType::Power);
+                    assert!(matches!(*right, Expr::Binary { operator: expr::BinaryOpType::Power, .. }), "Expected '**' to be right associative");
+                    return;
+                }
+            }
+        }
+        panic!("Test_power_precedence expected '**' to bind tighter than unary '-'.");
+    }
+
+    #[test]
+    fn test_bitwise_precedence() {
+        //Shifts bind tighter than '&', then '^', then '|', all tighter than comparisons
+        let source = "1 | 2 ^ 3 & 4 << 1 == 7;".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+        let stmt = parse_begin(tokens).unwrap();
+
+        if let Stmt::Expr { expression } = &stmt[0] {
+            if let Expr::Binary { left, operator: expr::BinaryOpType::EqualEqual, right: _ , line: _ , col: _ } = *expression.clone(){
+                if let Expr::Binary { left: _ , operator: expr::BinaryOpType::BitOr, right, line: _ , col: _ } = *left{
+                    if let Expr::Binary { left: _ , operator: expr::BinaryOpType::BitXor, right, line: _ , col: _ } = *right{
+                        if let Expr::Binary { left: _ , operator: expr::BinaryOpType::BitAnd, right, line: _ , col: _ } = *right{
+                            assert!(matches!(*right, Expr::Binary { operator: expr::BinaryOpType::ShiftLeft, .. }));
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+        panic!("Test_bitwise_precedence built the wrong tree.");
+    }
+
+
+    #[test]
+    fn test_lambda() {
+        let source = "var add = lambda (a, b) { return a + b; };\nvar twice = lambda (x) x * 2;".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+        let stmt = parse_begin(tokens.clone());
+
+        match stmt{
+            Ok(stmt) => {
+                assert_eq!(stmt.len(), 2);
+                if let Stmt::Var { name: _ , line: _ , column: _ , initializer: Some(init) } = &stmt[0] {
+                    assert_eq!(expr_ident(init.clone()), "Lambda");
+                    if let Expr::Lambda { parameters, body, line: _ , col: _ } = init{
+                        assert_eq!(parameters.len(), 2);
+                        assert_eq!(body.len(), 1);
+                        assert_eq!(stmt_ident(body[0].clone()), "Return");
+                    }
+                }
+                else{
+                    panic!("Test_lambda match for 'var_init' is empty.");
+                }
+                if let Stmt::Var { name: _ , line: _ , column: _ , initializer: Some(init) } = &stmt[1] {
+                    if let Expr::Lambda { parameters, body, line, col: _ } = init{
+                        assert_eq!(parameters.len(), 1);
+                        assert_eq!(*line, 2);
+                        if let Stmt::Return { keyword: _ , value: Some(value) } = &body[0]{
+                            assert_eq!(expr_ident(value.clone()), "Binary");
+                        }
+                        else{
+                            panic!("Test_lambda expected the short form to return its expression.");
+                        }
+                    }
+                }
+            },
+            Err(_) => {
+                panic!("Test_lambda match for 'stmt' has errored.");
+            }
+        }
+    }
+
+
+    #[test]
+    fn test_for_with_continue() {
+        let source = "for (var i = 0; i < 3; i = i + 1) { if (i == 1) continue; break; }".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+        let stmt = parse_begin(tokens.clone());
+
+        match stmt{
+            Ok(stmt) => {
+                assert_eq!(stmt.len(), 1);
+                if let Stmt::Block { statements } = &stmt[0] {
+                    assert_eq!(statements.len(), 2);
+                    if let Stmt::While { condition: _ , body, increment } = &statements[1]{
+                        assert!(increment.is_some(), "Expected the for increment to be kept on the loop");
+                        if let Stmt::Block { statements: body_stmts } = *body.clone(){
+                            assert_eq!(body_stmts.len(), 2);
+                            if let Stmt::If { condition: _ , then_branch, else_branch: _ } = body_stmts[0].clone(){
+                                assert_eq!(stmt_ident(*then_branch), "Continue");
+                            }
+                            assert_eq!(stmt_ident(body_stmts[1].clone()), "Break");
+                        }
+                    }
+                    else{
+                        panic!("Test_for_with_continue expected a while loop.");
+                    }
+                }
+            },
+            Err(_) => {
+                panic!("Test_for_with_continue match for 'stmt' has errored.");
+            }
+        }
+    }
+
 }
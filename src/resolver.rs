@@ -4,24 +4,31 @@ use crate::stmt::*;
 use crate::expr::*;
 use crate::interpreter::*;
 use crate::environment::*;
+use crate::diagnostics::{Diagnostic, DiagnosticKind};
 
 pub struct Resolver{
     pub interpreter: Interpreter,
     pub scopes: Vec<HashMap<String, bool>>,
+    //Names in the order they were declared in each scope, a name's position is its slot
+    pub slots: Vec<Vec<String>>,
     pub errors: Vec<String>,
+    //line and column of each entry in errors, (0, 0) when unknown
+    pub error_positions: Vec<(usize, i64)>,
     pub state: ResolverState,
     pub current_class: ClassState
 }
 
 #[derive(Debug)]
 pub struct ResolverState{
-    function: FunctionState
+    function: FunctionState,
+    loop_depth: usize
 }
 
 impl Default for ResolverState{
     fn default() -> Self {
         ResolverState{
-            function: FunctionState::None
+            function: FunctionState::None,
+            loop_depth: 0
         }
     }
 }
@@ -46,19 +53,20 @@ impl Resolver{
         Resolver { 
             interpreter: interpreter,
             scopes: Vec::new(),
+            slots: Vec::new(),
             errors: Vec::new(),
+            error_positions: Vec::new(),
             state: ResolverState::default(),
             current_class: ClassState::None
         }        
     }
 
+    //Top level declarations are globals, so they get no scope and stay unresolved
     pub fn resolve(&mut self, stmts: Vec<Stmt>) -> (Result<bool, Vec<String>>, &Interpreter){
-        self.begin_scope();
         self.resolve_vec_stmt(stmts);
         if self.errors.is_empty(){
             return (Ok(true), &self.interpreter);
         }
-        self.end_scope();
         return (Err(self.errors.clone()), &self.interpreter);
     }
 
@@ -77,13 +85,7 @@ impl Resolver{
             }
             Stmt::Class { name, superclass , methods } => {
                 let enclosing_class = self.current_class;
-                self.begin_scope();
                 self.current_class = ClassState::Class;
-                let x = self.scopes.last_mut();
-                match x{
-                    Some(scop) => scop.insert(format!("this"), true),
-                    None => return ()
-                };
                 self.declare(name.clone());
                 self.define(name.clone());
                 let class_name = name.clone();
@@ -92,26 +94,27 @@ impl Resolver{
                     Some(sup) => {
                         self.current_class = ClassState::SubClass;
                         is_super = true;
-                        if let Expr::Variable { name, line: _ , col: _ } = sup.clone(){
+                        if let Expr::Variable { name, line, col, id: _ } = sup.clone(){
                             if name.eq(&class_name){
-                                self.errors.push(format!("A class can't inherit from itself."));
+                                self.error(format!("A class can't inherit from itself."), line, col);
                             }
                             self.resolve_expr(sup);
                         }
                     }
                     None => ()
                 }
+                //Same nesting as the runtime: the super scope wraps the scope bind() adds for this
                 if is_super{
                     self.begin_scope();
-                    let x = self.scopes.last_mut();
-                    match x{
-                        Some(scop) => scop.insert(format!("super"), true),
-                        None => return ()
-                    };
+                    self.declare(format!("super"));
+                    self.define(format!("super"));
                 }
+                self.begin_scope();
+                self.declare(format!("this"));
+                self.define(format!("this"));
                 for method in *methods{
                     let mut declaration = FunctionState::Method;
-                    if let Stmt::Function { name, parameters, body } = method.clone(){
+                    if let Stmt::Function { name, line: _ , column: _ , parameters: _ , body: _ } = method.clone(){
                         if name.eq(&format!("init")){
                             declaration = FunctionState::Init
                         }
@@ -127,7 +130,7 @@ impl Resolver{
             Stmt::Expr { expression } => {
                 self.resolve_expr(*expression);
             }
-            Stmt::Function { name, parameters: _ , body: _ } => {
+            Stmt::Function { name, line: _ , column: _ , parameters: _ , body: _ } => {
                 self.declare(name.clone());
                 self.define(name);
                 self.resolve_function(stmt, FunctionState::Function);
@@ -144,11 +147,11 @@ impl Resolver{
             }
             Stmt::Return { keyword, value } => {
                 if self.state.function == FunctionState::None{
-                    self.errors.push(format!("Illegal return statement"));
+                    self.error(format!("Illegal return statement"), keyword.line, keyword.column);
                 }
                 if let Some(value) = value{
                     if self.state.function == FunctionState::Init{
-                        self.errors.push(format!("Can't return a value from an initializer"));
+                        self.error(format!("Can't return a value from an initializer"), keyword.line, keyword.column);
                     }
                     self.resolve_expr(value);
                 }
@@ -162,18 +165,51 @@ impl Resolver{
                 }
                 self.define(name);
             }
-            Stmt::While { condition, body } => {
+            Stmt::While { condition, body, increment } => {
                 self.resolve_expr(condition);
+                self.state.loop_depth += 1;
                 self.resolve_stmt(*body);
+                self.state.loop_depth -= 1;
+                if let Some(increment) = increment{
+                    self.resolve_expr(increment);
+                }
+            }
+            Stmt::Break { keyword } => {
+                if self.state.loop_depth == 0{
+                    self.error(format!("Can't use 'break' outside of a loop"), keyword.line, keyword.column);
+                }
+            }
+            Stmt::Continue { keyword } => {
+                if self.state.loop_depth == 0{
+                    self.error(format!("Can't use 'continue' outside of a loop"), keyword.line, keyword.column);
+                }
+            }
+            Stmt::Throw { keyword: _ , value } => {
+                self.resolve_expr(value);
+            }
+            Stmt::Try { keyword: _ , body, catch_name, catch_body, finally_body } => {
+                self.scoped(|resolver| resolver.resolve_vec_stmt(body));
+                //The caught value is the only slot in the scope the catch body runs in, like a parameter
+                if let (Some(catch_name), Some(catch_body)) = (catch_name, catch_body){
+                    self.begin_scope();
+                    let name = String::from_utf8(catch_name.lexeme).unwrap();
+                    self.declare(name.clone());
+                    self.define(name);
+                    self.resolve_vec_stmt(catch_body);
+                    self.end_scope();
+                }
+                if let Some(finally_body) = finally_body{
+                    self.scoped(|resolver| resolver.resolve_vec_stmt(finally_body));
+                }
             }
         }
     }
 
     fn resolve_expr(&mut self, expr: Expr) -> (){
         match expr.clone(){
-            Expr::Assign { name, line, column, value } => {
+            Expr::Assign { name, line: _ , column: _ , value, id: _ } => {
                 self.resolve_expr(*value);
-                self.resolve_local(name.clone(), Expr::Variable { name: name.clone(), line: line, col: column });
+                self.resolve_local(name, expr);
             }
             Expr::Binary { left, operator: _ , right, line: _ , col: _ } => {
                 self.resolve_expr(*left);
@@ -185,7 +221,7 @@ impl Resolver{
                     self.resolve_expr(arg);
                 }
             }
-            Expr::Get { object, name } => {
+            Expr::Get { object, name: _ , line: _ , col: _ } => {
                 self.resolve_expr(*object);
             }
             Expr::Grouping { expression } => {
@@ -198,22 +234,22 @@ impl Resolver{
                 self.resolve_expr(*left);
                 self.resolve_expr(*right);
             }
-            Expr::Set { object, name: _ , value } => {
+            Expr::Set { object, name: _ , value, line: _ , col: _ } => {
                 self.resolve_expr(*object);
                 self.resolve_expr(*value);
             }
-            Expr::Super { keyword, method: _ } => {
+            Expr::Super { keyword, method: _ , line, col, id: _ } => {
                 if self.current_class == ClassState::None{
-                    self.errors.push(format!("Can't use 'super' outside of a class"));
+                    self.error(format!("Can't use 'super' outside of a class"), line, col);
                 }
                 else if self.current_class != ClassState::SubClass{
-                    self.errors.push(format!("Can't use 'super' in a class with no superclass"));
+                    self.error(format!("Can't use 'super' in a class with no superclass"), line, col);
                 }
                 self.resolve_local(keyword, expr);
             }
-            Expr::This { keyword } => {
+            Expr::This { keyword, line, col, id: _ } => {
                 if self.current_class == ClassState::None{
-                    self.errors.push(format!("Can't use 'this' outside of class."));
+                    self.error(format!("Can't use 'this' outside of class."), line, col);
                     return ();
                 }
                 self.resolve_local(keyword, expr);
@@ -221,27 +257,70 @@ impl Resolver{
             Expr::Unary { operator: _ , right, line: _ , col: _ } => {
                 self.resolve_expr(*right);
             }
-            Expr::Variable { name, line: _ , col: _ } => {
+            Expr::Variable { name, line, col, id: _ } => {
                 //println!("Resolving {} Expression", name.clone());
                 if self.query(name.clone(), false){
-                    self.errors.push(format!("Cannot read local variable in its own initializer"));
+                    self.error(format!("Cannot read local variable in its own initializer"), line, col);
                     return ();
                 }
                 //println!("Resolving {} Expression Pt 2", name.clone());
                 self.resolve_local(name, expr);
             }
+            Expr::List { elements, line: _ , col: _ } => {
+                for element in *elements{
+                    self.resolve_expr(element);
+                }
+            }
+            Expr::Index { object, index, line: _ , col: _ } => {
+                self.resolve_expr(*object);
+                self.resolve_expr(*index);
+            }
+            Expr::IndexSet { object, index, value, line: _ , col: _ } => {
+                self.resolve_expr(*object);
+                self.resolve_expr(*index);
+                self.resolve_expr(*value);
+            }
+            Expr::Map { entries, line: _ , col: _ } => {
+                for (key, value) in *entries{
+                    self.resolve_expr(key);
+                    self.resolve_expr(value);
+                }
+            }
+            Expr::Lambda { parameters, body, line, col } => {
+                let declaration = Stmt::Function { name: format!("lambda"), line: line, column: col, parameters: parameters, body: body };
+                self.resolve_function(declaration, FunctionState::Function);
+            }
+            Expr::Interpolation { parts, line: _ , col: _ } => {
+                for part in *parts{
+                    self.resolve_expr(part);
+                }
+            }
             Expr::None => {
-                self.errors.push(format!("Wtf are you doing here"));
+                self.error(format!("Wtf are you doing here"), 0, 0);
             }
         }
     }
 
+    fn error(&mut self, message: String, line: usize, col: i64) -> (){
+        self.errors.push(message);
+        self.error_positions.push((line, col));
+    }
+
+    pub fn diagnostics(&self) -> Vec<Diagnostic>{
+        return self.errors.iter().zip(self.error_positions.iter())
+        .map(|(message, (line, col))| Diagnostic::new(DiagnosticKind::Resolve, message.clone(), *line, *col))
+        .collect();
+    }
+
     fn declare(&mut self, name: String) -> (){
         if self.scopes.is_empty(){
             return ();
         }
         if let Some(scope) = self.scopes.last_mut(){
-            scope.insert(name, false);
+            scope.insert(name.clone(), false);
+        }
+        if let Some(slots) = self.slots.last_mut(){
+            slots.push(name);
         }
     }
 
@@ -256,7 +335,7 @@ impl Resolver{
                     return ();
                 }
                 None => {
-                    self.errors.push(format!("Variable {} is not defined", name));
+                    self.error(format!("Variable {} is not defined", name), 0, 0);
                 }
             }
         }
@@ -279,15 +358,20 @@ impl Resolver{
             //println!("Depth: {}", depth);
             if scope.contains_key(&name){
                 //println!("HERE {}", name.clone());
-                self.interpreter.resolve_local(name, depth, expr);
+                //A redeclared name uses its latest slot
+                let slots = &self.slots[self.slots.len() - 1 - depth];
+                let slot = slots.iter().rposition(|slot_name| *slot_name == name).unwrap_or(0);
+                self.interpreter.resolve_local(&expr, depth, slot);
                 return ();
             }
         }
     }
 
     fn resolve_function(&mut self, stmt: Stmt, state: FunctionState){
-        if let Stmt::Function { name, parameters, body } = stmt{
+        if let Stmt::Function { name, line: _ , column: _ , parameters, body } = stmt{
             let prior_state = mem::replace(&mut self.state.function, state);
+            //A loop outside the function body does not make 'break' legal inside it
+            let prior_loop_depth = mem::replace(&mut self.state.loop_depth, 0);
             self.begin_scope();
             for param in parameters{
                 self.declare(String::from_utf8(param.lexeme.clone()).unwrap());
@@ -296,6 +380,7 @@ impl Resolver{
             self.resolve_vec_stmt(*body);
             self.end_scope();
             self.state.function = prior_state;
+            self.state.loop_depth = prior_loop_depth;
         }
         else{
             panic!("Unreachable function resolver");
@@ -304,10 +389,12 @@ impl Resolver{
 
     fn begin_scope(&mut self) -> (){
         self.scopes.push(HashMap::new());
+        self.slots.push(Vec::new());
     }
 
     fn end_scope(&mut self) -> (){
         self.scopes.pop();
+        self.slots.pop();
     }
 
 
@@ -414,6 +501,7 @@ mod tests {
             line: 1,
             column: 1,
             value: Box::new(Expr::Literal { value: LiteralType::Number(42.0) }),
+            id: 1,
         };
         let expr_binary = Expr::Binary {
             left: Box::new(Expr::Literal { value: LiteralType::Number(1.0) }),
@@ -502,6 +590,8 @@ mod tests {
     fn test_resolve_function() {
         let function_stmt = Stmt::Function {
             name: "my_function".to_string(),
+            line: 1,
+            column: 1,
             parameters: vec![
                 crate::scanner::Token {
                     token_type: crate::scanner::TokenType::Identifier,
@@ -593,6 +683,8 @@ mod tests {
 
         let method = Stmt::Function {
             name: "method_name".to_string(),
+            line: 1,
+            column: 1,
             parameters: vec![],
             body: Box::new(vec![
                 Stmt::Print {
@@ -739,4 +831,144 @@ mod tests {
         assert!(!resolver.query("var2".to_string(), true), "Expected 'var2' to be out of scope after ending scope");
         assert!(!resolver.query("var_in_function".to_string(), true), "Expected 'var_in_function' to be out of scope after ending scope");
     } 
-}
\ No newline at end of file
+
+    #[test]
+    fn test_lambda_resolution() {
+        let interpreter = Interpreter::new(Vec::new());
+        let mut resolver = Resolver::new(interpreter);
+
+        let keyword = crate::scanner::Token {
+            token_type: crate::scanner::TokenType::Lambda,
+            lexeme: b"lambda".to_vec(),
+            literal: None,
+            line: 1,
+            column: 5,
+        };
+        let lambda = Expr::Lambda {
+            parameters: vec![],
+            body: Box::new(vec![
+                Stmt::Return { keyword: keyword, value: Some(Expr::Literal { value: LiteralType::Number(1.0) }) }
+            ]),
+            line: 1,
+            col: 5,
+        };
+
+        resolver.resolve_expr(lambda);
+
+        assert!(resolver.errors.is_empty(), "Resolver encountered errors: {:?}", resolver.errors);
+        assert_eq!(resolver.state.function, FunctionState::None, "Expected function state to be restored");
+    }
+
+    #[test]
+    fn test_break_outside_loop() {
+        let interpreter = Interpreter::new(Vec::new());
+        let mut resolver = Resolver::new(interpreter);
+
+        let keyword = crate::scanner::Token {
+            token_type: crate::scanner::TokenType::Break,
+            lexeme: b"break".to_vec(),
+            literal: None,
+            line: 1,
+            column: 4,
+        };
+        resolver.resolve_stmt(Stmt::Break { keyword: keyword });
+
+        assert_eq!(resolver.errors, vec!["Can't use 'break' outside of a loop".to_string()]);
+    }
+
+    #[test]
+    fn test_continue_inside_function_inside_loop() {
+        let interpreter = Interpreter::new(Vec::new());
+        let mut resolver = Resolver::new(interpreter);
+
+        let keyword = crate::scanner::Token {
+            token_type: crate::scanner::TokenType::Continue,
+            lexeme: b"continue".to_vec(),
+            literal: None,
+            line: 2,
+            column: 7,
+        };
+        let function = Stmt::Function {
+            name: "inner".to_string(),
+            line: 1,
+            column: 1,
+            parameters: vec![],
+            body: Box::new(vec![Stmt::Continue { keyword: keyword.clone() }]),
+        };
+        let loop_stmt = Stmt::While {
+            condition: Expr::Literal { value: LiteralType::True },
+            body: Box::new(Stmt::Block { statements: vec![function, Stmt::Continue { keyword: keyword }] }),
+            increment: None,
+        };
+        resolver.resolve_stmt(loop_stmt);
+
+        assert_eq!(resolver.errors, vec!["Can't use 'continue' outside of a loop".to_string()]);
+    }
+
+    #[test]
+    fn test_shadowed_names_resolve_by_id() {
+        let interpreter = Interpreter::new(Vec::new());
+        let mut resolver = Resolver::new(interpreter);
+
+        let read_outer = Expr::Variable { name: "x".to_string(), line: 5, col: 1, id: 1 };
+        let read_inner = Expr::Variable { name: "x".to_string(), line: 3, col: 1, id: 2 };
+        let declare = |line: usize| Stmt::Var {
+            name: "x".to_string(),
+            initializer: Some(Expr::Literal { value: LiteralType::Number(1.0) }),
+            line: line,
+            column: 5,
+        };
+        let block = Stmt::Block { statements: vec![
+            declare(1),
+            Stmt::Block { statements: vec![
+                Stmt::Block { statements: vec![
+                    Stmt::Print { expression: Box::new(read_outer) },
+                ]},
+                declare(2),
+                Stmt::Print { expression: Box::new(read_inner) },
+            ]},
+        ]};
+
+        let (result, interpreter) = resolver.resolve(vec![block]);
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(interpreter.locals.get(&1), Some(&(2, 0)), "Expected the read before the shadowing declaration to reach the outer x");
+        assert_eq!(interpreter.locals.get(&2), Some(&(0, 0)), "Expected the read after the shadowing declaration to use the inner x");
+    }
+
+    #[test]
+    fn test_slots_follow_declaration_order() {
+        let interpreter = Interpreter::new(Vec::new());
+        let mut resolver = Resolver::new(interpreter);
+
+        let param = crate::scanner::Token {
+            token_type: crate::scanner::TokenType::Identifier,
+            lexeme: b"n".to_vec(),
+            literal: None,
+            line: 1,
+            column: 8,
+        };
+        let local = Stmt::Var {
+            name: "total".to_string(),
+            initializer: Some(Expr::Variable { name: "n".to_string(), line: 2, col: 15, id: 1 }),
+            line: 2,
+            column: 11,
+        };
+        let function = Stmt::Function {
+            name: "f".to_string(),
+            line: 1,
+            column: 1,
+            parameters: vec![param],
+            body: Box::new(vec![
+                local,
+                Stmt::Print { expression: Box::new(Expr::Variable { name: "total".to_string(), line: 3, col: 13, id: 2 }) },
+            ]),
+        };
+
+        let (result, interpreter) = resolver.resolve(vec![function]);
+
+        assert_eq!(result, Ok(true));
+        assert_eq!(interpreter.locals.get(&1), Some(&(0, 0)), "Expected the parameter to take the first slot");
+        assert_eq!(interpreter.locals.get(&2), Some(&(0, 1)), "Expected the local to follow the parameter");
+    }
+}
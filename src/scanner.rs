@@ -5,11 +5,16 @@ use std::path::Path;
 use std::fs;
 use std::io::Error;
 use std::str;
+use std::panic;
+use std::thread;
 use text_io::read;
 
+use crate::compiler;
+use crate::diagnostics::{self, Diagnostic, DiagnosticKind};
 use crate::interpreter::Interpreter;
 use crate::parser::{self};
 use crate::resolver::Resolver;
+use crate::vm;
 
 #[derive(Eq, PartialEq, Debug, Copy, Clone)]
 pub enum TokenType {
@@ -29,6 +34,12 @@ pub enum TokenType {
     Slash,
     Star,
     Mod,
+    //~/ is integer division, // already starts a comment
+    TildeSlash,
+    Tilde,
+    Ampersand,
+    Pipe,
+    Caret,
 
     // One or two character tokens.
     Bang,
@@ -39,10 +50,17 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    LessLess,
+    GreaterGreater,
+    StarStar,
 
     // Literals.
     Identifier,
     String,
+    //The text of a string up to a "${", the embedded expression's tokens follow it
+    Interpolation,
+    //The '}' closing an embedded expression, the rest of the string follows it
+    InterpolationEnd,
     Number,
 
     // Keywords.
@@ -63,6 +81,14 @@ pub enum TokenType {
     Var,
     While,
     Lambda,
+    Break,
+    Continue,
+    Throw,
+    Try,
+    Catch,
+    Finally,
+    //Stands in for text the scanner could not make sense of, so scanning can carry on past it
+    Error,
     Eof,
     None
 }
@@ -88,6 +114,7 @@ pub enum Literal {
     Identifier(String),
     String(String),
     Number(f64),
+    Int(i64),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -97,15 +124,28 @@ pub struct ScannerError{
     pub column: i64,
 }
 
+impl ScannerError{
+    pub fn diagnostic(&self) -> Diagnostic{
+        return Diagnostic::new(DiagnosticKind::Scan, self.error.clone(), self.line, self.column);
+    }
+}
+
 pub struct Scanner{
     tokens: Vec<Token>,
-    source: Vec<u8>,
-    error: Option<ScannerError>,
+    //Scanned by character rather than byte so UTF-8 survives and columns count characters
+    source: Vec<char>,
+    errors: Vec<ScannerError>,
     start: usize,
     current: usize,
     line: usize,
     column: i64,
+    //Where the token being scanned begins, tokens report their first character
+    start_line: usize,
+    start_column: i64,
     keywords: HashMap<String, TokenType>,
+    //One entry per "${" still open: braces opened inside it, whether its string is triple quoted,
+    //and the line and column of that string's opening quote
+    interpolations: Vec<(usize, bool, usize, i64)>,
 }
 
 impl Default for Scanner{
@@ -113,11 +153,13 @@ impl Default for Scanner{
         Scanner{
             tokens: Vec::new(),
             source: Vec::new(),
-            error: None,
+            errors: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
-            column: -1,
+            column: 0,
+            start_line: 1,
+            start_column: 1,
             keywords: vec![
                 ("and".to_string(), TokenType::And),
                 ("class".to_string(), TokenType::Class),
@@ -135,33 +177,43 @@ impl Default for Scanner{
                 ("true".to_string(), TokenType::True),
                 ("var".to_string(), TokenType::Var),
                 ("while".to_string(), TokenType::While),
-                ("lambda".to_string(), TokenType::Lambda)
-            ].into_iter().map(|(k, v)| (k, v)).collect()
+                ("lambda".to_string(), TokenType::Lambda),
+                ("break".to_string(), TokenType::Break),
+                ("continue".to_string(), TokenType::Continue),
+                ("throw".to_string(), TokenType::Throw),
+                ("try".to_string(), TokenType::Try),
+                ("catch".to_string(), TokenType::Catch),
+                ("finally".to_string(), TokenType::Finally)
+            ].into_iter().map(|(k, v)| (k, v)).collect(),
+            interpolations: Vec::new()
         }
     }
 }
 
 impl Scanner{
-    pub fn scan_tokens(&mut self, input_file: String) -> Vec<Token>{
-        //Starts scanning process, continues until eof or error
-        self.source = input_file.into_bytes();
-        while !self.is_done_with_error(){
+    pub fn scan_tokens(&mut self, input_file: String) -> Result<Vec<Token>, Vec<ScannerError>>{
+        //Starts scanning process, continues until eof recording every error on the way
+        self.source = input_file.chars().collect();
+        while !self.is_finished(){
             self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column + 1;
             self.scan_individual_tokens();
         }
-        match self.error{
-            Some(_) => {
-                print_error(self.error.clone().unwrap());
-                return Vec::new();
-            },
-            None => self.tokens.push(Token {
-                token_type: TokenType::Eof,
-                lexeme: Vec::new(),
-                literal: None,
-                line: self.line,
-                column: self.column })
+        if let Some(&(_, _, line, column)) = self.interpolations.first(){
+            self.interpolations.clear();
+            self.report(format!("Unterminated string"), line, column);
+        }
+        self.tokens.push(Token {
+            token_type: TokenType::Eof,
+            lexeme: Vec::new(),
+            literal: None,
+            line: self.line,
+            column: self.column + 1 });
+        if !self.errors.is_empty(){
+            return Err(self.errors.clone());
         }
-        return self.tokens.clone();
+        return Ok(self.tokens.clone());
     }
     
     fn scan_individual_tokens(&mut self) -> (){
@@ -171,8 +223,30 @@ impl Scanner{
         match scanned_char {
             '(' => self.add_token(TokenType::LeftParen, None),
             ')' => self.add_token(TokenType::RightParen, None),
-            '{' => self.add_token(TokenType::LeftBrace, None),
-            '}' => self.add_token(TokenType::RightBrace, None),
+            '{' => {
+                if let Some(interpolation) = self.interpolations.last_mut(){
+                    interpolation.0 += 1;
+                }
+                self.add_token(TokenType::LeftBrace, None);
+            }
+            '}' => {
+                match self.interpolations.last_mut(){
+                    //Closes a "${", so the string it was embedded in carries on
+                    Some(&mut (0, triple, line, column)) => {
+                        self.interpolations.pop();
+                        self.add_token(TokenType::InterpolationEnd, None);
+                        self.start = self.current;
+                        self.start_line = self.line;
+                        self.start_column = self.column + 1;
+                        self.string_body(false, triple, line, column);
+                    }
+                    Some(interpolation) => {
+                        interpolation.0 -= 1;
+                        self.add_token(TokenType::RightBrace, None);
+                    }
+                    None => self.add_token(TokenType::RightBrace, None)
+                }
+            }
             '[' => self.add_token(TokenType::LeftBracket, None),
             ']' => self.add_token(TokenType::RightBracket, None),
             ',' => self.add_token(TokenType::Comma, None),
@@ -181,8 +255,26 @@ impl Scanner{
             '+' => self.add_token(TokenType::Plus, None),
             ';' => self.add_token(TokenType::Semicolon, None),
             ':' => self.add_token(TokenType::Colon, None),
-            '*' => self.add_token(TokenType::Star, None),
+            '*' => {
+                if self.matches('*'){
+                    self.add_token(TokenType::StarStar, None);
+                }
+                else{
+                    self.add_token(TokenType::Star, None);
+                }
+            }
             '%' => self.add_token(TokenType::Mod,None),
+            '~' => {
+                if self.matches('/'){
+                    self.add_token(TokenType::TildeSlash, None);
+                }
+                else{
+                    self.add_token(TokenType::Tilde, None);
+                }
+            }
+            '&' => self.add_token(TokenType::Ampersand, None),
+            '|' => self.add_token(TokenType::Pipe, None),
+            '^' => self.add_token(TokenType::Caret, None),
             '!' => {
                 let is_equal: bool = self.matches('=');
                 if is_equal {
@@ -206,6 +298,9 @@ impl Scanner{
                 if is_equal {
                     self.add_token(TokenType::LessEqual, None);
                 }
+                else if self.matches('<'){
+                    self.add_token(TokenType::LessLess, None);
+                }
                 else{
                     self.add_token(TokenType::Less, None);
                 }
@@ -215,6 +310,9 @@ impl Scanner{
                 if is_equal {
                     self.add_token(TokenType::GreaterEqual, None);
                 }
+                else if self.matches('>'){
+                    self.add_token(TokenType::GreaterGreater, None);
+                }
                 else{
                     self.add_token(TokenType::Greater, None);
                 }
@@ -237,22 +335,23 @@ impl Scanner{
                 self.column = 0;
             }
             ' ' | '\r' | '\t' => {}
-            '"' => self.string(),
+            '"' => self.string(false),
+            //r"..." is a raw string, backslashes are kept as written
+            'r' if self.peek() == '"' => {
+                self.advance_char();
+                self.string(true);
+            }
             //following is for all other characters
             _ => {
                 //implement number, letter, and error
                 if scanned_char.is_ascii_digit() {
                     self.number();
                 }
-                else if scanned_char.is_ascii_alphabetic() {
+                else if Scanner::is_alpha(scanned_char) {
                     self.identifier();
                 }
                 else {
-                    self.error = Some(ScannerError{
-                        error: format!("Scanner can not process {}", scanned_char),
-                        line: self.line,
-                        column: self.column,
-                    });
+                    self.error(format!("Scanner can not process {}", scanned_char));
                 }                
             }
 
@@ -264,85 +363,280 @@ impl Scanner{
     fn advance_char(&mut self) -> char {
         self.current += 1;
         self.column += 1;
-        return char::from(self.source[self.current - 1]);
+        return self.source[self.current - 1];
     }
 
     fn is_digit(c: char) -> bool{
         return c.is_ascii_digit();
     }
 
+    //Identifiers follow Unicode's rules loosely: any letter may start one,
+    //and letters, digits or '_' may continue it
     fn is_alpha(c: char) -> bool{
-        return c.is_ascii_alphabetic();
+        return c.is_alphabetic();
     }
 
     fn is_alpha_num(c: char) -> bool{
-        return Scanner::is_digit(c) || Scanner::is_alpha(c);
+        return c.is_alphanumeric() || c == '_';
     }
 
     fn peek(&mut self) -> char{
         if self.is_finished(){
             return '\0';
         }
-        return char::from(self.source[self.current]);
+        return self.source[self.current];
     }
 
     fn peek_next(&mut self) -> char{
         if self.current + 1 >= self.source.len(){
             return '\0';
         }
-        return char::from(self.source[self.current + 1]);
+        return self.source[self.current + 1];
+    }
+
+    fn text(&self, start: usize, end: usize) -> String{
+        return self.source[start..end].iter().collect();
     }
 
     fn add_token(&mut self, add_token_type: TokenType, add_literal: Option<Literal>) -> (){
-        let text: Vec<u8> = self.source[self.start..self.current].to_vec();
+        let text: Vec<u8> = self.text(self.start, self.current).into_bytes();
         self.tokens.push(Token {
             token_type: add_token_type,
             lexeme: text,
             literal: add_literal,
-            line: self.line,
-            column: self.column })
+            line: self.start_line,
+            column: self.start_column })
     }
     
-    fn string (&mut self) -> (){
-        while self.peek() != '"' && !self.is_finished(){
+    //Handles "...", and """...""" which may span lines and drops a newline straight after
+    //the opening quotes. Escapes and "${expr}" are processed unless the string is raw.
+    fn string (&mut self, raw: bool) -> (){
+        let triple: bool = self.peek() == '"' && self.peek_next() == '"';
+        if triple{
+            self.advance_char();
+            self.advance_char();
             if self.peek() == '\n'{
+                self.advance_char();
                 self.line += 1;
                 self.column = 0;
             }
-            self.advance_char();
         }
+        self.string_body(raw, triple, self.start_line, self.start_column);
+    }
+
+    //Scans from just inside the quotes, or just after the '}' ending an interpolated expression.
+    //line and column are where the string's opening quote is.
+    fn string_body (&mut self, raw: bool, triple: bool, line: usize, column: i64) -> (){
+        let mut value: String = String::new();
+        let mut valid: bool = true;
+        loop{
             if self.is_finished(){
-                self.error = Some(ScannerError{
-                    error: format!("Unterminated string"),
-                    line: self.line,
-                    column: self.column,
-                });
+                //A string left open inside "${" is reported once, against the outermost string
+                let (line, column) = match self.interpolations.first(){
+                    Some(&(_, _, outer_line, outer_column)) => (outer_line, outer_column),
+                    None => (line, column)
+                };
+                self.interpolations.clear();
+                self.report(format!("Unterminated string"), line, column);
+                self.add_token(TokenType::Error, None);
                 return;
             }
-            self.advance_char();
-            let value: String = String::from_utf8(self.source[self.start + 1..self.current - 1].to_vec()).unwrap();
+            let current_char: char = self.advance_char();
+            match current_char{
+                '"' if !triple => break,
+                '"' if self.peek() == '"' && self.peek_next() == '"' => {
+                    self.advance_char();
+                    self.advance_char();
+                    break;
+                }
+                '\n' => {
+                    self.line += 1;
+                    self.column = 0;
+                    value.push(current_char);
+                }
+                '\\' if !raw => {
+                    match self.escape(){
+                        Some(escaped) => value.push(escaped),
+                        None => valid = false
+                    }
+                }
+                '$' if !raw && self.peek() == '{' => {
+                    self.advance_char();
+                    self.interpolations.push((0, triple, line, column));
+                    if valid{
+                        self.add_token(TokenType::Interpolation, Some(Literal::String(value)));
+                    }
+                    else{
+                        self.add_token(TokenType::Error, None);
+                    }
+                    return;
+                }
+                _ => value.push(current_char)
+            }
+        }
+        if valid{
             self.add_token(TokenType::String, Some(Literal::String(value)));
+        }
+        else{
+            self.add_token(TokenType::Error, None);
+        }
+    }
+
+    //Called just after a backslash, errors point at the backslash
+    fn escape(&mut self) -> Option<char>{
+        let line: usize = self.line;
+        let column: i64 = self.column;
+        if self.is_finished() || self.peek() == '\n'{
+            self.report(format!("Unfinished escape sequence"), line, column);
+            return None;
+        }
+        let escaped: char = self.advance_char();
+        match escaped{
+            'n' => return Some('\n'),
+            't' => return Some('\t'),
+            'r' => return Some('\r'),
+            '0' => return Some('\0'),
+            '"' => return Some('"'),
+            '\\' => return Some('\\'),
+            '$' => return Some('$'),
+            'u' => {
+                if !self.matches('{'){
+                    self.report(format!("Expected '{{' after '\\u'"), line, column);
+                    return None;
+                }
+                let mut digits: String = String::new();
+                while self.peek().is_ascii_hexdigit(){
+                    digits.push(self.advance_char());
+                }
+                if !self.matches('}'){
+                    self.report(format!("Expected '}}' to close unicode escape"), line, column);
+                    return None;
+                }
+                let code_point: Option<char> = match digits.len(){
+                    1..=6 => char::from_u32(u32::from_str_radix(&digits, 16).unwrap()),
+                    _ => None
+                };
+                if code_point.is_none(){
+                    self.report(format!("Invalid unicode escape '\\u{{{}}}'", digits), line, column);
+                }
+                return code_point;
+            }
+            _ => {
+                self.report(format!("Invalid escape sequence '\\{}'", escaped), line, column);
+                return None;
+            }
+        }
     }
 
+    //Decimal numbers may have a fraction and an exponent, 0x and 0b start hex and binary.
+    //Any of them can use '_' between digits as a separator. Numbers without a fraction
+    //or exponent are integers.
     fn number (&mut self) -> (){
-        while Scanner::is_digit(self.peek()){
+        let first: char = self.source[self.start];
+        if first == '0' && (self.peek() == 'x' || self.peek() == 'X'){
+            self.advance_char();
+            self.radix_number(16, "hexadecimal");
+            return;
+        }
+        if first == '0' && (self.peek() == 'b' || self.peek() == 'B'){
             self.advance_char();
+            self.radix_number(2, "binary");
+            return;
+        }
+        if self.digits(10, true).is_none(){
+            return;
+        }
+        let is_float: bool = (self.peek() == '.' && Scanner::is_digit(self.peek_next())) || self.peek() == 'e' || self.peek() == 'E';
+        if !is_float{
+            self.integer(self.start, 10);
+            return;
         }
         if self.peek() == '.' && Scanner::is_digit(self.peek_next()){
             self.advance_char();
+            if self.digits(10, false).is_none(){
+                return;
+            }
         }
-        while Scanner::is_digit(self.peek()){
+        if self.peek() == 'e' || self.peek() == 'E'{
             self.advance_char();
+            if self.peek() == '+' || self.peek() == '-'{
+                self.advance_char();
+            }
+            match self.digits(10, false){
+                None => return,
+                Some(0) => {
+                    self.error(format!("Expected digits in the exponent of a number"));
+                    return;
+                }
+                Some(_) => ()
+            }
         }
-        let value: f64 = String::from_utf8(self.source[self.start..self.current].to_vec()).unwrap().parse().unwrap();
+        let value: f64 = self.text(self.start, self.current).replace('_', "").parse().unwrap();
         self.add_token(TokenType::Number, Some(Literal::Number(value)));
     }
 
+    fn radix_number (&mut self, radix: u32, name: &str) -> (){
+        let prefix: char = self.source[self.start + 1];
+        match self.digits(radix, false){
+            None => return,
+            Some(0) => {
+                self.error(format!("Expected {} digits after '0{}'", name, prefix));
+                return;
+            }
+            Some(_) => ()
+        }
+        if Scanner::is_alpha_num(self.peek()){
+            let invalid: char = self.advance_char();
+            self.error(format!("Invalid digit '{}' in {} number", invalid, name));
+            return;
+        }
+        self.integer(self.start + 2, radix);
+    }
+
+    fn integer (&mut self, digits_start: usize, radix: u32) -> (){
+        let digits: String = self.text(digits_start, self.current).replace('_', "");
+        match i64::from_str_radix(&digits, radix){
+            Ok(value) => self.add_token(TokenType::Number, Some(Literal::Int(value))),
+            Err(_) => self.token_error(format!("Integer literal is too large"))
+        }
+    }
+
+    //Consumes a run of digits, returning how many there were or None once an error is reported.
+    //follows_digit says whether a digit was consumed just before, so a leading '_' is allowed.
+    fn digits (&mut self, radix: u32, follows_digit: bool) -> Option<usize>{
+        let mut count: usize = 0;
+        let mut after_digit: bool = follows_digit;
+        loop{
+            let next: char = self.peek();
+            if next.is_digit(radix){
+                self.advance_char();
+                count += 1;
+                after_digit = true;
+            }
+            else if next == '_'{
+                self.advance_char();
+                if !after_digit{
+                    self.error(format!("Digit separator '_' must come between digits"));
+                    return None;
+                }
+                after_digit = false;
+            }
+            else{
+                break;
+            }
+        }
+        if !after_digit && (count > 0 || follows_digit){
+            self.error(format!("Digit separator '_' must come between digits"));
+            return None;
+        }
+        return Some(count);
+    }
+
     fn identifier (&mut self) -> (){
         while Scanner::is_alpha_num(self.peek()){
             self.advance_char();
         }
-        let value: String = String::from_utf8(self.source[self.start..self.current].to_vec()).unwrap();
+        let value: String = self.text(self.start, self.current);
         let token_type: TokenType = match self.keywords.get(&value){
             Some(key_token_type) => *key_token_type,
             None => TokenType::Identifier,
@@ -368,24 +662,19 @@ impl Scanner{
 
     fn discard_block_comment(&mut self) -> (){
         while !self.is_finished(){
-            if self.peek() == '\n'{
+            let current_char: char = self.advance_char();
+            if current_char == '\n'{
                 self.line += 1;
-                self.column = 0; 
+                self.column = 0;
             }
-            let current_char: char = self.advance_char();
             if current_char == '*'{
                 if self.peek() == '/'{
                     self.advance_char();
-                    self.column += 1;
                     return;
                 }
             }
         }
-        self.error = Some(ScannerError{
-            error: format!("Unclosed block comment"),
-            line: self.line,
-            column: self.column,
-        })
+        self.token_error(format!("Unclosed block comment"));
     }
     
     fn matches(&mut self, expected_char: char) -> bool{
@@ -404,8 +693,24 @@ impl Scanner{
         return self.current >= self.source.len();
     }
 
-    fn is_done_with_error(&self) -> bool{
-        return self.is_finished() || self.error.is_some();
+    //Records the error and leaves an Error token covering the offending text in its place
+    fn error(&mut self, message: String) -> (){
+        self.report(message, self.line, self.column);
+        self.add_token(TokenType::Error, None);
+    }
+
+    //Like error, but for problems with the token as a whole so it points at where the token starts
+    fn token_error(&mut self, message: String) -> (){
+        self.report(message, self.start_line, self.start_column);
+        self.add_token(TokenType::Error, None);
+    }
+
+    fn report(&mut self, message: String, line: usize, column: i64) -> (){
+        self.errors.push(ScannerError{
+            error: message,
+            line: line,
+            column: column,
+        });
     }
 }
 
@@ -415,7 +720,7 @@ pub(crate) fn run_file(file_path: String) -> (){
         Ok(file_string) => file_string,
         Err(error) => panic!("Problem opening the file: {error:?}")
     };
-    run(file_contents);
+    run(&file_path, file_contents, false);
 }
 
 pub(crate) fn run_prompt() ->(){
@@ -425,13 +730,35 @@ pub(crate) fn run_prompt() ->(){
         if line.trim().is_empty(){
             break;
         }
-        run(line)
+        run("<stdin>", line, false)
+    }
+}
+
+//Tree-walked Lox calls recurse on the Rust stack, so programs run on a thread with room
+//for interpreter::DEFAULT_MAX_CALL_DEPTH of them even in a debug build
+const RUN_STACK_SIZE: usize = 256 * 1024 * 1024;
+
+pub(crate) fn run(file_name: &str, source: String, use_vm: bool) ->(){
+    let file_name: String = file_name.to_string();
+    let runner = thread::Builder::new()
+        .stack_size(RUN_STACK_SIZE)
+        .spawn(move || run_program(&file_name, source, use_vm))
+        .expect("Could not start the interpreter thread");
+    if let Err(panic) = runner.join(){
+        panic::resume_unwind(panic);
     }
 }
 
-pub(crate) fn run(source: String) ->(){
+fn run_program(file_name: &str, source: String, use_vm: bool) ->(){
     let mut scanner: Scanner = Scanner::default();
-    let tokens: Vec<Token> = scanner.scan_tokens(source);
+    let tokens: Vec<Token> = match scanner.scan_tokens(source.clone()){
+        Ok(tokens) => tokens,
+        Err(errors) => {
+            let errors: Vec<Diagnostic> = errors.iter().map(|err| err.diagnostic()).collect();
+            diagnostics::report(&errors, file_name, &source);
+            return ();
+        }
+    };
     // for tok in tokens.clone(){
     //     println!("{}", String::from_utf8(tok.lexeme).unwrap());
     // }
@@ -442,23 +769,29 @@ pub(crate) fn run(source: String) ->(){
             let mut resolver = Resolver::new(interpreter);
             let resolved = resolver.resolve(stmt.clone()); 
             match resolved.0{
+                Ok(_good) if use_vm => {
+                    let script = compiler::compile(&stmt);
+                    let result = match script{
+                        Ok(script) => vm::interpret(script).map_err(|err| err.diagnostic()),
+                        Err(err) => Err(err.diagnostic())
+                    };
+                    if let Err(err) = result{
+                        diagnostics::report(&[err], file_name, &source);
+                    }
+                }
                 Ok(_good) => {
                     //println!("Made through resolving");
                     let mut inter = resolved.1.clone();
                     let interp = inter.interpret(stmt);
                     match interp{
                         Ok(()) => return (),
-                        Err(err) => println!("{}\n", err.return_error())
-                    }
-                }
-                Err(err) => {
-                    for str in err{
-                        println!("{}", str);
+                        Err(err) => diagnostics::report(&[err.diagnostic()], file_name, &source)
                     }
                 }
+                Err(_) => diagnostics::report(&resolver.diagnostics(), file_name, &source)
             }
         },
-        Err(err) => println!("{}\n", err.return_error())
+        Err(err) => diagnostics::report(&err.diagnostics(), file_name, &source)
     }
 
     //for token in tokens.clone(){
@@ -466,11 +799,6 @@ pub(crate) fn run(source: String) ->(){
     //}
 }
 
-pub fn print_error(scanner_error: ScannerError) ->(){
-    println!("Error Occurred: {} at line {}, column {}", 
-    scanner_error.error, scanner_error.line, scanner_error.column);
-}
-
 // Returns an iterator to the read of the lines of the file. Output is wrapped in a Result to allow matching on errors
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
 where P: AsRef<Path>, {
@@ -493,14 +821,14 @@ mod tests{
     #[test]
     fn print_token(){
         let tokens = "/ and *".to_string();
-        run(tokens);
+        run("<test>", tokens, false);
     }
 
     #[test]
     fn scan_single_character_tokens() {
         let source = "( ) { } [ ] , . - + ; : * %".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
 
         let expected_tokens = vec![
             TokenType::LeftParen, TokenType::RightParen,
@@ -521,7 +849,7 @@ mod tests{
     fn scan_two_character_tokens() {
         let source = "!= == >= <=".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
 
         let expected_tokens = vec![
             TokenType::BangEqual, TokenType::EqualEqual,
@@ -533,23 +861,48 @@ mod tests{
         assert_eq!(expected_tokens, actual_tokens);
     }
 
+    #[test]
+    fn scan_bitwise_tokens() {
+        let source = "& | ^ ~ ~/ << >> ** * <= >=".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+
+        let expected_tokens = vec![
+            TokenType::Ampersand, TokenType::Pipe, TokenType::Caret,
+            TokenType::Tilde, TokenType::TildeSlash,
+            TokenType::LessLess, TokenType::GreaterGreater,
+            TokenType::StarStar, TokenType::Star,
+            TokenType::LessEqual, TokenType::GreaterEqual,
+            TokenType::Eof
+        ];
+
+        let actual_tokens: Vec<TokenType> = tokens.into_iter().map(|t| t.token_type).collect();
+        assert_eq!(expected_tokens, actual_tokens);
+    }
+
     #[test]
     fn scan_numbers() {
-        let source = "123 45.67".to_string();
+        let source = "123 45.67 0xFF 0b1010 1e-9 6.02e23 1_000_000".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
 
         assert_eq!(tokens[0].token_type, TokenType::Number);
-        assert_eq!(tokens[0].literal, Some(Literal::Number(123.0)));
+        assert_eq!(tokens[0].literal, Some(Literal::Int(123)));
         assert_eq!(tokens[1].token_type, TokenType::Number);
         assert_eq!(tokens[1].literal, Some(Literal::Number(45.67)));
+        assert_eq!(tokens[2].literal, Some(Literal::Int(255)));
+        assert_eq!(tokens[3].literal, Some(Literal::Int(10)));
+        assert_eq!(tokens[4].literal, Some(Literal::Number(1e-9)));
+        assert_eq!(tokens[5].literal, Some(Literal::Number(6.02e23)));
+        assert_eq!(tokens[6].literal, Some(Literal::Int(1_000_000)));
+        assert_eq!(tokens[7].token_type, TokenType::Eof);
     }
 
     #[test]
     fn scan_string_literal() {
         let source = "\"hello world\"".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
 
         assert_eq!(tokens[0].token_type, TokenType::String);
         assert_eq!(tokens[0].literal, Some(Literal::String("hello world".to_string())));
@@ -559,7 +912,7 @@ mod tests{
     fn scan_keywords() {
         let source = "class var fun".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
 
         let expected_tokens = vec![
             TokenType::Class, TokenType::Var, TokenType::Fun,
@@ -569,52 +922,67 @@ mod tests{
         let actual_tokens: Vec<TokenType> = tokens.into_iter().map(|t| t.token_type).collect();
         assert_eq!(expected_tokens, actual_tokens);
     }
+
+    #[test]
+    fn scan_loop_keywords() {
+        let source = "break continue breaking".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+
+        let expected_tokens = vec![
+            TokenType::Break, TokenType::Continue, TokenType::Identifier,
+            TokenType::Eof
+        ];
+
+        let actual_tokens: Vec<TokenType> = tokens.into_iter().map(|t| t.token_type).collect();
+        assert_eq!(expected_tokens, actual_tokens);
+    }
     
     #[test]
     fn unterminated_string_error() {
         let source = "\"hello world".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let errors = scanner.scan_tokens(source).unwrap_err();
 
-        assert!(scanner.error.is_some());
-        assert_eq!(scanner.error.unwrap().error, "Unterminated string");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].error, "Unterminated string");
     }
 
     #[test]
     fn skip_single_line_comment() {
         let source = "// this is a comment\n123".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
 
         assert_eq!(tokens[0].token_type, TokenType::Number);
-        assert_eq!(tokens[0].literal, Some(Literal::Number(123.0)));
+        assert_eq!(tokens[0].literal, Some(Literal::Int(123)));
     }
 
     #[test]
     fn skip_block_comment() {
         let source = "/* this is a block comment */123".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
 
         assert_eq!(tokens[0].token_type, TokenType::Number);
-        assert_eq!(tokens[0].literal, Some(Literal::Number(123.0)));
+        assert_eq!(tokens[0].literal, Some(Literal::Int(123)));
     }
 
     #[test]
     fn unterminated_block_comment_error() {
         let source = "/* unclosed comment".to_string();
         let mut scanner = Scanner::default();
-        scanner.scan_tokens(source);
+        let errors = scanner.scan_tokens(source).unwrap_err();
 
-        assert!(scanner.error.is_some());
-        assert_eq!(scanner.error.unwrap().error, "Unclosed block comment");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].error, "Unclosed block comment");
     }
 
     #[test]
     fn scan_mixed_tokens() {
         let source = "123 + variable * 4.56".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
 
         let expected_tokens = vec![
             TokenType::Number, TokenType::Plus,
@@ -630,7 +998,7 @@ mod tests{
     fn scan_empty_source() {
         let source = "".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
 
         assert_eq!(tokens.len(), 1); 
         assert_eq!(tokens[0].token_type, TokenType::Eof);
@@ -640,7 +1008,7 @@ mod tests{
     fn scan_with_whitespace() {
         let source = "( )   \t\n{ }\n\t[ ] \r\n ,\t. - + ; : * %\n".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
     
         let expected_tokens = vec![
             TokenType::LeftParen, TokenType::RightParen,
@@ -661,22 +1029,22 @@ mod tests{
     fn test_advance_char() {
         let source = "abc".to_string();
         let mut scanner = Scanner::default();
-        scanner.source = source.into_bytes();
+        scanner.source = source.chars().collect();
     
         let first_char = scanner.advance_char();
         assert_eq!(first_char, 'a');
         assert_eq!(scanner.current, 1);
-        assert_eq!(scanner.column, 0);
+        assert_eq!(scanner.column, 1);
 
         let second_char = scanner.advance_char();
         assert_eq!(second_char, 'b');
         assert_eq!(scanner.current, 2);
-        assert_eq!(scanner.column, 1);
+        assert_eq!(scanner.column, 2);
 
         let third_char = scanner.advance_char();
         assert_eq!(third_char, 'c');
         assert_eq!(scanner.current, 3);
-        assert_eq!(scanner.column, 2);
+        assert_eq!(scanner.column, 3);
     }
     
     #[test]
@@ -685,8 +1053,9 @@ mod tests{
         scanner.start = 0;
         scanner.current = 3;
         scanner.line = 1;
-        scanner.column = 2;
-        scanner.source = "abc".to_string().into_bytes();
+        scanner.column = 3;
+        scanner.start_column = 1;
+        scanner.source = "abc".chars().collect();
     
         scanner.add_token(TokenType::Identifier, Some(Literal::Identifier("abc".to_string())));
     
@@ -696,34 +1065,34 @@ mod tests{
         assert_eq!(token.lexeme, b"abc".to_vec());
         assert_eq!(token.literal, Some(Literal::Identifier("abc".to_string())));
         assert_eq!(token.line, 1);
-        assert_eq!(token.column, 2);
+        assert_eq!(token.column, 1);
     }
 
     #[test]
     fn test_matches() {
         let source = "== !=".to_string();
         let mut scanner = Scanner::default();
-        scanner.source = source.into_bytes();
+        scanner.source = source.chars().collect();
     
         scanner.advance_char();
         let is_match = scanner.matches('=');
         assert!(is_match);
         assert_eq!(scanner.current, 2);
-        assert_eq!(scanner.column, 1);
+        assert_eq!(scanner.column, 2);
     
         scanner.advance_char(); 
         scanner.advance_char(); 
         let is_match = scanner.matches('=');
         assert!(is_match);
         assert_eq!(scanner.current, 5);
-        assert_eq!(scanner.column, 4);
+        assert_eq!(scanner.column, 5);
     }
 
     #[test]
     fn test_discard_comment() {
         let source = "// this is a comment\n123".to_string();
         let mut scanner = Scanner::default();
-        scanner.source = source.into_bytes();
+        scanner.source = source.chars().collect();
 
         scanner.advance_char();
         scanner.advance_char(); 
@@ -741,7 +1110,7 @@ mod tests{
     fn test_discard_block_comment() {
         let source = "/* this is a block comment */123".to_string();
         let mut scanner = Scanner::default();
-        scanner.source = source.into_bytes();
+        scanner.source = source.chars().collect();
     
         scanner.advance_char(); 
         scanner.advance_char(); 
@@ -759,19 +1128,40 @@ mod tests{
     fn test_invalid_character_error() {
         let source = "@".to_string(); 
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let errors = scanner.scan_tokens(source).unwrap_err();
     
-        assert!(scanner.error.is_some());
-        assert_eq!(scanner.error.unwrap().error, "Scanner can not process @");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].error, "Scanner can not process @");
         
-        assert!(tokens.is_empty());
+        let token_types: Vec<TokenType> = scanner.tokens.iter().map(|t| t.token_type).collect();
+        assert_eq!(token_types, vec![TokenType::Error, TokenType::Eof]);
+    }
+
+    #[test]
+    fn test_reports_every_error() {
+        let source = "var a = @;\nprint #1;\n\"open".to_string();
+        let mut scanner = Scanner::default();
+        let errors = scanner.scan_tokens(source).unwrap_err();
+
+        let messages: Vec<String> = errors.iter().map(|err| err.error.clone()).collect();
+        assert_eq!(messages, vec!["Scanner can not process @", "Scanner can not process #", "Unterminated string"]);
+        let lines: Vec<usize> = errors.iter().map(|err| err.line).collect();
+        assert_eq!(lines, vec![1, 2, 3]);
+
+        //Scanning carried on past each error
+        let token_types: Vec<TokenType> = scanner.tokens.iter().map(|t| t.token_type).collect();
+        assert_eq!(token_types, vec![
+            TokenType::Var, TokenType::Identifier, TokenType::Equal, TokenType::Error, TokenType::Semicolon,
+            TokenType::Print, TokenType::Error, TokenType::Number, TokenType::Semicolon,
+            TokenType::Error, TokenType::Eof
+        ]);
     }
 
     #[test]
     fn test_identifier_edge_cases() {
         let source = "var1 varWithNumbers123 var123AndSymbols".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
     
         assert_eq!(tokens.len(), 4);
     
@@ -791,7 +1181,7 @@ mod tests{
     fn test_whitespace_and_mixed_whitespace() {
         let source = "   \t\nvar1  \tvar2\n   var3\t\n".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
     
         assert_eq!(tokens.len(), 4); 
     
@@ -811,7 +1201,7 @@ mod tests{
     fn test_keyword_identifier_edge_cases() {
         let source = "class var variableClass className fun functionName".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
     
         assert_eq!(tokens.len(), 7); 
     
@@ -832,26 +1222,155 @@ mod tests{
 
     #[test]
     fn test_string_escapes() {
-        let source = "\"hello\nworld\" \"escape\\sequence\"".to_string();
+        let source = "\"hello\nworld\" \"escape\\\\sequence\" \"a\\tb\\nc\\\"d\\\"\\u{1F600}\"".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
     
-        assert_eq!(tokens.len(), 3); 
+        assert_eq!(tokens.len(), 4); 
     
         assert_eq!(tokens[0].token_type, TokenType::String);
         assert_eq!(tokens[0].literal, Some(Literal::String("hello\nworld".to_string())));
     
         assert_eq!(tokens[1].token_type, TokenType::String);
         assert_eq!(tokens[1].literal, Some(Literal::String("escape\\sequence".to_string())));
+
+        assert_eq!(tokens[2].literal, Some(Literal::String("a\tb\nc\"d\"😀".to_string())));
     
-        assert_eq!(tokens[2].token_type, TokenType::Eof);
+        assert_eq!(tokens[3].token_type, TokenType::Eof);
+    }
+
+    #[test]
+    fn test_invalid_escapes() {
+        let source = "\"ok\" \"bad\\q\";\n  \"\\u{D800}\" \"\\u{}\" \"\\u41\"".to_string();
+        let mut scanner = Scanner::default();
+        let errors = scanner.scan_tokens(source).unwrap_err();
+
+        let reported: Vec<(String, usize, i64)> = errors.into_iter().map(|err| (err.error, err.line, err.column)).collect();
+        assert_eq!(reported, vec![
+            ("Invalid escape sequence '\\q'".to_string(), 1, 10),
+            ("Invalid unicode escape '\\u{D800}'".to_string(), 2, 4),
+            ("Invalid unicode escape '\\u{}'".to_string(), 2, 15),
+            ("Expected '{' after '\\u'".to_string(), 2, 22),
+        ]);
+        //The bad strings become error tokens and scanning carries on after them
+        let token_types: Vec<TokenType> = scanner.tokens.iter().map(|t| t.token_type).collect();
+        assert_eq!(token_types, vec![
+            TokenType::String, TokenType::Error, TokenType::Semicolon,
+            TokenType::Error, TokenType::Error, TokenType::Error, TokenType::Eof
+        ]);
+    }
+
+    #[test]
+    fn test_raw_and_triple_quoted_strings() {
+        let source = "r\"C:\\new\\table\" \"\"\"\nSELECT *\n  FROM \"t\"\\n\"\"\" r\"\"\"a\\n\"b\"\"\" \"\" x".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+
+        let literals: Vec<Option<Literal>> = tokens.iter().map(|t| t.literal.clone()).collect();
+        assert_eq!(literals, vec![
+            Some(Literal::String("C:\\new\\table".to_string())),
+            Some(Literal::String("SELECT *\n  FROM \"t\"\n".to_string())),
+            Some(Literal::String("a\\n\"b".to_string())),
+            Some(Literal::String("".to_string())),
+            Some(Literal::Identifier("x".to_string())),
+            None
+        ]);
+        assert_eq!(tokens[4].line, 3);
+    }
+
+    #[test]
+    fn test_unicode_strings_and_comments() {
+        let source = "// héllo wörld\n\"naïve café 😀\" /* ∑ */ 1".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[0].literal, Some(Literal::String("naïve café 😀".to_string())));
+        assert_eq!(tokens[0].lexeme, "\"naïve café 😀\"".as_bytes().to_vec());
+        assert_eq!(tokens[1].token_type, TokenType::Number);
+    }
+
+    #[test]
+    fn test_unicode_identifiers() {
+        let source = "var größe_2 = π;".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+
+        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(tokens[1].literal, Some(Literal::Identifier("größe_2".to_string())));
+        assert_eq!(tokens[3].literal, Some(Literal::Identifier("π".to_string())));
+    }
+
+    #[test]
+    fn test_columns_count_characters() {
+        let source = "\"ü€😀\" + x;\n\"ü€😀\" @".to_string();
+        let mut scanner = Scanner::default();
+        let errors = scanner.scan_tokens(source).unwrap_err();
+
+        //Each multi-byte character counts as one column
+        assert_eq!(scanner.tokens[0].column, 1);
+        assert_eq!(scanner.tokens[1].column, 7);
+        assert_eq!(scanner.tokens[2].column, 9);
+        assert_eq!((errors[0].line, errors[0].column), (2, 7));
+    }
+
+    #[test]
+    fn test_tokens_record_start_column() {
+        let source = "var total >= 10;
+  print \"\"\"a\nb\"\"\" != 0x1F;".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+
+        //Every line counts from 1 and a token sits at its first character
+        let positions: Vec<(usize, i64)> = tokens.iter().map(|t| (t.line, t.column)).collect();
+        assert_eq!(positions, vec![
+            (1, 1), (1, 5), (1, 11), (1, 14), (1, 16),
+            (2, 3), (2, 9), (3, 6), (3, 9), (3, 13), (3, 14)
+        ]);
+    }
+
+    #[test]
+    fn test_string_interpolation_tokens() {
+        let source = "\"a${x + {}}b${ \"c${y}\" }d\\${e}\" }".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+
+        let token_types: Vec<TokenType> = tokens.iter().map(|t| t.token_type).collect();
+        assert_eq!(token_types, vec![
+            TokenType::Interpolation, TokenType::Identifier, TokenType::Plus, TokenType::LeftBrace, TokenType::RightBrace, TokenType::InterpolationEnd,
+            TokenType::Interpolation, TokenType::Interpolation, TokenType::Identifier, TokenType::InterpolationEnd, TokenType::String, TokenType::InterpolationEnd,
+            TokenType::String, TokenType::RightBrace, TokenType::Eof
+        ]);
+        assert_eq!(tokens[0].literal, Some(Literal::String("a".to_string())));
+        assert_eq!(tokens[6].literal, Some(Literal::String("b".to_string())));
+        assert_eq!(tokens[12].literal, Some(Literal::String("d${e}".to_string())));
+        //The closing '}' is its own token and the text after it starts just past it
+        assert_eq!((tokens[5].lexeme.clone(), tokens[5].column, tokens[6].column), (b"}".to_vec(), 11, 12));
+    }
+
+    #[test]
+    fn test_unterminated_interpolation() {
+        //Each case is reported once, at the opening quote of the string left open
+        let cases = vec![
+            ("\"a${x", (1, 1)),
+            ("print \"${\";", (1, 7)),
+            ("var s = 1;\n  \"a${x}b", (2, 3)),
+            ("print \"a${ \"b${1}\" }c${\n\"d", (1, 7)),
+        ];
+        for (source, position) in cases{
+            let mut scanner = Scanner::default();
+            let errors = scanner.scan_tokens(source.to_string()).unwrap_err();
+
+            let reported: Vec<(String, usize, i64)> = errors.into_iter().map(|err| (err.error, err.line, err.column)).collect();
+            assert_eq!(reported, vec![("Unterminated string".to_string(), position.0, position.1)], "{}", source);
+        }
     }
 
     #[test]
     fn test_incomplete_tokens() {
         let source = "! = < >!".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
     
         assert_eq!(tokens.len(), 6);
     
@@ -867,15 +1386,15 @@ mod tests{
     fn test_number_edge_cases() {
         let source = "0 123 45.67 0.001 123.0".to_string();
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
     
         assert_eq!(tokens.len(), 6); 
     
         assert_eq!(tokens[0].token_type, TokenType::Number);
-        assert_eq!(tokens[0].literal, Some(Literal::Number(0.0)));
+        assert_eq!(tokens[0].literal, Some(Literal::Int(0)));
     
         assert_eq!(tokens[1].token_type, TokenType::Number);
-        assert_eq!(tokens[1].literal, Some(Literal::Number(123.0)));
+        assert_eq!(tokens[1].literal, Some(Literal::Int(123)));
     
         assert_eq!(tokens[2].token_type, TokenType::Number);
         assert_eq!(tokens[2].literal, Some(Literal::Number(45.67)));
@@ -887,13 +1406,59 @@ mod tests{
         assert_eq!(tokens[4].literal, Some(Literal::Number(123.0)));
     
         assert_eq!(tokens[5].token_type, TokenType::Eof);
+
+        let source = "0X1f_FF 0B1_0 0x0 0b0 1E3 2.5e+2 1_0.0_5 0x7FFFFFFFFFFFFFFF 1.e5".to_string();
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source).unwrap();
+        let literals: Vec<Option<Literal>> = tokens.into_iter().map(|t| t.literal).collect();
+
+        assert_eq!(literals, vec![
+            Some(Literal::Int(8191)),
+            Some(Literal::Int(2)),
+            Some(Literal::Int(0)),
+            Some(Literal::Int(0)),
+            Some(Literal::Number(1000.0)),
+            Some(Literal::Number(250.0)),
+            Some(Literal::Number(10.05)),
+            Some(Literal::Int(i64::MAX)),
+            //A '.' needs a digit after it to be part of the number
+            Some(Literal::Int(1)),
+            None,
+            Some(Literal::Identifier("e5".to_string())),
+            None
+        ]);
+    }
+
+    #[test]
+    fn test_malformed_numbers() {
+        let source = "0x;\n0b;\n1e;\n2e+;\n0b102;\n0xFG;\n1__0;\n1_;\n0x_1;\n0xFFFFFFFFFFFFFFFF;\n9223372036854775808;".to_string();
+        let mut scanner = Scanner::default();
+        let errors = scanner.scan_tokens(source).unwrap_err();
+
+        let reported: Vec<(String, usize, i64)> = errors.into_iter().map(|err| (err.error, err.line, err.column)).collect();
+        assert_eq!(reported, vec![
+            ("Expected hexadecimal digits after '0x'".to_string(), 1, 2),
+            ("Expected binary digits after '0b'".to_string(), 2, 2),
+            ("Expected digits in the exponent of a number".to_string(), 3, 2),
+            ("Expected digits in the exponent of a number".to_string(), 4, 3),
+            ("Invalid digit '2' in binary number".to_string(), 5, 5),
+            ("Invalid digit 'G' in hexadecimal number".to_string(), 6, 4),
+            ("Digit separator '_' must come between digits".to_string(), 7, 3),
+            ("Digit separator '_' must come between digits".to_string(), 8, 2),
+            ("Digit separator '_' must come between digits".to_string(), 9, 3),
+            ("Integer literal is too large".to_string(), 10, 1),
+            ("Integer literal is too large".to_string(), 11, 1),
+        ]);
+        //Each bad literal becomes one error token and the ';' after it still scans
+        let token_types: Vec<TokenType> = scanner.tokens.iter().map(|t| t.token_type).collect();
+        assert_eq!(token_types.iter().filter(|t| **t == TokenType::Semicolon).count(), 11);
     }
 
     #[test]
     fn test_boundary_with_extremely_long_input() {
         let source = "a".repeat(1_000_000); 
         let mut scanner = Scanner::default();
-        let tokens = scanner.scan_tokens(source);
+        let tokens = scanner.scan_tokens(source).unwrap();
     
         assert_eq!(tokens.len(), 2); 
     
@@ -906,13 +1471,14 @@ mod tests{
     fn test_unterminated_block_comment_with_new_lines() {
         let source = "/* This is an unterminated block comment\n with multiple lines\n and more text".to_string();
         let mut scanner = Scanner::default();
-        scanner.source = source.clone().into_bytes();
-        scanner.scan_tokens(source);
+        scanner.source = source.chars().collect();
+        let errors = scanner.scan_tokens(source).unwrap_err();
     
-        assert!(scanner.error.is_some());
-        assert_eq!(scanner.error.unwrap().error, "Unclosed block comment");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].error, "Unclosed block comment");
     
-        assert_eq!(scanner.tokens.len(), 0);
+        let token_types: Vec<TokenType> = scanner.tokens.iter().map(|t| t.token_type).collect();
+        assert_eq!(token_types, vec![TokenType::Error, TokenType::Eof]);
     }
     
 }
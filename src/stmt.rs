@@ -25,10 +25,19 @@ pub enum Stmt {
     },
     While{
         condition: Expr,
-        body: Box<Stmt>
+        body: Box<Stmt>,
+        increment: Option<Expr>
+    },
+    Break{
+        keyword: Token
+    },
+    Continue{
+        keyword: Token
     },
     Function{
         name: String,
+        line: usize,
+        column: i64,
         parameters: Vec<Token>,
         body: Box<Vec<Stmt>>
     },
@@ -40,5 +49,17 @@ pub enum Stmt {
         name: String,
         superclass: Option<Expr>,
         methods: Box<Vec<Stmt>>
+    },
+    Throw{
+        keyword: Token,
+        value: Expr
+    },
+    //catch_name and catch_body are both set or both None, the parser requires a catch or a finally
+    Try{
+        keyword: Token,
+        body: Vec<Stmt>,
+        catch_name: Option<Token>,
+        catch_body: Option<Vec<Stmt>>,
+        finally_body: Option<Vec<Stmt>>
     }
 }
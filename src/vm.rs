@@ -0,0 +1,1080 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::chunk::*;
+use crate::diagnostics::{Diagnostic, DiagnosticKind};
+use crate::expr::{BinaryOpType, UnaryOpType};
+use crate::interpreter::{ErrorKind, InterpreterConfig, Value};
+
+#[derive(Debug, Clone)]
+pub enum VmValue{
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Int(i64),
+    String(Rc<str>),
+    Closure(Rc<Closure>),
+    Native(Rc<NativeFunction>),
+    Class(Rc<Class>),
+    Instance(Rc<Instance>),
+    BoundMethod(Rc<BoundMethod>),
+    List(Rc<RefCell<Vec<VmValue>>>),
+    Map(Rc<RefCell<VmMap>>)
+}
+
+impl VmValue{
+    //Mirrors Value::value_to_string so both backends print the same thing
+    pub fn to_string(&self) -> String{
+        match self{
+            VmValue::Nil => format!("nil"),
+            VmValue::Bool(bool) => format!("{}", bool),
+            VmValue::Number(num) => format!("{}", num),
+            VmValue::Int(num) => format!("{}", num),
+            VmValue::String(str) => format!("{}", str),
+            VmValue::Closure(closure) => format!("{}", closure.function.name),
+            VmValue::Native(native) => format!("{}", native.name),
+            VmValue::Class(class) => format!("{}", class.name),
+            VmValue::Instance(instance) => format!("{} instance", instance.class.name),
+            VmValue::BoundMethod(bound) => format!("{}", bound.method.function.name),
+            VmValue::List(list) => {
+                let elements: Vec<String> = list.borrow().iter().map(|element| element.to_string()).collect();
+                format!("[{}]", elements.join(", "))
+            }
+            VmValue::Map(map) => {
+                let entries: Vec<String> = map.borrow().entries.iter()
+                .map(|(key, value)| format!("{}: {}", key.to_string(), value.to_string()))
+                .collect();
+                format!("{{{}}}", entries.join(", "))
+            }
+        }
+    }
+
+    //Same names Type::type_to_string uses
+    pub fn type_name(&self) -> String{
+        match self{
+            VmValue::Nil => format!("Nil"),
+            VmValue::Bool(_) => format!("Bool"),
+            VmValue::Number(_) => format!("Number"),
+            VmValue::Int(_) => format!("Int"),
+            VmValue::String(_) => format!("String"),
+            VmValue::Closure(_) | VmValue::BoundMethod(_) => format!("User Defined Function"),
+            VmValue::Native(_) => format!("Native Function"),
+            VmValue::Class(_) => format!("User Defined Class"),
+            VmValue::Instance(_) => format!("User Defined Class Instance"),
+            VmValue::List(_) => format!("List"),
+            VmValue::Map(_) => format!("Map")
+        }
+    }
+
+    fn is_truthy(&self) -> bool{
+        match self{
+            VmValue::Nil => false,
+            VmValue::Bool(bool) => *bool,
+            _ => true
+        }
+    }
+
+    fn is_equal(&self, other: &VmValue) -> bool{
+        match (self, other){
+            (VmValue::Nil, VmValue::Nil) => true,
+            (VmValue::Number(num1), VmValue::Number(num2)) => num1 == num2,
+            (VmValue::Int(num1), VmValue::Int(num2)) => num1 == num2,
+            (VmValue::Int(num1), VmValue::Number(num2)) | (VmValue::Number(num2), VmValue::Int(num1)) => Value::int_equals_number(*num1, *num2),
+            (VmValue::String(str1), VmValue::String(str2)) => str1 == str2,
+            (VmValue::Bool(bool1), VmValue::Bool(bool2)) => bool1 == bool2,
+            (VmValue::List(list1), VmValue::List(list2)) => Rc::ptr_eq(list1, list2),
+            (VmValue::Map(map1), VmValue::Map(map2)) => Rc::ptr_eq(map1, map2),
+            (_, _) => false
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Upvalue{
+    //Still living in a stack slot of an active frame
+    Open(usize),
+    Closed(VmValue)
+}
+
+#[derive(Debug)]
+pub struct Closure{
+    pub function: Rc<Function>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>
+}
+
+#[derive(Debug)]
+pub struct NativeFunction{
+    pub name: String,
+    pub arity: usize,
+    pub callable: fn(&[VmValue]) -> Result<VmValue, String>
+}
+
+#[derive(Debug)]
+pub struct Class{
+    pub name: String,
+    pub methods: RefCell<HashMap<Rc<str>, Rc<Closure>>>
+}
+
+#[derive(Debug)]
+pub struct Instance{
+    pub class: Rc<Class>,
+    pub fields: RefCell<HashMap<Rc<str>, VmValue>>
+}
+
+#[derive(Debug)]
+pub struct BoundMethod{
+    pub receiver: VmValue,
+    pub method: Rc<Closure>
+}
+
+//Insertion ordered like LoxMap, keys follow the same rules as MapKey
+#[derive(Debug, Default)]
+pub struct VmMap{
+    entries: Vec<(VmValue, VmValue)>
+}
+
+impl VmMap{
+    fn check_key(key: &VmValue) -> Result<(), String>{
+        match key{
+            VmValue::Number(_) | VmValue::Int(_) | VmValue::String(_) | VmValue::Bool(_) | VmValue::Nil | VmValue::Instance(_) => Ok(()),
+            other => Err(format!("Cannot use a value of type {} as a map key", other.type_name()))
+        }
+    }
+
+    fn same_key(key1: &VmValue, key2: &VmValue) -> bool{
+        match (key1, key2){
+            (VmValue::Number(num1), VmValue::Number(num2)) => num1 == num2 || (num1.is_nan() && num2.is_nan()),
+            (VmValue::Instance(inst1), VmValue::Instance(inst2)) => Rc::ptr_eq(inst1, inst2),
+            (_, _) => key1.is_equal(key2)
+        }
+    }
+
+    fn position(&self, key: &VmValue) -> Result<Option<usize>, String>{
+        VmMap::check_key(key)?;
+        return Ok(self.entries.iter().position(|(existing, _)| VmMap::same_key(existing, key)));
+    }
+
+    pub fn get(&self, key: &VmValue) -> Result<Option<VmValue>, String>{
+        return Ok(self.position(key)?.map(|position| self.entries[position].1.clone()));
+    }
+
+    pub fn insert(&mut self, key: VmValue, value: VmValue) -> Result<(), String>{
+        match self.position(&key)?{
+            Some(position) => self.entries[position].1 = value,
+            None => self.entries.push((key, value))
+        }
+        return Ok(());
+    }
+
+    pub fn remove(&mut self, key: &VmValue) -> Result<Option<VmValue>, String>{
+        return Ok(self.position(key)?.map(|position| self.entries.remove(position).1));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmError{
+    message: String,
+    line: usize,
+    column: i64,
+    kind: ErrorKind
+}
+
+impl VmError{
+    //Raised by the config's limits between instructions, like InterpreterError::aborted
+    fn aborted(kind: ErrorKind, message: String) -> Self{
+        return VmError {
+            message: message,
+            line: 0,
+            column: 0,
+            kind: kind
+        };
+    }
+
+    pub fn kind(&self) -> ErrorKind{
+        return self.kind;
+    }
+
+    pub fn return_error(&self) -> String{
+        return self.message.clone();
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic{
+        return Diagnostic::new(DiagnosticKind::Runtime, self.message.clone(), self.line, self.column);
+    }
+}
+
+struct CallFrame{
+    closure: Rc<Closure>,
+    ip: usize,
+    //stack index of the callee, its arguments and locals sit above it
+    base: usize
+}
+
+pub struct Vm{
+    stack: Vec<VmValue>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<Rc<str>, VmValue>,
+    //sorted by stack slot so closing a frame only looks at the tail
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    //The same limits the tree-walker runs under, here a step is one instruction
+    pub config: InterpreterConfig,
+    pub steps: u64
+}
+
+impl Default for Vm{
+    fn default() -> Self{
+        let mut vm = Vm {
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: HashMap::new(),
+            open_upvalues: Vec::new(),
+            config: InterpreterConfig::default(),
+            steps: 0
+        };
+        vm.define_native("clock", 0, |_|{
+            let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            Ok(VmValue::Number(since_epoch.as_millis() as f64))
+        });
+        vm.define_native("len", 1, |args|{
+            match &args[0]{
+                VmValue::List(list) => Ok(VmValue::Int(list.borrow().len() as i64)),
+                VmValue::Map(map) => Ok(VmValue::Int(map.borrow().entries.len() as i64)),
+                VmValue::String(str) => Ok(VmValue::Int(str.chars().count() as i64)),
+                other => Err(format!("len() expects a list, map or string but got {}", other.type_name()))
+            }
+        });
+        vm.define_native("keys", 1, |args|{
+            let map = ensure_map(&args[0], "keys")?;
+            let keys = map.borrow().entries.iter().map(|entry| entry.0.clone()).collect();
+            Ok(VmValue::List(Rc::new(RefCell::new(keys))))
+        });
+        vm.define_native("values", 1, |args|{
+            let map = ensure_map(&args[0], "values")?;
+            let values = map.borrow().entries.iter().map(|entry| entry.1.clone()).collect();
+            Ok(VmValue::List(Rc::new(RefCell::new(values))))
+        });
+        vm.define_native("has", 2, |args|{
+            let map = ensure_map(&args[0], "has")?;
+            let found = map.borrow().position(&args[1])?.is_some();
+            Ok(VmValue::Bool(found))
+        });
+        vm.define_native("remove", 2, |args|{
+            let map = ensure_map(&args[0], "remove")?;
+            let removed = map.borrow_mut().remove(&args[1])?;
+            Ok(removed.unwrap_or(VmValue::Nil))
+        });
+        return vm;
+    }
+}
+
+fn ensure_map(value: &VmValue, function: &str) -> Result<Rc<RefCell<VmMap>>, String>{
+    match value{
+        VmValue::Map(map) => Ok(map.clone()),
+        other => Err(format!("{}() expects a map but got {}", function, other.type_name()))
+    }
+}
+
+pub fn interpret(script: Rc<Function>) -> Result<(), VmError>{
+    let mut vm = Vm::default();
+    return vm.run_script(script);
+}
+
+impl Vm{
+    fn define_native(&mut self, name: &str, arity: usize, callable: fn(&[VmValue]) -> Result<VmValue, String>){
+        let native = NativeFunction { name: name.to_string(), arity: arity, callable: callable };
+        self.globals.insert(Rc::from(name), VmValue::Native(Rc::new(native)));
+    }
+
+    pub fn run_script(&mut self, script: Rc<Function>) -> Result<(), VmError>{
+        let closure = Rc::new(Closure { function: script, upvalues: Vec::new() });
+        self.stack.push(VmValue::Closure(closure.clone()));
+        self.call(closure, 0)?;
+        let result = self.run();
+        if result.is_err(){
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        return result;
+    }
+
+    fn frame(&self) -> &CallFrame{
+        return self.frames.last().unwrap();
+    }
+
+    fn constant(&self, index: usize) -> &Constant{
+        return &self.frame().closure.function.chunk.constants[index];
+    }
+
+    fn name_constant(&self, index: usize) -> Rc<str>{
+        match self.constant(index){
+            Constant::String(name) => name.clone(),
+            other => panic!("Expected a name constant but found {:?}", other)
+        }
+    }
+
+    fn position(&self) -> (usize, i64){
+        let frame = self.frame();
+        return frame.closure.function.chunk.positions[frame.ip - 1];
+    }
+
+    fn error(&self, message: String) -> VmError{
+        let (line, column) = self.position();
+        return VmError {
+            message: format!("{} at line: {}, column: {}", message, line, column),
+            line: line,
+            column: column,
+            kind: ErrorKind::Runtime
+        };
+    }
+
+    fn pop(&mut self) -> VmValue{
+        return self.stack.pop().unwrap();
+    }
+
+    fn peek(&self, distance: usize) -> &VmValue{
+        return &self.stack[self.stack.len() - 1 - distance];
+    }
+
+    fn run(&mut self) -> Result<(), VmError>{
+        loop{
+            self.steps += 1;
+            self.config.check_step(self.steps).map_err(|(kind, message)| VmError::aborted(kind, message))?;
+            let op = {
+                let frame = self.frames.last_mut().unwrap();
+                let op = frame.closure.function.chunk.code[frame.ip];
+                frame.ip += 1;
+                op
+            };
+            match op{
+                OpCode::Constant(index) => {
+                    let value = match self.constant(index){
+                        Constant::Number(num) => VmValue::Number(*num),
+                        Constant::Int(num) => VmValue::Int(*num),
+                        Constant::String(str) => VmValue::String(str.clone()),
+                        Constant::Function(_) => panic!("Functions are loaded through Closure")
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::Nil => self.stack.push(VmValue::Nil),
+                OpCode::True => self.stack.push(VmValue::Bool(true)),
+                OpCode::False => self.stack.push(VmValue::Bool(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal(slot) => {
+                    let value = self.stack[self.frame().base + slot].clone();
+                    self.stack.push(value);
+                }
+                OpCode::SetLocal(slot) => {
+                    let index = self.frame().base + slot;
+                    self.stack[index] = self.peek(0).clone();
+                }
+                OpCode::GetGlobal(index) => {
+                    let name = self.name_constant(index);
+                    match self.globals.get(&name){
+                        Some(value) => {
+                            let value = value.clone();
+                            self.stack.push(value);
+                        }
+                        None => return Err(self.error(format!("use of undefined and undeclared variable '{}'", name)))
+                    }
+                }
+                OpCode::DefineGlobal(index) => {
+                    let name = self.name_constant(index);
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+                OpCode::SetGlobal(index) => {
+                    let name = self.name_constant(index);
+                    if !self.globals.contains_key(&name){
+                        return Err(self.error(format!("Attempting to assign undefined variable '{}'", name)));
+                    }
+                    let value = self.peek(0).clone();
+                    self.globals.insert(name, value);
+                }
+                OpCode::GetUpvalue(index) => {
+                    let upvalue = self.frame().closure.upvalues[index].clone();
+                    let value = match &*upvalue.borrow(){
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone()
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::SetUpvalue(index) => {
+                    let upvalue = self.frame().closure.upvalues[index].clone();
+                    let value = self.peek(0).clone();
+                    let open_slot = match &mut *upvalue.borrow_mut(){
+                        Upvalue::Open(slot) => Some(*slot),
+                        Upvalue::Closed(closed) => {
+                            *closed = value.clone();
+                            None
+                        }
+                    };
+                    if let Some(slot) = open_slot{
+                        self.stack[slot] = value;
+                    }
+                }
+                OpCode::GetProperty(index) => {
+                    let name = self.name_constant(index);
+                    let instance = match self.peek(0){
+                        VmValue::Instance(instance) => instance.clone(),
+                        _ => return Err(self.error(format!("Only objects have properties")))
+                    };
+                    let field = instance.fields.borrow().get(&name).cloned();
+                    match field{
+                        Some(value) => {
+                            self.pop();
+                            self.stack.push(value);
+                        }
+                        None => self.bind_method(instance.class.clone(), &name)?
+                    }
+                }
+                OpCode::SetProperty(index) => {
+                    let name = self.name_constant(index);
+                    let value = self.pop();
+                    match self.pop(){
+                        VmValue::Instance(instance) => {
+                            instance.fields.borrow_mut().insert(name, value.clone());
+                            self.stack.push(value);
+                        }
+                        _ => return Err(self.error(format!("Only objects have properties")))
+                    }
+                }
+                OpCode::GetSuper(index) => {
+                    let name = self.name_constant(index);
+                    match self.pop(){
+                        VmValue::Class(superclass) => self.bind_method(superclass, &name)?,
+                        _ => return Err(self.error(format!("Cannot access super method")))
+                    }
+                }
+                OpCode::GetIndex => {
+                    let index = self.pop();
+                    let object = self.pop();
+                    let value = self.get_index(object, index)?;
+                    self.stack.push(value);
+                }
+                OpCode::SetIndex => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let object = self.pop();
+                    self.set_index(object, index, value.clone())?;
+                    self.stack.push(value);
+                }
+                OpCode::Equal => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(VmValue::Bool(left.is_equal(&right)));
+                }
+                OpCode::NotEqual => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(VmValue::Bool(!left.is_equal(&right)));
+                }
+                OpCode::Greater => self.binary(BinaryOpType::Greater)?,
+                OpCode::GreaterEqual => self.binary(BinaryOpType::GreaterEqual)?,
+                OpCode::Less => self.binary(BinaryOpType::Less)?,
+                OpCode::LessEqual => self.binary(BinaryOpType::LessEqual)?,
+                OpCode::Add => self.binary(BinaryOpType::Plus)?,
+                OpCode::Subtract => self.binary(BinaryOpType::Minus)?,
+                OpCode::Multiply => self.binary(BinaryOpType::Star)?,
+                OpCode::Divide => self.binary(BinaryOpType::Slash)?,
+                OpCode::Modulo => self.binary(BinaryOpType::Mod)?,
+                OpCode::IntDivide => self.binary(BinaryOpType::IntDivide)?,
+                OpCode::Power => self.binary(BinaryOpType::Power)?,
+                OpCode::BitAnd => self.binary(BinaryOpType::BitAnd)?,
+                OpCode::BitOr => self.binary(BinaryOpType::BitOr)?,
+                OpCode::BitXor => self.binary(BinaryOpType::BitXor)?,
+                OpCode::ShiftLeft => self.binary(BinaryOpType::ShiftLeft)?,
+                OpCode::ShiftRight => self.binary(BinaryOpType::ShiftRight)?,
+                OpCode::BitNot => {
+                    let value = self.pop();
+                    let num = self.bitwise_operand(value)?;
+                    self.stack.push(VmValue::Int(!num));
+                }
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.stack.push(VmValue::Bool(!value.is_truthy()));
+                }
+                OpCode::Negate => {
+                    match self.pop(){
+                        VmValue::Number(num) => self.stack.push(VmValue::Number(-num)),
+                        VmValue::Int(num) => {
+                            match num.checked_neg(){
+                                Some(negated) => self.stack.push(VmValue::Int(negated)),
+                                None => return Err(self.error(format!("Integer overflow")))
+                            }
+                        }
+                        other => return Err(self.error(format!("Incorrect use of unary operator {:?} on object of type {:?}",
+                        UnaryOpType::Minus, other.type_name())))
+                    }
+                }
+                OpCode::Print => {
+                    let value = self.pop();
+                    println!("{}", value.to_string());
+                }
+                OpCode::Jump(target) | OpCode::Loop(target) => {
+                    self.frames.last_mut().unwrap().ip = target;
+                }
+                OpCode::JumpIfFalse(target) => {
+                    if !self.peek(0).is_truthy(){
+                        self.frames.last_mut().unwrap().ip = target;
+                    }
+                }
+                OpCode::Call(arg_count) => {
+                    let callee = self.peek(arg_count).clone();
+                    self.call_value(callee, arg_count)?;
+                }
+                OpCode::Closure(index) => {
+                    let function = match self.constant(index){
+                        Constant::Function(function) => function.clone(),
+                        other => panic!("Expected a function constant but found {:?}", other)
+                    };
+                    let mut upvalues = Vec::with_capacity(function.upvalues.len());
+                    for upvalue in function.upvalues.iter(){
+                        if upvalue.is_local{
+                            let slot = self.frame().base + upvalue.index;
+                            upvalues.push(self.capture_upvalue(slot));
+                        }
+                        else{
+                            upvalues.push(self.frame().closure.upvalues[upvalue.index].clone());
+                        }
+                    }
+                    self.stack.push(VmValue::Closure(Rc::new(Closure { function: function, upvalues: upvalues })));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().unwrap();
+                    self.close_upvalues(frame.base);
+                    self.stack.truncate(frame.base);
+                    if self.frames.is_empty(){
+                        return Ok(());
+                    }
+                    self.stack.push(result);
+                }
+                OpCode::Class(index) => {
+                    let name = self.name_constant(index);
+                    let class = Class { name: name.to_string(), methods: RefCell::new(HashMap::new()) };
+                    self.stack.push(VmValue::Class(Rc::new(class)));
+                }
+                OpCode::Inherit => {
+                    let superclass = match self.peek(1){
+                        VmValue::Class(superclass) => superclass.clone(),
+                        _ => return Err(self.error(format!("Superclass must be a class")))
+                    };
+                    if let VmValue::Class(subclass) = self.peek(0){
+                        //Copy the methods down now, the subclass's own methods are added after and win
+                        let inherited = superclass.methods.borrow().clone();
+                        subclass.methods.borrow_mut().extend(inherited);
+                    }
+                    self.pop();
+                }
+                OpCode::Method(index) => {
+                    let name = self.name_constant(index);
+                    let method = match self.pop(){
+                        VmValue::Closure(closure) => closure,
+                        other => panic!("Expected a method closure but found {:?}", other)
+                    };
+                    if let VmValue::Class(class) = self.peek(0){
+                        class.methods.borrow_mut().insert(name, method);
+                    }
+                }
+                OpCode::BuildList(count) => {
+                    let elements = self.stack.split_off(self.stack.len() - count);
+                    self.stack.push(VmValue::List(Rc::new(RefCell::new(elements))));
+                }
+                OpCode::BuildMap(count) => {
+                    let flat = self.stack.split_off(self.stack.len() - count * 2);
+                    let mut map = VmMap::default();
+                    let mut items = flat.into_iter();
+                    while let (Some(key), Some(value)) = (items.next(), items.next()){
+                        if let Err(err) = map.insert(key, value){
+                            return Err(self.error(err));
+                        }
+                    }
+                    self.stack.push(VmValue::Map(Rc::new(RefCell::new(map))));
+                }
+                OpCode::ToString => {
+                    let method = match self.peek(0){
+                        VmValue::Instance(instance) => instance.class.methods.borrow().get("toString").cloned(),
+                        _ => None
+                    };
+                    //The instance is already in the receiver slot, the result replaces it on return
+                    if let Some(method) = method{
+                        if method.function.arity == 0{
+                            self.call(method, 0)?;
+                        }
+                    }
+                }
+                OpCode::BuildString(count) => {
+                    let parts = self.stack.split_off(self.stack.len() - count);
+                    let text: String = parts.iter().map(|part| part.to_string()).collect();
+                    self.stack.push(VmValue::String(Rc::from(text)));
+                }
+            }
+        }
+    }
+
+    fn binary(&mut self, operator: BinaryOpType) -> Result<(), VmError>{
+        let right = self.pop();
+        let left = self.pop();
+        if operator.is_bitwise(){
+            let num1 = self.bitwise_operand(left)?;
+            let num2 = self.bitwise_operand(right)?;
+            match Value::bitwise(operator, num1, num2){
+                Ok(result) => self.stack.push(VmValue::Int(result)),
+                Err(message) => return Err(self.error(message))
+            }
+            return Ok(());
+        }
+        if let (VmValue::Int(num1), VmValue::Int(num2)) = (&left, &right){
+            if let Some(result) = self.int_binary(operator, *num1, *num2)?{
+                self.stack.push(result);
+                return Ok(());
+            }
+        }
+        //Same promotion as the tree-walker: an Int mixed with a float becomes a float
+        let (left, right) = match (left, right){
+            (VmValue::Int(num1), VmValue::Number(num2)) => (VmValue::Number(num1 as f64), VmValue::Number(num2)),
+            (VmValue::Number(num1), VmValue::Int(num2)) => (VmValue::Number(num1), VmValue::Number(num2 as f64)),
+            (left, right) => (left, right)
+        };
+        let result = match (operator, &left, &right){
+            (BinaryOpType::Plus, VmValue::Number(num1), VmValue::Number(num2)) => VmValue::Number(num1 + num2),
+            (BinaryOpType::Plus, VmValue::String(str1), VmValue::String(str2)) => {
+                VmValue::String(Rc::from(format!("{}{}", str1, str2)))
+            }
+            (BinaryOpType::Minus, VmValue::Number(num1), VmValue::Number(num2)) => VmValue::Number(num1 - num2),
+            (BinaryOpType::Star, VmValue::Number(num1), VmValue::Number(num2)) => VmValue::Number(num1 * num2),
+            (BinaryOpType::Slash, VmValue::Number(num1), VmValue::Number(num2)) => {
+                if *num2 == 0.0{
+                    return Err(self.error(format!("Divide by zero error")));
+                }
+                VmValue::Number(num1 / num2)
+            }
+            (BinaryOpType::Mod, VmValue::Number(num1), VmValue::Number(num2)) => {
+                if *num2 == 0.0{
+                    return Err(self.error(format!("Modulo by zero error")));
+                }
+                VmValue::Number(num1 % num2)
+            }
+            (BinaryOpType::Power, VmValue::Number(num1), VmValue::Number(num2)) => VmValue::Number(num1.powf(*num2)),
+            (BinaryOpType::IntDivide, VmValue::Number(num1), VmValue::Number(num2)) => {
+                if *num2 == 0.0{
+                    return Err(self.error(format!("Divide by zero error")));
+                }
+                let quotient = (num1 / num2).trunc();
+                if !quotient.is_finite() || quotient < i64::MIN as f64 || quotient >= i64::MAX as f64{
+                    return Err(self.error(format!("Integer overflow")));
+                }
+                VmValue::Int(quotient as i64)
+            }
+            (BinaryOpType::Greater, VmValue::Number(num1), VmValue::Number(num2)) => VmValue::Bool(num1 > num2),
+            (BinaryOpType::GreaterEqual, VmValue::Number(num1), VmValue::Number(num2)) => VmValue::Bool(num1 >= num2),
+            (BinaryOpType::Less, VmValue::Number(num1), VmValue::Number(num2)) => VmValue::Bool(num1 < num2),
+            (BinaryOpType::LessEqual, VmValue::Number(num1), VmValue::Number(num2)) => VmValue::Bool(num1 <= num2),
+            (_, _, _) => return Err(self.error(format!("Incorrect use of unary operator {:?} on objects of type {:?} and {:?}",
+            operator, left.type_name(), right.type_name())))
+        };
+        self.stack.push(result);
+        return Ok(());
+    }
+
+    //Mirrors Interpreter::int_binary, None means the operator is not arithmetic or a comparison
+    fn int_binary(&self, operator: BinaryOpType, num1: i64, num2: i64) -> Result<Option<VmValue>, VmError>{
+        let result = match operator{
+            BinaryOpType::Plus => num1.checked_add(num2),
+            BinaryOpType::Minus => num1.checked_sub(num2),
+            BinaryOpType::Star => num1.checked_mul(num2),
+            BinaryOpType::Slash => {
+                if num2 == 0{
+                    return Err(self.error(format!("Divide by zero error")));
+                }
+                return Ok(Some(VmValue::Number(num1 as f64 / num2 as f64)));
+            }
+            BinaryOpType::IntDivide => {
+                if num2 == 0{
+                    return Err(self.error(format!("Divide by zero error")));
+                }
+                num1.checked_div(num2)
+            }
+            BinaryOpType::Mod => {
+                if num2 == 0{
+                    return Err(self.error(format!("Modulo by zero error")));
+                }
+                num1.checked_rem(num2)
+            }
+            BinaryOpType::Power => {
+                if num2 < 0{
+                    return Ok(Some(VmValue::Number((num1 as f64).powf(num2 as f64))));
+                }
+                u32::try_from(num2).ok().and_then(|exponent| num1.checked_pow(exponent))
+            }
+            BinaryOpType::Greater => return Ok(Some(VmValue::Bool(num1 > num2))),
+            BinaryOpType::GreaterEqual => return Ok(Some(VmValue::Bool(num1 >= num2))),
+            BinaryOpType::Less => return Ok(Some(VmValue::Bool(num1 < num2))),
+            BinaryOpType::LessEqual => return Ok(Some(VmValue::Bool(num1 <= num2))),
+            _ => return Ok(None)
+        };
+        match result{
+            Some(num) => return Ok(Some(VmValue::Int(num))),
+            None => return Err(self.error(format!("Integer overflow")))
+        }
+    }
+
+    fn bitwise_operand(&self, value: VmValue) -> Result<i64, VmError>{
+        let found = match value{
+            VmValue::Int(num) => return Ok(num),
+            VmValue::Number(num) => {
+                if let Some(whole) = Value::whole_number(num){
+                    return Ok(whole);
+                }
+                VmValue::Number(num).to_string()
+            }
+            other => other.type_name()
+        };
+        return Err(self.error(format!("Bitwise operators need integers but got {}", found)));
+    }
+
+    fn call_value(&mut self, callee: VmValue, arg_count: usize) -> Result<(), VmError>{
+        match callee{
+            VmValue::Closure(closure) => return self.call(closure, arg_count),
+            VmValue::BoundMethod(bound) => {
+                let callee_slot = self.stack.len() - 1 - arg_count;
+                self.stack[callee_slot] = bound.receiver.clone();
+                return self.call(bound.method.clone(), arg_count);
+            }
+            VmValue::Class(class) => {
+                let callee_slot = self.stack.len() - 1 - arg_count;
+                let instance = Instance { class: class.clone(), fields: RefCell::new(HashMap::new()) };
+                self.stack[callee_slot] = VmValue::Instance(Rc::new(instance));
+                let initializer = class.methods.borrow().get("init").cloned();
+                match initializer{
+                    Some(initializer) => return self.call(initializer, arg_count),
+                    None if arg_count != 0 => return Err(self.error(format!("Expected {} arguments but got {}", 0, arg_count))),
+                    None => return Ok(())
+                }
+            }
+            VmValue::Native(native) => {
+                if arg_count != native.arity{
+                    return Err(self.error(format!("Expected {} arguments but got {}", native.arity, arg_count)));
+                }
+                let args = self.stack.split_off(self.stack.len() - arg_count);
+                self.pop();
+                match (native.callable)(&args){
+                    Ok(result) => self.stack.push(result),
+                    Err(err) => return Err(self.error(err))
+                }
+                return Ok(());
+            }
+            _ => return Err(self.error(format!("Can only call functions and classes")))
+        }
+    }
+
+    fn call(&mut self, closure: Rc<Closure>, arg_count: usize) -> Result<(), VmError>{
+        if arg_count != closure.function.arity{
+            return Err(self.error(format!("Expected {} arguments but got {}", closure.function.arity, arg_count)));
+        }
+        //The script's own frame is not a call
+        if self.frames.len() > self.config.max_call_depth{
+            return Err(self.error(format!("Stack overflow")));
+        }
+        let base = self.stack.len() - 1 - arg_count;
+        self.frames.push(CallFrame { closure: closure, ip: 0, base: base });
+        return Ok(());
+    }
+
+    //Replaces the receiver on top of the stack with the bound method
+    fn bind_method(&mut self, class: Rc<Class>, name: &str) -> Result<(), VmError>{
+        let method = match class.methods.borrow().get(name){
+            Some(method) => method.clone(),
+            None => return Err(self.error(format!("Undefined property '{}'", name)))
+        };
+        let receiver = self.pop();
+        self.stack.push(VmValue::BoundMethod(Rc::new(BoundMethod { receiver: receiver, method: method })));
+        return Ok(());
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>>{
+        let mut insert_at = self.open_upvalues.len();
+        for (position, upvalue) in self.open_upvalues.iter().enumerate().rev(){
+            match *upvalue.borrow(){
+                Upvalue::Open(open_slot) if open_slot == slot => return upvalue.clone(),
+                Upvalue::Open(open_slot) if open_slot < slot => break,
+                _ => insert_at = position
+            }
+        }
+        let created = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.insert(insert_at, created.clone());
+        return created;
+    }
+
+    fn close_upvalues(&mut self, from: usize){
+        while let Some(upvalue) = self.open_upvalues.last(){
+            let slot = match *upvalue.borrow(){
+                Upvalue::Open(slot) if slot >= from => slot,
+                _ => break
+            };
+            let upvalue = self.open_upvalues.pop().unwrap();
+            *upvalue.borrow_mut() = Upvalue::Closed(self.stack[slot].clone());
+        }
+    }
+
+    fn list_position(&self, length: usize, index: &VmValue) -> Result<usize, VmError>{
+        match index{
+            VmValue::Int(num) => {
+                if *num < 0 || *num >= length as i64{
+                    return Err(self.error(format!("Index {} out of bounds for list of length {}", num, length)));
+                }
+                return Ok(*num as usize);
+            }
+            VmValue::Number(num) if num.fract() == 0.0 => {
+                if *num < 0.0 || *num >= length as f64{
+                    return Err(self.error(format!("Index {} out of bounds for list of length {}", num, length)));
+                }
+                return Ok(*num as usize);
+            }
+            _ => return Err(self.error(format!("List index must be a whole number, found {}", index.to_string())))
+        }
+    }
+
+    fn get_index(&self, object: VmValue, index: VmValue) -> Result<VmValue, VmError>{
+        match object{
+            VmValue::List(list) => {
+                let position = self.list_position(list.borrow().len(), &index)?;
+                return Ok(list.borrow()[position].clone());
+            }
+            VmValue::Map(map) => {
+                match map.borrow().get(&index){
+                    Ok(Some(value)) => return Ok(value),
+                    Ok(None) => return Err(self.error(format!("Undefined key '{}'", index.to_string()))),
+                    Err(err) => return Err(self.error(err))
+                }
+            }
+            _ => return Err(self.error(format!("Can only index into lists and maps, found {}", object.type_name())))
+        }
+    }
+
+    fn set_index(&self, object: VmValue, index: VmValue, value: VmValue) -> Result<(), VmError>{
+        match object{
+            VmValue::List(list) => {
+                let position = self.list_position(list.borrow().len(), &index)?;
+                list.borrow_mut()[position] = value;
+                return Ok(());
+            }
+            VmValue::Map(map) => {
+                let inserted = map.borrow_mut().insert(index, value);
+                return inserted.map_err(|err| self.error(err));
+            }
+            _ => return Err(self.error(format!("Can only index into lists and maps, found {}", object.type_name())))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile;
+    use crate::interpreter::{Interpreter, InterruptFlag};
+    use crate::parser::parse_begin;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+    use std::time::{Duration, Instant};
+
+    fn run_vm(source: &str) -> Result<Vm, String> {
+        let mut scanner = Scanner::default();
+        let tokens = scanner.scan_tokens(source.to_string())
+        .map_err(|errs| errs.iter().map(|err| err.error.clone()).collect::<Vec<String>>().join("\n"))?;
+        let stmts = parse_begin(tokens).map_err(|err| err.return_error())?;
+        let mut resolver = Resolver::new(Interpreter::new(stmts.clone()));
+        let (resolved, _) = resolver.resolve(stmts.clone());
+        resolved.map_err(|errs| errs.join("\n"))?;
+        let script = compile(&stmts).map_err(|err| err.return_error())?;
+        let mut vm = Vm::default();
+        vm.run_script(script).map_err(|err| err.return_error())?;
+        Ok(vm)
+    }
+
+    fn run_vm_with_config(source: &str, config: InterpreterConfig) -> Result<(), VmError> {
+        let mut scanner = Scanner::default();
+        let stmts = parse_begin(scanner.scan_tokens(source.to_string()).unwrap()).unwrap();
+        let mut vm = Vm::default();
+        vm.config = config;
+        return vm.run_script(compile(&stmts).unwrap());
+    }
+
+    fn global(vm: &Vm, name: &str) -> String {
+        match vm.globals.get(name) {
+            Some(value) => value.to_string(),
+            None => panic!("Could not read '{}'", name),
+        }
+    }
+
+    #[test]
+    fn arithmetic_and_strings() {
+        let vm = run_vm("var a = (1 + 2) * 3 - 4 / 2 % 5;\nvar b = \"foo\" + \"bar\";\nvar c = !nil == true;").unwrap();
+        assert_eq!(global(&vm, "a"), "7");
+        assert_eq!(global(&vm, "b"), "foobar");
+        assert_eq!(global(&vm, "c"), "true");
+    }
+
+    #[test]
+    fn closures_share_captured_variable() {
+        let source = "
+            var get; var set;
+            fun make() {
+              var value = 1;
+              fun g() { return value; }
+              fun s(v) { value = v; }
+              get = g; set = s;
+            }
+            make();
+            set(42);
+            var seen = get();";
+        let vm = run_vm(source).unwrap();
+        assert_eq!(global(&vm, "seen"), "42");
+    }
+
+    #[test]
+    fn loop_closures_capture_each_iteration() {
+        let source = "
+            var first; var second;
+            for (var i = 0; i < 3; i = i + 1) {
+              var j = i;
+              if (i == 0) { first = lambda () j; continue; }
+              second = lambda () j;
+              break;
+            }
+            var a = first(); var b = second();";
+        let vm = run_vm(source).unwrap();
+        assert_eq!(global(&vm, "a"), "0");
+        assert_eq!(global(&vm, "b"), "1");
+    }
+
+    #[test]
+    fn classes_inheritance_and_initializers() {
+        let source = "
+            class A { init(x) { this.x = x; } name() { return \"A\" + this.x; } }
+            class B < A { init(x) { super.init(x + \"!\"); } name() { return \"B\" + super.name(); } }
+            var b = B(\"hi\");
+            var n = b.name();
+            var again = b.init(\"yo\").x;";
+        let vm = run_vm(source).unwrap();
+        assert_eq!(global(&vm, "n"), "BAhi!");
+        assert_eq!(global(&vm, "again"), "yo!");
+        assert_eq!(global(&vm, "b"), "B instance");
+    }
+
+    #[test]
+    fn lists_maps_and_natives() {
+        let source = "
+            var l = [1, 2, 3]; l[1] = 20;
+            var m = {\"a\": 1}; m[\"b\"] = l;
+            var k = keys(m); var n = len(l) + len(m);
+            remove(m, \"a\");";
+        let vm = run_vm(source).unwrap();
+        assert_eq!(global(&vm, "l"), "[1, 20, 3]");
+        assert_eq!(global(&vm, "k"), "[a, b]");
+        assert_eq!(global(&vm, "n"), "5");
+        assert_eq!(global(&vm, "m"), "{b: [1, 20, 3]}");
+    }
+
+    #[test]
+    fn runtime_errors_match_tree_walker() {
+        let err = run_vm("fun f() {\n  var x = 1 / 0;\n}\nf();").err().unwrap();
+        assert_eq!(err, "Divide by zero error at line: 2, column: 13");
+
+        let err = run_vm("var l = [1];\nprint l[4];").err().unwrap();
+        assert_eq!(err, "Index 4 out of bounds for list of length 1 at line: 2, column: 8");
+
+        let err = run_vm("class A {}\nA().missing;").err().unwrap();
+        assert_eq!(err, "Undefined property 'missing' at line: 2, column: 5");
+    }
+
+    #[test]
+    fn string_interpolation() {
+        let source = "
+            class Point {
+              init(x, y) { this.x = x; this.y = y; }
+              toString() { return \"(${this.x}, ${this.y})\"; }
+            }
+            var n = 3;
+            var a = \"n is ${n}, list ${[n, nil]}\";
+            var b = \"at ${Point(1, n)} and ${ {\"k\": n}[\"k\"] }\";";
+        let vm = run_vm(source).unwrap();
+        assert_eq!(global(&vm, "a"), "n is 3, list [3, nil]");
+        assert_eq!(global(&vm, "b"), "at (1, 3) and 3");
+    }
+
+    #[test]
+    fn int_arithmetic_matches_tree_walker() {
+        let source = "var a = 7 + 2 * 3;\nvar b = 7 / 2;\nvar c = -7 ~/ 2;\nvar d = 7.5 ~/ 2;\nvar e = 1 == 1.0;\nvar f = 9007199254740993;\nvar g = len([1, 2]) + 0.5;";
+        let vm = run_vm(source).unwrap();
+        assert_eq!(global(&vm, "a"), "13");
+        assert_eq!(global(&vm, "b"), "3.5");
+        assert_eq!(global(&vm, "c"), "-3");
+        assert_eq!(global(&vm, "d"), "3");
+        assert_eq!(global(&vm, "e"), "true");
+        assert_eq!(global(&vm, "f"), "9007199254740993");
+        assert_eq!(global(&vm, "g"), "2.5");
+
+        let err = run_vm("var x = 9223372036854775807;\nx + 1;").err().unwrap();
+        assert_eq!(err, "Integer overflow at line: 2, column: 3");
+    }
+
+    #[test]
+    fn exceptions_are_rejected_at_compile_time() {
+        let err = run_vm("var x = 1;\ntry { x = 2; } catch (e) { }").err().unwrap();
+        assert_eq!(err, "Exceptions are not supported by the VM at line: 2, column: 1");
+    }
+
+    #[test]
+    fn bitwise_and_power_operators() {
+        let source = "var a = (12 & 10) | (1 << 4);\nvar b = ~5 ^ 3;\nvar c = -16 >> 2;\nvar d = -2 ** 2;\nvar e = 2 ** -1;\nvar f = 6.0 & 3;";
+        let vm = run_vm(source).unwrap();
+        assert_eq!(global(&vm, "a"), "24");
+        assert_eq!(global(&vm, "b"), "-7");
+        assert_eq!(global(&vm, "c"), "-4");
+        assert_eq!(global(&vm, "d"), "-4");
+        assert_eq!(global(&vm, "e"), "0.5");
+        assert_eq!(global(&vm, "f"), "2");
+
+        let err = run_vm("var x = 2.5;\nx & 1;").err().unwrap();
+        assert_eq!(err, "Bitwise operators need integers but got 2.5 at line: 2, column: 3");
+    }
+
+    #[test]
+    fn deep_recursion_overflows_cleanly() {
+        let err = run_vm("fun f(n) { return f(n + 1); }\nf(0);").err().unwrap();
+        assert_eq!(err, "Stack overflow at line: 1, column: 26");
+    }
+
+    #[test]
+    fn config_limits_apply_to_the_vm() {
+        let config = InterpreterConfig { max_steps: Some(1000), ..InterpreterConfig::default() };
+        let err = run_vm_with_config("while (true) {}", config).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::StepLimit);
+        assert_eq!(err.return_error(), "Step limit of 1000 exceeded");
+
+        let config = InterpreterConfig { deadline: Some(Instant::now() + Duration::from_millis(20)), ..InterpreterConfig::default() };
+        let err = run_vm_with_config("while (true) {}", config).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::Timeout);
+
+        let interrupt = InterruptFlag::default();
+        interrupt.interrupt();
+        let config = InterpreterConfig { interrupt: Some(interrupt), ..InterpreterConfig::default() };
+        let err = run_vm_with_config("print 1;", config).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::Interrupted);
+
+        let config = InterpreterConfig { max_call_depth: 20, ..InterpreterConfig::default() };
+        assert!(run_vm_with_config("fun down(n) { if (n == 0) return 0; return down(n - 1); }\ndown(19);", config.clone()).is_ok());
+        let err = run_vm_with_config("fun down(n) { if (n == 0) return 0; return down(n - 1); }\ndown(20);", config).err().unwrap();
+        assert_eq!(err.return_error(), "Stack overflow at line: 1, column: 54");
+    }
+}